@@ -0,0 +1,78 @@
+//! Wallet analyzer: learns each tracked wallet's habits from its stitched
+//! trades into `wallet_patterns`, and who follows whom into `follow_edges`
+//! (run the stitcher first)
+//!
+//!   cargo run -p analyzer            # recompute every `analyzer.interval_secs`
+//!   cargo run -p analyzer -- --once  # one pass
+
+use anyhow::{bail, Result};
+use common::config::{AnalyzerConfig, Config};
+use common::logging;
+use db::follow;
+use db::migrations::{MigrationMode, Schema};
+use db::patterns;
+use tokio::time::{interval, Duration};
+use tracing::{error, info};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    logging::init();
+
+    let mode = std::env::args().nth(1);
+    let config = Config::load("configs/config.example.toml")?;
+    let migration_mode = if config.database.migrate_check_only {
+        MigrationMode::Check
+    } else {
+        MigrationMode::Apply
+    };
+    let pool = db::connect(Some(&config.database.url), Schema::Main, migration_mode).await?;
+    let settings = &config.analyzer;
+    let excluded_roles = config.roles_where(|r| r.exclude_from_leaders);
+
+    match mode.as_deref() {
+        Some("--once") => run_pass(&pool, settings, &excluded_roles).await?,
+        None => {
+            let period = Duration::from_secs(settings.interval_secs.max(1));
+            info!("🧠 Analyzing wallets over {}d every {}s", settings.window_days, period.as_secs());
+            let mut ticker = interval(period);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = tokio::signal::ctrl_c() => {
+                        info!("🛑 Received Ctrl+C, stopping");
+                        break;
+                    }
+                }
+                if let Err(e) = run_pass(&pool, settings, &excluded_roles).await {
+                    error!("Analyzer pass failed: {}", e);
+                }
+            }
+        }
+        Some(other) => bail!("Unknown argument '{}' (expected --once)", other),
+    }
+
+    Ok(())
+}
+
+/// Patterns first, so the follow job's scores land on fresh rows
+async fn run_pass(pool: &db::Pool, settings: &AnalyzerConfig, excluded_roles: &[String]) -> Result<()> {
+    let stats = patterns::analyze_tracked_wallets(pool, settings.window_days).await?;
+    info!(
+        "🧠 Updated {} of {} wallet patterns from {} trades in the last {}d",
+        stats.patterns, stats.wallets, stats.trades, settings.window_days
+    );
+
+    let stats = follow::detect_follow_edges(
+        pool,
+        settings.window_days,
+        settings.follow_max_lag_ms,
+        settings.follow_min_mints,
+        excluded_roles,
+    )
+    .await?;
+    info!(
+        "🧠 Found {} follow edges among {} tracked BUYs, {} entries credited to a leader",
+        stats.edges, stats.entries, stats.leaders_backfilled
+    );
+    Ok(())
+}
@@ -0,0 +1,259 @@
+//! Wallet management for the copytrader database
+//!
+//!   cargo run -p cli -- wallets add <pubkey> [--alias NAME] [--notes TEXT] [--role ROLE]...
+//!   cargo run -p cli -- wallets remove <pubkey>
+//!   cargo run -p cli -- wallets alias <pubkey> <alias>
+//!   cargo run -p cli -- wallets track|untrack <pubkey>
+//!   cargo run -p cli -- wallets roles <pubkey> [add|remove <role>...]
+//!   cargo run -p cli -- wallets list [--all]
+//!   cargo run -p cli -- wallets import sqlite [path]   # default: sources.sqlite_wallets_path
+//!   cargo run -p cli -- wallets import csv <path>      # wallet[,alias[,role;role...]]
+//!   cargo run -p cli -- watch add <mint> [--note TEXT]  # record every trade on the mint
+//!   cargo run -p cli -- watch remove <mint>
+//!   cargo run -p cli -- watch list
+
+use anyhow::{bail, Context, Result};
+use common::{config::Config, logging};
+use db::migrations::{MigrationMode, Schema};
+use db::wallets::{self, ImportRow};
+use db::watchlist;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+use sqlx::Row;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+const USAGE: &str = "usage: cli wallets <add|remove|alias|track|untrack|roles|list|import> ...\n       cli watch <add|remove|list> ...";
+
+#[derive(Debug)]
+enum Command {
+    Add { wallet: String, alias: Option<String>, notes: Option<String>, roles: Vec<String> },
+    Remove(String),
+    Alias(String, String),
+    Track(String, bool),
+    Roles { wallet: String, add: Vec<String>, remove: Vec<String> },
+    List { all: bool },
+    ImportSqlite(Option<String>),
+    ImportCsv(String),
+    Watch { mint: String, note: Option<String> },
+    Unwatch(String),
+    WatchList,
+}
+
+fn parse(args: &[String]) -> Result<Command> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.split_first() {
+        Some((&"wallets", rest)) => parse_wallets(rest),
+        Some((&"watch", rest)) => parse_watch(rest),
+        _ => bail!(USAGE),
+    }
+}
+
+fn parse_watch(rest: &[&str]) -> Result<Command> {
+    Ok(match rest {
+        ["add", mint] => Command::Watch { mint: mint.to_string(), note: None },
+        ["add", mint, "--note", note] => Command::Watch { mint: mint.to_string(), note: Some(note.to_string()) },
+        ["remove", mint] => Command::Unwatch(mint.to_string()),
+        ["list"] => Command::WatchList,
+        _ => bail!(USAGE),
+    })
+}
+
+fn parse_wallets(rest: &[&str]) -> Result<Command> {
+    let wallet = |i: usize| -> Result<String> {
+        let wallet = rest.get(i).context("missing wallet address")?;
+        wallets::validate_pubkey(wallet)?;
+        Ok(wallet.to_string())
+    };
+
+    Ok(match rest {
+        ["add", ..] => {
+            let wallet = wallet(1)?;
+            let (mut alias, mut notes, mut roles) = (None, None, Vec::new());
+            let mut flags = rest[2..].iter();
+            while let Some(flag) = flags.next() {
+                let value = flags.next().with_context(|| format!("{} needs a value", flag))?.to_string();
+                match *flag {
+                    "--alias" => alias = Some(value),
+                    "--notes" => notes = Some(value),
+                    "--role" => roles.push(value),
+                    other => bail!("unknown option '{}' for add", other),
+                }
+            }
+            Command::Add { wallet, alias, notes, roles }
+        }
+        ["remove", _] => Command::Remove(wallet(1)?),
+        ["alias", _, alias] => Command::Alias(wallet(1)?, alias.to_string()),
+        ["track", _] => Command::Track(wallet(1)?, true),
+        ["untrack", _] => Command::Track(wallet(1)?, false),
+        ["roles", _] => Command::Roles { wallet: wallet(1)?, add: vec![], remove: vec![] },
+        ["roles", _, op @ ("add" | "remove"), roles @ ..] if !roles.is_empty() => {
+            let roles: Vec<String> = roles.iter().map(|r| r.to_string()).collect();
+            let wallet = wallet(1)?;
+            if *op == "add" {
+                Command::Roles { wallet, add: roles, remove: vec![] }
+            } else {
+                Command::Roles { wallet, add: vec![], remove: roles }
+            }
+        }
+        ["list"] => Command::List { all: false },
+        ["list", "--all"] => Command::List { all: true },
+        ["import", "sqlite"] => Command::ImportSqlite(None),
+        ["import", "sqlite", path] => Command::ImportSqlite(Some(path.to_string())),
+        ["import", "csv", path] => Command::ImportCsv(path.to_string()),
+        _ => bail!(USAGE),
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    logging::init();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = parse(&args)?;
+
+    let config = Config::load("configs/config.example.toml")?;
+    let migration_mode = if config.database.migrate_check_only {
+        MigrationMode::Check
+    } else {
+        MigrationMode::Apply
+    };
+    let pool = db::connect(Some(&config.database.url), Schema::Main, migration_mode).await?;
+
+    match command {
+        Command::Add { wallet, alias, notes, roles } => {
+            wallets::add(&pool, &wallet, alias.as_deref(), notes.as_deref()).await?;
+            wallets::add_roles(&pool, &wallet, &roles).await?;
+            info!("➕ Tracking {}", wallet);
+        }
+        Command::Remove(wallet) => {
+            if !wallets::remove(&pool, &wallet).await? {
+                bail!("{} is not in wallets", wallet);
+            }
+            info!("🗑️  Removed {}", wallet);
+        }
+        Command::Alias(wallet, alias) => {
+            if !wallets::set_alias(&pool, &wallet, Some(&alias)).await? {
+                bail!("{} is not in wallets", wallet);
+            }
+            info!("🏷️  {} is now '{}'", wallet, alias);
+        }
+        Command::Track(wallet, tracked) => {
+            if !wallets::set_tracked(&pool, &wallet, tracked).await? {
+                bail!("{} is not in wallets", wallet);
+            }
+            info!("{} {}", if tracked { "➕ Tracking" } else { "➖ Stopped tracking" }, wallet);
+        }
+        Command::Roles { wallet, add, remove } => {
+            wallets::add_roles(&pool, &wallet, &add).await?;
+            wallets::remove_roles(&pool, &wallet, &remove).await?;
+            let entry = wallets::list(&pool, true).await?.into_iter().find(|w| w.wallet == wallet);
+            match entry {
+                Some(entry) => println!("{}: {}", wallet, entry.roles.join(", ")),
+                None => bail!("{} is not in wallets", wallet),
+            }
+        }
+        Command::List { all } => {
+            for w in wallets::list(&pool, all).await? {
+                println!(
+                    "{:<44}  {:<20}  {:<9}  {}",
+                    w.wallet,
+                    w.alias.as_deref().unwrap_or("-"),
+                    match (w.is_tracked, w.auto_promoted) {
+                        (true, true) => "auto",
+                        (true, false) => "tracked",
+                        (false, _) => "untracked",
+                    },
+                    w.roles.join(", ")
+                );
+            }
+        }
+        Command::ImportSqlite(path) => {
+            let path = path.unwrap_or(config.sources.sqlite_wallets_path);
+            let rows = read_wallets_db(&path).await?;
+            report(&path, wallets::import(&pool, &rows).await?);
+        }
+        Command::ImportCsv(path) => {
+            let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))?;
+            report(&path, wallets::import(&pool, &wallets::parse_csv(&text)).await?);
+        }
+        Command::Watch { mint, note } => {
+            watchlist::add(&pool, &mint, note.as_deref()).await?;
+            info!("👁️  Watching {}", mint);
+        }
+        Command::Unwatch(mint) => {
+            if !watchlist::remove(&pool, &mint).await? {
+                bail!("{} is not watched", mint);
+            }
+            info!("🙈 Stopped watching {}", mint);
+        }
+        Command::WatchList => {
+            for w in watchlist::list(&pool).await? {
+                println!("{:<44}  {:<6}  {}", w.mint, w.source, w.note.as_deref().unwrap_or(""));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn report(source: &str, stats: wallets::ImportStats) {
+    for reason in &stats.rejected {
+        warn!("Skipped: {}", reason);
+    }
+    info!(
+        "📥 Imported {} wallets and {} new roles from {} ({} skipped)",
+        stats.wallets,
+        stats.roles,
+        source,
+        stats.rejected.len()
+    );
+}
+
+/// Column names differ between versions of the legacy wallets.db
+async fn pick_column(pool: &SqlitePool, table: &str, candidates: &[&str]) -> Result<Option<String>> {
+    let columns: Vec<String> = sqlx::query(&format!("PRAGMA table_info({})", table))
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(|row| row.get("name"))
+        .collect();
+    Ok(candidates.iter().find(|c| columns.iter().any(|col| col == *c)).map(|c| c.to_string()))
+}
+
+/// Wallets (wallet or wallet_address, name or alias) and wallet_roles of a legacy wallets.db
+async fn read_wallets_db(path: &str) -> Result<Vec<ImportRow>> {
+    let options = SqliteConnectOptions::from_str(&format!("sqlite://{}", path))?.read_only(true);
+    let source = SqlitePool::connect_with(options)
+        .await
+        .with_context(|| format!("Failed to open {}", path))?;
+
+    let wallet_col = pick_column(&source, "wallets", &["wallet_address", "wallet"])
+        .await?
+        .context("wallets table has no wallet or wallet_address column")?;
+    let alias_col = pick_column(&source, "wallets", &["name", "alias"])
+        .await?
+        .context("wallets table must have a 'name' or 'alias' column")?;
+    let mut rows: Vec<ImportRow> = sqlx::query_as::<_, (String, Option<String>)>(&format!(
+        "SELECT {}, {} FROM wallets",
+        wallet_col, alias_col
+    ))
+    .fetch_all(&source)
+    .await?
+    .into_iter()
+    .map(|(wallet, alias)| ImportRow { wallet, alias, roles: vec![] })
+    .collect();
+
+    // wallet_roles is optional
+    if let Some(role_wallet_col) = pick_column(&source, "wallet_roles", &["wallet_address", "wallet"]).await? {
+        let roles: Vec<(String, String)> =
+            sqlx::query_as(&format!("SELECT {}, role FROM wallet_roles", role_wallet_col))
+                .fetch_all(&source)
+                .await?;
+        for (wallet, role) in roles {
+            if let Some(row) = rows.iter_mut().find(|r| r.wallet == wallet) {
+                row.roles.push(role);
+            }
+        }
+    }
+    Ok(rows)
+}
@@ -2,6 +2,7 @@
 
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,6 +14,31 @@ pub struct Config {
     pub risk: RiskConfig,
     #[serde(default)]
     pub execution: ExecutionConfig,
+    #[serde(default)]
+    pub pipeline: PipelineConfig,
+    #[serde(default)]
+    pub stitcher: StitcherConfig,
+    #[serde(default)]
+    pub analyzer: AnalyzerConfig,
+    #[serde(default)]
+    pub scoring: ScoringConfig,
+    #[serde(default)]
+    pub classification: ClassificationConfig,
+    #[serde(default)]
+    pub promotion: PromotionConfig,
+    #[serde(default)]
+    pub sources: SourcesConfig,
+    #[serde(default)]
+    pub watchlist: WatchlistConfig,
+    #[serde(default)]
+    pub creators: CreatorsConfig,
+    #[serde(default)]
+    pub mint_features: MintFeaturesConfig,
+    #[serde(default)]
+    pub funding_trace: FundingTraceConfig,
+    /// Behaviour per `wallet_roles` role, e.g. `[roles.liquidity]`
+    #[serde(default)]
+    pub roles: HashMap<String, RoleConfig>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +64,16 @@ pub struct DatabaseConfig {
     pub url: String,
     #[serde(default)]
     pub discovery_url: Option<String>,
+    /// Fallback re-poll of the wallets table (changes are normally pushed via NOTIFY)
+    #[serde(default = "default_wallet_reload_poll_secs")]
+    pub wallet_reload_poll_secs: u64,
+    /// Only verify the schema version at startup instead of applying migrations
+    #[serde(default)]
+    pub migrate_check_only: bool,
+}
+
+fn default_wallet_reload_poll_secs() -> u64 {
+    60
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -60,10 +96,397 @@ pub struct ExecutionConfig {
     pub use_jito: bool,
 }
 
+/// Capacities and overflow behaviour of the ingestion pipeline stages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineConfig {
+    /// Transactions waiting between the gRPC receive and decode stages
+    #[serde(default = "default_tx_queue_capacity")]
+    pub tx_queue_capacity: usize,
+    /// Decoded tracked-wallet events waiting to be buffered for the DB
+    #[serde(default = "default_event_queue_capacity")]
+    pub event_queue_capacity: usize,
+    /// Discovery stat updates waiting for the discovery DB
+    #[serde(default = "default_discovery_queue_capacity")]
+    pub discovery_queue_capacity: usize,
+    #[serde(default)]
+    pub on_full: QueueFullPolicy,
+    /// How often aggregated discovery stats are written out
+    #[serde(default = "default_discovery_flush_secs")]
+    pub discovery_flush_secs: u64,
+    /// Flush early once this many wallets have pending discovery deltas
+    #[serde(default = "default_discovery_max_pending_wallets")]
+    pub discovery_max_pending_wallets: usize,
+    /// Per-mint activity waiting for the mint trackers (creators, features)
+    #[serde(default = "default_mint_queue_capacity")]
+    pub mint_queue_capacity: usize,
+    /// How often the mint trackers are written out
+    #[serde(default = "default_mint_flush_secs")]
+    pub mint_flush_secs: u64,
+    /// How often queue depths are logged
+    #[serde(default = "default_metrics_interval_secs")]
+    pub metrics_interval_secs: u64,
+    /// Where batches are spilled when the database write fails
+    #[serde(default = "default_spill_journal_path")]
+    pub spill_journal_path: String,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        Self {
+            tx_queue_capacity: default_tx_queue_capacity(),
+            event_queue_capacity: default_event_queue_capacity(),
+            discovery_queue_capacity: default_discovery_queue_capacity(),
+            on_full: QueueFullPolicy::default(),
+            discovery_flush_secs: default_discovery_flush_secs(),
+            discovery_max_pending_wallets: default_discovery_max_pending_wallets(),
+            mint_queue_capacity: default_mint_queue_capacity(),
+            mint_flush_secs: default_mint_flush_secs(),
+            metrics_interval_secs: default_metrics_interval_secs(),
+            spill_journal_path: default_spill_journal_path(),
+        }
+    }
+}
+
+/// Trade stitcher (`cargo run -p stitcher`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StitcherConfig {
+    /// How often new raw_events are stitched into trades
+    #[serde(default = "default_stitcher_interval_secs")]
+    pub interval_secs: u64,
+}
+
+impl Default for StitcherConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_stitcher_interval_secs(),
+        }
+    }
+}
+
+/// Wallet pattern analyzer (`cargo run -p analyzer`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalyzerConfig {
+    /// How often `wallet_patterns` is recomputed
+    #[serde(default = "default_analyzer_interval_secs")]
+    pub interval_secs: u64,
+    /// Trailing window of trades each pattern is learned from
+    #[serde(default = "default_analyzer_window_days")]
+    pub window_days: u32,
+    /// A tracked wallet's BUY this soon after another's (in a later slot) follows it
+    #[serde(default = "default_follow_max_lag_ms")]
+    pub follow_max_lag_ms: i64,
+    /// Mints a pair needs in common before it becomes a follow edge
+    #[serde(default = "default_follow_min_mints")]
+    pub follow_min_mints: usize,
+}
+
+impl Default for AnalyzerConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_analyzer_interval_secs(),
+            window_days: default_analyzer_window_days(),
+            follow_max_lag_ms: default_follow_max_lag_ms(),
+            follow_min_mints: default_follow_min_mints(),
+        }
+    }
+}
+
+/// How discovery ranks wallets into `wallet_stats.profit_score`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoringConfig {
+    #[serde(default)]
+    pub model: ScoringModelKind,
+    #[serde(default = "default_sharpe_weight")]
+    pub sharpe_weight: f64,
+    #[serde(default = "default_median_roi_weight")]
+    pub median_roi_weight: f64,
+    #[serde(default = "default_consistency_weight")]
+    pub consistency_weight: f64,
+    #[serde(default = "default_drawdown_weight")]
+    pub drawdown_weight: f64,
+    /// Idle days after which a wallet's score is halved (0 disables the decay)
+    #[serde(default = "default_recency_half_life_days")]
+    pub recency_half_life_days: f64,
+    /// Closed lots at which a wallet's score counts half
+    #[serde(default = "default_confidence_trades")]
+    pub confidence_trades: f64,
+}
+
+impl Default for ScoringConfig {
+    fn default() -> Self {
+        Self {
+            model: ScoringModelKind::default(),
+            sharpe_weight: default_sharpe_weight(),
+            median_roi_weight: default_median_roi_weight(),
+            consistency_weight: default_consistency_weight(),
+            drawdown_weight: default_drawdown_weight(),
+            recency_half_life_days: default_recency_half_life_days(),
+            confidence_trades: default_confidence_trades(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoringModelKind {
+    /// Weighted Sharpe-like ratio, median ROI, consistency and drawdown
+    #[default]
+    RiskAdjusted,
+    /// (net_pnl_sol * win_rate * total_trades) / 100, zero below 10 trades
+    Legacy,
+}
+
+/// When discovery tags a wallet as a bot instead of a manual trader
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassificationConfig {
+    /// Same-slot buy-before/sell-after brackets before a wallet is a sandwicher
+    #[serde(default = "default_min_sandwiches")]
+    pub min_sandwiches: i32,
+    /// Buys in a mint's CREATE slot before a wallet can be a sniper...
+    #[serde(default = "default_min_sniper_buys")]
+    pub min_sniper_buys: i32,
+    /// ...and the share of its buys they must be
+    #[serde(default = "default_sniper_buy_share")]
+    pub sniper_buy_share: f64,
+    /// Average trades per active day from which a wallet is a high-frequency bot
+    #[serde(default = "default_hf_trades_per_day")]
+    pub hf_trades_per_day: f64,
+}
+
+impl Default for ClassificationConfig {
+    fn default() -> Self {
+        Self {
+            min_sandwiches: default_min_sandwiches(),
+            min_sniper_buys: default_min_sniper_buys(),
+            sniper_buy_share: default_sniper_buy_share(),
+            hf_trades_per_day: default_hf_trades_per_day(),
+        }
+    }
+}
+
+/// Which discovered wallets `discovery -- promote` moves into tracking and back out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromotionConfig {
+    #[serde(default = "default_promote_min_profit_score")]
+    pub min_profit_score: f64,
+    #[serde(default = "default_promote_min_trades")]
+    pub min_trades: i32,
+    /// Only wallets that traded within this many days are promoted
+    #[serde(default = "default_promote_max_idle_days")]
+    pub max_idle_days: i64,
+    /// Wallet classes that may be promoted (empty = any)
+    #[serde(default = "default_promote_classes")]
+    pub classes: Vec<String>,
+    /// Promoted wallets are dropped below this score...
+    #[serde(default = "default_demote_below_score")]
+    pub demote_below_score: f64,
+    /// ...or after this many days without a trade
+    #[serde(default = "default_demote_idle_days")]
+    pub demote_idle_days: i64,
+    #[serde(default = "default_promote_max_per_run")]
+    pub max_per_run: i64,
+}
+
+impl Default for PromotionConfig {
+    fn default() -> Self {
+        Self {
+            min_profit_score: default_promote_min_profit_score(),
+            min_trades: default_promote_min_trades(),
+            max_idle_days: default_promote_max_idle_days(),
+            classes: default_promote_classes(),
+            demote_below_score: default_demote_below_score(),
+            demote_idle_days: default_demote_idle_days(),
+            max_per_run: default_promote_max_per_run(),
+        }
+    }
+}
+
+/// Where wallets are imported from (`cargo run -p cli -- wallets import`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SourcesConfig {
+    /// Legacy SQLite wallets.db
+    #[serde(default = "default_sqlite_wallets_path")]
+    pub sqlite_wallets_path: String,
+}
+
+impl Default for SourcesConfig {
+    fn default() -> Self {
+        Self {
+            sqlite_wallets_path: default_sqlite_wallets_path(),
+        }
+    }
+}
+
+/// Mints whose every trade the ingestor records, whoever the trader. Synced
+/// into `watched_mints` at startup; `cli watch add` adds more at runtime.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WatchlistConfig {
+    #[serde(default)]
+    pub mints: Vec<String>,
+}
+
+/// Creator (dev) wallet tracking in the ingestor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatorsConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Record a DEV_SELL and alert once the creator has sold or moved out
+    /// this share of the supply. Moves by plain SPL transfers outside
+    /// Pump.fun transactions are not seen.
+    #[serde(default = "default_dump_supply_fraction")]
+    pub dump_supply_fraction: f64,
+    /// How long after launch a mint's creator is followed
+    #[serde(default = "default_creator_window_hours")]
+    pub window_hours: i64,
+}
+
+impl Default for CreatorsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dump_supply_fraction: default_dump_supply_fraction(),
+            window_hours: default_creator_window_hours(),
+        }
+    }
+}
+
+/// Per-mint rug-risk features (`mint_features`) computed by the ingestor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MintFeaturesConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// How long after launch a mint's holders are followed
+    #[serde(default = "default_mint_features_window_hours")]
+    pub window_hours: i64,
+}
+
+impl Default for MintFeaturesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window_hours: default_mint_features_window_hours(),
+        }
+    }
+}
+
+/// Funding tracer over RPC (`cargo run -p discovery -- trace-funding`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FundingTraceConfig {
+    /// Funders walked back from a discovered wallet
+    #[serde(default = "default_funding_max_hops")]
+    pub max_hops: u32,
+    /// Signatures of each wallet paged back through to reach its oldest transactions
+    #[serde(default = "default_funding_history_per_wallet")]
+    pub history_per_wallet: usize,
+    /// Oldest transactions of each wallet searched for inbound SOL
+    #[serde(default = "default_funding_signatures_per_wallet")]
+    pub signatures_per_wallet: usize,
+    /// Largest funders followed from each wallet
+    #[serde(default = "default_funding_max_funders_per_wallet")]
+    pub max_funders_per_wallet: usize,
+    /// How long a wallet's fetched funders are reused before asking the RPC again
+    #[serde(default = "default_funding_cache_hours")]
+    pub cache_hours: i64,
+    /// Funders never walked through (exchange hot wallets and the like)
+    #[serde(default)]
+    pub ignore_funders: Vec<String>,
+}
+
+impl Default for FundingTraceConfig {
+    fn default() -> Self {
+        Self {
+            max_hops: default_funding_max_hops(),
+            history_per_wallet: default_funding_history_per_wallet(),
+            signatures_per_wallet: default_funding_signatures_per_wallet(),
+            max_funders_per_wallet: default_funding_max_funders_per_wallet(),
+            cache_hours: default_funding_cache_hours(),
+            ignore_funders: Vec::new(),
+        }
+    }
+}
+
+/// How the pipeline treats wallets with a role. A wallet with several roles
+/// gets the strictest combination.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoleConfig {
+    /// Write the wallet's events to raw_events
+    #[serde(default = "default_true")]
+    pub record_events: bool,
+    /// Keep the wallet's trades out of discovery P&L and rankings
+    #[serde(default)]
+    pub exclude_from_discovery: bool,
+    /// Never count the wallet as a leader (or follower) in follow detection
+    #[serde(default)]
+    pub exclude_from_leaders: bool,
+}
+
+impl Default for RoleConfig {
+    fn default() -> Self {
+        Self {
+            record_events: true,
+            exclude_from_discovery: false,
+            exclude_from_leaders: false,
+        }
+    }
+}
+
+/// What a stage does when the next queue is full
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueFullPolicy {
+    /// Wait for space on every queue (backpressure reaches the gRPC stream)
+    Block,
+    /// Drop discovery-only work (discovery stats, mint activity) when its queue
+    /// is full; tracked events still block
+    #[default]
+    DropDiscovery,
+}
+
 fn default_per_trade_sol_cap() -> f64 { 1.0 }
 fn default_per_hour_loss_cap_sol() -> f64 { 5.0 }
 fn default_max_concurrent_per_mint() -> usize { 1 }
 fn default_base_slippage_bps() -> u16 { 75 }
+fn default_tx_queue_capacity() -> usize { 10_000 }
+fn default_event_queue_capacity() -> usize { 10_000 }
+fn default_discovery_queue_capacity() -> usize { 50_000 }
+fn default_metrics_interval_secs() -> u64 { 30 }
+fn default_mint_queue_capacity() -> usize { 50_000 }
+fn default_mint_flush_secs() -> u64 { 5 }
+fn default_discovery_flush_secs() -> u64 { 5 }
+fn default_discovery_max_pending_wallets() -> usize { 10_000 }
+fn default_spill_journal_path() -> String { "data/spill_journal.jsonl".to_string() }
+fn default_stitcher_interval_secs() -> u64 { 30 }
+fn default_analyzer_interval_secs() -> u64 { 3600 }
+fn default_analyzer_window_days() -> u32 { 14 }
+fn default_follow_max_lag_ms() -> i64 { 10_000 }
+fn default_follow_min_mints() -> usize { 3 }
+fn default_sharpe_weight() -> f64 { 1.0 }
+fn default_median_roi_weight() -> f64 { 1.0 }
+fn default_consistency_weight() -> f64 { 1.0 }
+fn default_drawdown_weight() -> f64 { 0.5 }
+fn default_recency_half_life_days() -> f64 { 14.0 }
+fn default_confidence_trades() -> f64 { 10.0 }
+fn default_min_sandwiches() -> i32 { 2 }
+fn default_min_sniper_buys() -> i32 { 3 }
+fn default_sniper_buy_share() -> f64 { 0.5 }
+fn default_hf_trades_per_day() -> f64 { 200.0 }
+fn default_promote_min_profit_score() -> f64 { 0.5 }
+fn default_promote_min_trades() -> i32 { 20 }
+fn default_promote_max_idle_days() -> i64 { 3 }
+fn default_promote_classes() -> Vec<String> { vec!["manual".to_string()] }
+fn default_demote_below_score() -> f64 { 0.0 }
+fn default_demote_idle_days() -> i64 { 14 }
+fn default_promote_max_per_run() -> i64 { 10 }
+fn default_sqlite_wallets_path() -> String { "data/wallets.db".to_string() }
+fn default_dump_supply_fraction() -> f64 { 0.05 }
+fn default_creator_window_hours() -> i64 { 24 }
+fn default_mint_features_window_hours() -> i64 { 24 }
+fn default_funding_max_hops() -> u32 { 3 }
+fn default_funding_history_per_wallet() -> usize { 5000 }
+fn default_funding_signatures_per_wallet() -> usize { 25 }
+fn default_funding_max_funders_per_wallet() -> usize { 3 }
+fn default_funding_cache_hours() -> i64 { 168 }
+fn default_true() -> bool { true }
 
 impl Config {
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
@@ -73,4 +496,12 @@ impl Config {
             .context("Failed to parse config TOML")?;
         Ok(config)
     }
+
+    /// Roles whose config matches `pred`, sorted
+    pub fn roles_where(&self, pred: impl Fn(&RoleConfig) -> bool) -> Vec<String> {
+        let mut roles: Vec<String> =
+            self.roles.iter().filter(|(_, r)| pred(r)).map(|(name, _)| name.clone()).collect();
+        roles.sort();
+        roles
+    }
 }
@@ -1,6 +1,7 @@
 //! Common types and utilities shared across crates.
 
 pub mod config;
+pub mod rpc;
 pub mod sol_price;
 pub mod types {
     use serde::{Deserialize, Serialize};
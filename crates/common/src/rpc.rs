@@ -0,0 +1,165 @@
+//! Minimal Solana JSON-RPC client for the few lookups the tools make
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::time::Duration;
+
+/// Most signatures `getSignaturesForAddress` returns per call
+const SIGNATURES_PAGE: usize = 1000;
+
+/// SOL a wallet received from `funder` through a System Program transfer
+#[derive(Debug, Clone, PartialEq)]
+pub struct InboundTransfer {
+    pub funder: String,
+    pub lamports: u64,
+    pub sig: String,
+    /// Unix seconds, when the node knows it
+    pub block_time: Option<i64>,
+}
+
+pub struct RpcClient {
+    url: String,
+    client: reqwest::Client,
+    next_id: AtomicU64,
+}
+
+impl RpcClient {
+    pub fn new(url: &str) -> Result<Self> {
+        Ok(Self {
+            url: url.to_string(),
+            client: reqwest::Client::builder().timeout(Duration::from_secs(30)).build()?,
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    async fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let response: Value = self
+            .client
+            .post(&self.url)
+            .json(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))
+            .send()
+            .await
+            .with_context(|| format!("{} request to {} failed", method, self.url))?
+            .error_for_status()?
+            .json()
+            .await?;
+        if let Some(error) = response.get("error") {
+            bail!("{} failed: {}", method, error);
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    /// Signatures of successful transactions touching `address`, newest first,
+    /// paged back with `before` until the history ends or `max` were read
+    pub async fn signatures_for_address(&self, address: &str, max: usize) -> Result<Vec<String>> {
+        let mut signatures = Vec::new();
+        let mut before: Option<String> = None;
+        let mut read = 0;
+        while read < max {
+            let limit = (max - read).min(SIGNATURES_PAGE);
+            let mut options = json!({ "limit": limit });
+            if let Some(before) = &before {
+                options["before"] = json!(before);
+            }
+            let result = self.call("getSignaturesForAddress", json!([address, options])).await?;
+            let page = result.as_array().cloned().unwrap_or_default();
+            read += page.len();
+            before = page.last().and_then(|e| e["signature"].as_str()).map(str::to_string);
+            signatures.extend(
+                page.iter()
+                    .filter(|e| e["err"].is_null())
+                    .filter_map(|e| e.get("signature")?.as_str().map(str::to_string)),
+            );
+            if page.len() < limit || before.is_none() {
+                break;
+            }
+        }
+        Ok(signatures)
+    }
+
+    /// SOL transferred to `wallet` in its oldest `limit` transactions - where
+    /// funding sits - among the latest `history` signatures. A wallet busier
+    /// than `history` has its funding out of reach.
+    pub async fn inbound_transfers(&self, wallet: &str, history: usize, limit: usize) -> Result<Vec<InboundTransfer>> {
+        let mut transfers = Vec::new();
+        for sig in self.signatures_for_address(wallet, history).await?.iter().rev().take(limit) {
+            let tx = self
+                .call(
+                    "getTransaction",
+                    json!([sig, { "encoding": "jsonParsed", "maxSupportedTransactionVersion": 0 }]),
+                )
+                .await?;
+            transfers.extend(inbound_transfers_in(&tx, wallet, sig));
+        }
+        Ok(transfers)
+    }
+}
+
+/// System Program transfers to `wallet` in a `jsonParsed` transaction, top-level and inner
+pub fn inbound_transfers_in(tx: &Value, wallet: &str, sig: &str) -> Vec<InboundTransfer> {
+    let top_level = tx["transaction"]["message"]["instructions"].as_array().into_iter().flatten();
+    let inner = tx["meta"]["innerInstructions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|set| set["instructions"].as_array().into_iter().flatten());
+
+    top_level
+        .chain(inner)
+        .filter(|ix| ix["program"] == "system" && ix["parsed"]["type"] == "transfer")
+        .filter_map(|ix| {
+            let info = &ix["parsed"]["info"];
+            let funder = info["source"].as_str()?;
+            if info["destination"] != wallet || funder == wallet {
+                return None;
+            }
+            Some(InboundTransfer {
+                funder: funder.to_string(),
+                lamports: info["lamports"].as_u64()?,
+                sig: sig.to_string(),
+                block_time: tx["blockTime"].as_i64(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(source: &str, destination: &str, lamports: u64) -> Value {
+        json!({
+            "program": "system",
+            "programId": "11111111111111111111111111111111",
+            "parsed": { "type": "transfer", "info": { "source": source, "destination": destination, "lamports": lamports } }
+        })
+    }
+
+    #[test]
+    fn finds_top_level_and_inner_transfers_to_the_wallet() {
+        let tx = json!({
+            "blockTime": 1_700_000_000,
+            "transaction": { "message": { "instructions": [
+                transfer("Boss", "Fresh", 2_000_000_000),
+                // Moving SOL between its own accounts isn't funding
+                transfer("Fresh", "Fresh", 5_000),
+                transfer("Boss", "Someone", 7_000),
+                {
+                    "program": "spl-token",
+                    "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+                    "parsed": { "type": "transfer", "info": { "source": "Ata", "destination": "Fresh", "amount": "100" } }
+                },
+            ] } },
+            "meta": { "innerInstructions": [
+                { "index": 0, "instructions": [transfer("Router", "Fresh", 1_000_000)] },
+            ] }
+        });
+
+        let transfers = inbound_transfers_in(&tx, "Fresh", "sig1");
+        let found: Vec<(&str, u64)> = transfers.iter().map(|t| (t.funder.as_str(), t.lamports)).collect();
+        assert_eq!(found, vec![("Boss", 2_000_000_000), ("Router", 1_000_000)]);
+        assert!(transfers.iter().all(|t| t.sig == "sig1" && t.block_time == Some(1_700_000_000)));
+    }
+}
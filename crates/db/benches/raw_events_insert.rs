@@ -0,0 +1,107 @@
+//! Throughput of the raw_events insert paths on 10k-event batches
+//!
+//! Needs a Postgres with the raw_events schema:
+//!
+//!     DATABASE_URL=postgres://... cargo bench -p db --features postgres
+//!
+//! Writes under a throwaway wallet and deletes everything it inserted.
+
+use chrono::Utc;
+use db::raw_events::{batch_insert_raw_events, copy_insert_raw_events, insert_raw_events_batch, RawEvent};
+use serde_json::json;
+use sqlx::PgPool;
+use std::time::Instant;
+
+const BATCH_SIZE: usize = 10_000;
+const ROUNDS: usize = 3;
+const BENCH_WALLET: &str = "BenchWa11et1111111111111111111111111111111";
+
+fn make_batch(round: usize, path: &str) -> Vec<RawEvent> {
+    (0..BATCH_SIZE)
+        .map(|i| RawEvent {
+            ts_ns: Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+            slot: Some(250_000_000 + i as i64),
+            sig: Some(format!("bench-{}-{}-{}", path, round, i)),
+            wallet: BENCH_WALLET.to_string(),
+            alias: Some("bench".to_string()),
+            program: "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P".to_string(),
+            action: if i % 2 == 0 { "BUY" } else { "SELL" }.to_string(),
+            mint: Some("BenchMint111111111111111111111111111111111".to_string()),
+            base_mint: None,
+            quote_mint: None,
+            amount_in: Some(0.5),
+            amount_out: Some(1_234_567.0),
+            price_est: Some(0.000_000_4),
+            fee_sol: Some(0.000_005),
+            ix_accounts_json: Some(json!(["a", "b", "c"])),
+            meta_json: Some(json!({ "note": "quoted \"value\", with comma" })),
+            leader_wallet: None,
+            block_time: Some(Utc::now()),
+            recv_time_ns: Some(0),
+            ix_index: Some(0),
+            decode_ok: true,
+            decode_err: None,
+            pre_balance_sol: Some(10.0),
+            post_balance_sol: Some(9.5),
+            balance_change_sol: Some(-0.5),
+            role: Some("signer".to_string()),
+            wallet_roles: None,
+        })
+        .collect()
+}
+
+async fn cleanup(pool: &PgPool) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM raw_events WHERE wallet = $1")
+        .bind(BENCH_WALLET)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let Ok(url) = std::env::var("DATABASE_URL") else {
+        eprintln!("DATABASE_URL not set, skipping raw_events insert benchmark");
+        return Ok(());
+    };
+    let pool = PgPool::connect(&url).await?;
+
+    sqlx::query("INSERT INTO wallets (wallet, alias, is_tracked) VALUES ($1, 'bench', false) ON CONFLICT (wallet) DO NOTHING")
+        .bind(BENCH_WALLET)
+        .execute(&pool)
+        .await?;
+    cleanup(&pool).await?;
+
+    println!("{} rounds of {} events", ROUNDS, BATCH_SIZE);
+    for path in ["copy", "per-row", "per-row-tx"] {
+        let mut total = 0.0;
+        for round in 0..ROUNDS {
+            let batch = make_batch(round, path);
+            let start = Instant::now();
+            match path {
+                "copy" => {
+                    copy_insert_raw_events(&pool, &batch).await?;
+                }
+                "per-row" => {
+                    batch_insert_raw_events(&pool, &batch).await?;
+                }
+                _ => insert_raw_events_batch(&pool, &batch).await?,
+            }
+            total += start.elapsed().as_secs_f64();
+        }
+        let per_batch = total / ROUNDS as f64;
+        println!(
+            "{:>11}: {:>8.1} ms/batch  {:>9.0} events/s",
+            path,
+            per_batch * 1000.0,
+            BATCH_SIZE as f64 / per_batch
+        );
+    }
+
+    cleanup(&pool).await?;
+    sqlx::query("DELETE FROM wallets WHERE wallet = $1")
+        .bind(BENCH_WALLET)
+        .execute(&pool)
+        .await?;
+    Ok(())
+}
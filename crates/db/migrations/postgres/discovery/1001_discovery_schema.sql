@@ -1,11 +1,10 @@
 -- Discovery Database Schema
 -- Tracks ALL wallets for profitability analysis
 
--- Create discovery database
--- Run: createdb discovery
+-- The database itself must exist first: createdb discovery
 
 -- Wallet statistics
-CREATE TABLE wallet_stats (
+CREATE TABLE IF NOT EXISTS wallet_stats (
     wallet TEXT PRIMARY KEY,
     first_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
     last_seen TIMESTAMPTZ NOT NULL DEFAULT NOW(),
@@ -38,19 +37,19 @@ CREATE TABLE wallet_stats (
 );
 
 -- Indexes for performance
-CREATE INDEX idx_wallet_stats_profit_score ON wallet_stats(profit_score DESC NULLS LAST)
+CREATE INDEX IF NOT EXISTS idx_wallet_stats_profit_score ON wallet_stats(profit_score DESC NULLS LAST)
 WHERE total_trades >= 10;  -- Only rank wallets with sufficient activity
 
-CREATE INDEX idx_wallet_stats_pnl ON wallet_stats(net_pnl_sol DESC)
+CREATE INDEX IF NOT EXISTS idx_wallet_stats_pnl ON wallet_stats(net_pnl_sol DESC)
 WHERE total_trades >= 10;
 
-CREATE INDEX idx_wallet_stats_last_seen ON wallet_stats(last_seen DESC);
+CREATE INDEX IF NOT EXISTS idx_wallet_stats_last_seen ON wallet_stats(last_seen DESC);
 
-CREATE INDEX idx_wallet_stats_tracked ON wallet_stats(is_tracked)
+CREATE INDEX IF NOT EXISTS idx_wallet_stats_tracked ON wallet_stats(is_tracked)
 WHERE is_tracked = TRUE;
 
 -- Position tracking for P&L calculation
-CREATE TABLE positions (
+CREATE TABLE IF NOT EXISTS positions (
     wallet TEXT NOT NULL,
     mint TEXT NOT NULL,
     bought_at TIMESTAMPTZ NOT NULL,
@@ -69,13 +68,13 @@ CREATE TABLE positions (
     PRIMARY KEY (wallet, mint, bought_at)
 );
 
-CREATE INDEX idx_positions_open ON positions(wallet, mint)
+CREATE INDEX IF NOT EXISTS idx_positions_open ON positions(wallet, mint)
 WHERE NOT is_closed;
 
-CREATE INDEX idx_positions_wallet ON positions(wallet, bought_at DESC);
+CREATE INDEX IF NOT EXISTS idx_positions_wallet ON positions(wallet, bought_at DESC);
 
 -- Daily aggregated stats for trend analysis
-CREATE TABLE wallet_daily_stats (
+CREATE TABLE IF NOT EXISTS wallet_daily_stats (
     wallet TEXT NOT NULL,
     date DATE NOT NULL,
     trades INTEGER NOT NULL DEFAULT 0,
@@ -88,11 +87,11 @@ CREATE TABLE wallet_daily_stats (
     PRIMARY KEY (wallet, date)
 );
 
-CREATE INDEX idx_daily_stats_date ON wallet_daily_stats(date DESC);
-CREATE INDEX idx_daily_stats_pnl ON wallet_daily_stats(daily_pnl DESC);
+CREATE INDEX IF NOT EXISTS idx_daily_stats_date ON wallet_daily_stats(date DESC);
+CREATE INDEX IF NOT EXISTS idx_daily_stats_pnl ON wallet_daily_stats(daily_pnl DESC);
 
 -- View for top performers
-CREATE VIEW top_profitable_wallets AS
+CREATE OR REPLACE VIEW top_profitable_wallets AS
 SELECT 
     wallet,
     total_trades,
@@ -108,7 +107,7 @@ ORDER BY profit_score DESC
 LIMIT 100;
 
 -- View for emerging traders (high win rate, lower volume)
-CREATE VIEW emerging_traders AS
+CREATE OR REPLACE VIEW emerging_traders AS
 SELECT 
     wallet,
     total_trades,
@@ -0,0 +1,15 @@
+-- Lots carry token quantities: a SELL takes tokens from the oldest open lots,
+-- a partly sold lot stays open with the rest, and P&L is realized pro rata.
+-- token_amount = 0 marks a lot of unknown size (opened before this change).
+
+ALTER TABLE positions ADD COLUMN IF NOT EXISTS tokens_remaining DOUBLE PRECISION NOT NULL DEFAULT 0;
+
+-- Sells with no earlier buy (inventory from before the wallet was seen);
+-- their proceeds are kept out of P&L
+ALTER TABLE wallet_stats ADD COLUMN IF NOT EXISTS unmatched_sells INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE wallet_stats ADD COLUMN IF NOT EXISTS unmatched_sol_out DOUBLE PRECISION NOT NULL DEFAULT 0;
+
+-- net_pnl_sol is now realized P&L of sold lots, not total_sol_out - total_sol_in
+UPDATE wallet_stats SET net_pnl_sol = COALESCE(
+    (SELECT SUM(p.realized_pnl) FROM positions p WHERE p.wallet = wallet_stats.wallet), 0
+);
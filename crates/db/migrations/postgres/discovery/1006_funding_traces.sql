@@ -0,0 +1,23 @@
+-- Funding-graph tracing (db::funding): inbound SOL transfers fetched over RPC
+-- are cached in wallet_funding next to the ones the ingestor saw, and a
+-- discovered wallet reaching a tracked wallet or a sybil cluster is linked.
+
+-- stream | rpc
+ALTER TABLE wallet_funding ADD COLUMN IF NOT EXISTS source TEXT NOT NULL DEFAULT 'stream';
+
+-- Wallets whose inbound transfers were fetched over RPC
+CREATE TABLE IF NOT EXISTS funding_traces (
+    wallet TEXT PRIMARY KEY,
+    traced_at TIMESTAMPTZ NOT NULL,
+    transfers INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS wallet_links (
+    wallet TEXT PRIMARY KEY,
+    linked_to TEXT NOT NULL,   -- tracked wallet or cluster member reached
+    kind TEXT NOT NULL,        -- tracked | cluster
+    cluster_id TEXT,
+    hops INTEGER NOT NULL,
+    path TEXT NOT NULL,        -- funders walked, comma-separated, ending at linked_to
+    found_at TIMESTAMPTZ NOT NULL
+);
@@ -1,4 +1,4 @@
--- Add this to your Postgres schema for wallet_roles
+-- Role tags per wallet (seeded from wallets.db by scripts/seed_wallets.py)
 CREATE TABLE IF NOT EXISTS wallet_roles (
   wallet TEXT NOT NULL REFERENCES wallets(wallet),
   role TEXT NOT NULL,
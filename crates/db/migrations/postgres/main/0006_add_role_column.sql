@@ -0,0 +1,12 @@
+-- Add wallet role to raw_events
+-- A tracked wallet can appear in a transaction without being the trader;
+-- only 'signer' rows are attributed trades (amounts are NULL for the rest)
+
+ALTER TABLE raw_events 
+ADD COLUMN IF NOT EXISTS role TEXT;
+
+-- Add index for actor-only queries
+CREATE INDEX IF NOT EXISTS idx_raw_events_wallet_role 
+ON raw_events(wallet, role);
+
+COMMENT ON COLUMN raw_events.role IS 'Wallet role in the transaction: signer | fee_payer | counterparty | referenced';
@@ -0,0 +1,14 @@
+-- Notify the ingestor whenever the wallets table changes
+-- so tracked wallets reload live without a restart (LISTEN wallets_changed)
+
+CREATE OR REPLACE FUNCTION notify_wallets_changed() RETURNS trigger AS $$
+BEGIN
+  PERFORM pg_notify('wallets_changed', TG_OP);
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS trg_wallets_changed ON wallets;
+CREATE TRIGGER trg_wallets_changed
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON wallets
+FOR EACH STATEMENT EXECUTE FUNCTION notify_wallets_changed();
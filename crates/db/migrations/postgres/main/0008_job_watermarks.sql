@@ -0,0 +1,10 @@
+-- Last raw_events.id each background job has processed, so jobs like the
+-- trade stitcher only revisit wallets/mints with new events
+
+CREATE TABLE IF NOT EXISTS job_watermarks (
+  job TEXT PRIMARY KEY,
+  last_event_id BIGINT NOT NULL DEFAULT 0,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_trades_wallet_mint ON trades(wallet, mint);
@@ -0,0 +1,10 @@
+-- The wallet's roles from wallet_roles when the event was recorded, comma
+-- separated (e.g. 'liquidity,admin'); NULL for wallets without roles
+
+ALTER TABLE raw_events ADD COLUMN IF NOT EXISTS wallet_roles TEXT;
+
+-- Role changes reload the ingestor's tracked set too
+DROP TRIGGER IF EXISTS trg_wallet_roles_changed ON wallet_roles;
+CREATE TRIGGER trg_wallet_roles_changed
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON wallet_roles
+FOR EACH STATEMENT EXECUTE FUNCTION notify_wallets_changed();
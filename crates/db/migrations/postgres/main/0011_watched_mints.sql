@@ -0,0 +1,18 @@
+-- Mints whose every trade is recorded, whoever the trader
+-- (config `watchlist.mints` plus `cli watch add`)
+
+CREATE TABLE IF NOT EXISTS watched_mints (
+  mint TEXT PRIMARY KEY,
+  note TEXT,
+  source TEXT NOT NULL DEFAULT 'cli',  -- cli | config
+  added_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Traders on watched mints usually aren't in `wallets`
+ALTER TABLE raw_events DROP CONSTRAINT IF EXISTS raw_events_wallet_fkey;
+
+-- Watchlist changes reload the ingestor like wallet changes
+DROP TRIGGER IF EXISTS trg_watched_mints_changed ON watched_mints;
+CREATE TRIGGER trg_watched_mints_changed
+AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON watched_mints
+FOR EACH STATEMENT EXECUTE FUNCTION notify_wallets_changed();
@@ -0,0 +1,15 @@
+-- The CREATE signer of every mint the ingestor saw launch, and how much of
+-- the supply it has moved out since (see db::creators)
+
+CREATE TABLE IF NOT EXISTS mint_creators (
+  mint TEXT PRIMARY KEY,
+  creator TEXT NOT NULL,
+  create_sig TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  tokens_sold BIGINT NOT NULL DEFAULT 0,         -- raw units sold on the curve
+  tokens_transferred BIGINT NOT NULL DEFAULT 0,  -- raw units moved out otherwise
+  dumped_at TIMESTAMPTZ,                         -- crossed creators.dump_supply_fraction
+  dump_sig TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_mint_creators_creator ON mint_creators(creator);
+CREATE INDEX IF NOT EXISTS idx_mint_creators_created_at ON mint_creators(created_at);
@@ -0,0 +1,13 @@
+-- profit_score is a risk-adjusted model over closed lots (db::scoring) instead
+-- of (net_pnl_sol * win_rate * total_trades) / 100; its inputs are kept next to it.
+-- Existing scores are left as they are until `discovery -- rescore` runs.
+
+ALTER TABLE wallet_stats ADD COLUMN closed_trades INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE wallet_stats ADD COLUMN sharpe REAL NOT NULL DEFAULT 0;
+ALTER TABLE wallet_stats ADD COLUMN max_drawdown REAL NOT NULL DEFAULT 0;
+ALTER TABLE wallet_stats ADD COLUMN median_roi REAL NOT NULL DEFAULT 0;
+ALTER TABLE wallet_stats ADD COLUMN consistency REAL NOT NULL DEFAULT 0;
+
+-- Components are read back in sell order
+CREATE INDEX IF NOT EXISTS idx_positions_closed ON positions(wallet, sold_at)
+WHERE is_closed;
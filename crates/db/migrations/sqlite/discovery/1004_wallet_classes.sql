@@ -0,0 +1,11 @@
+-- Wallets are classified as manual traders or bots (db::classify) from
+-- same-slot signals counted here and their trade rate, so discovery can rank
+-- only wallets that can actually be copied.
+
+ALTER TABLE wallet_stats ADD COLUMN sniper_buys INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE wallet_stats ADD COLUMN sandwiches INTEGER NOT NULL DEFAULT 0;
+-- manual | sniper | sandwicher | hf_bot
+ALTER TABLE wallet_stats ADD COLUMN wallet_class TEXT NOT NULL DEFAULT 'manual';
+
+CREATE INDEX IF NOT EXISTS idx_wallet_stats_class_score ON wallet_stats(wallet_class, profit_score DESC)
+WHERE total_trades >= 10;
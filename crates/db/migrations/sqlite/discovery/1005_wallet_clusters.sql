@@ -0,0 +1,23 @@
+-- Sybil clusters (db::clusters): wallets that bought the same mint in the
+-- same slot with SOL from a common funder. Discovery ranks and promotes one
+-- wallet per cluster.
+
+-- SOL sent to a wallet in a transaction where it traded
+CREATE TABLE IF NOT EXISTS wallet_funding (
+    wallet TEXT NOT NULL,
+    funder TEXT NOT NULL,
+    lamports INTEGER NOT NULL DEFAULT 0,
+    first_seen TEXT NOT NULL,
+    PRIMARY KEY (wallet, funder)
+);
+CREATE INDEX IF NOT EXISTS idx_wallet_funding_funder ON wallet_funding(funder);
+
+CREATE TABLE IF NOT EXISTS wallet_clusters (
+    wallet TEXT PRIMARY KEY,
+    cluster_id TEXT NOT NULL,  -- smallest member address when the cluster formed
+    funder TEXT,               -- common funder that linked the wallet, if not a member
+    mint TEXT NOT NULL,        -- bundle that linked the wallet
+    slot INTEGER NOT NULL,
+    linked_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_wallet_clusters_cluster ON wallet_clusters(cluster_id);
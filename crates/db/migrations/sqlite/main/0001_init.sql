@@ -0,0 +1,104 @@
+-- SQLite schema (local dev / tests), equivalent to postgres/main 0001-0006.
+-- JSON is stored as TEXT, timestamps as ISO-8601 TEXT. Foreign keys are
+-- enabled per connection by db::connect.
+
+CREATE TABLE IF NOT EXISTS wallets (
+  wallet TEXT PRIMARY KEY,
+  alias TEXT,
+  is_tracked BOOLEAN NOT NULL DEFAULT TRUE,
+  notes TEXT,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS idx_wallets_tracked ON wallets(is_tracked);
+
+CREATE TABLE IF NOT EXISTS wallet_roles (
+  wallet TEXT NOT NULL REFERENCES wallets(wallet),
+  role TEXT NOT NULL,
+  PRIMARY KEY (wallet, role)
+);
+
+CREATE TABLE IF NOT EXISTS raw_events (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  ts_ns INTEGER NOT NULL,
+  slot INTEGER,
+  sig TEXT,
+  wallet TEXT NOT NULL REFERENCES wallets(wallet),
+  alias TEXT,
+  program TEXT NOT NULL,
+  action TEXT NOT NULL,
+  mint TEXT,
+  base_mint TEXT, quote_mint TEXT,
+  amount_in REAL, amount_out REAL,
+  price_est REAL,
+  fee_sol REAL,
+  ix_accounts_json TEXT,
+  meta_json TEXT,
+  leader_wallet TEXT,
+  block_time TEXT,
+  recv_time_ns INTEGER,
+  ix_index INTEGER,
+  decode_ok BOOLEAN NOT NULL DEFAULT TRUE,
+  decode_err TEXT,
+  pre_balance_sol REAL,
+  post_balance_sol REAL,
+  balance_change_sol REAL,
+  role TEXT,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE(sig, wallet, action)
+);
+CREATE INDEX IF NOT EXISTS idx_raw_events_wallet_ts ON raw_events(wallet, ts_ns);
+CREATE INDEX IF NOT EXISTS idx_raw_events_mint_ts ON raw_events(mint, ts_ns);
+CREATE INDEX IF NOT EXISTS idx_raw_events_alias ON raw_events(alias) WHERE alias IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_raw_events_balance_change ON raw_events(wallet, balance_change_sol) WHERE balance_change_sol IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_events_action_recv_time ON raw_events(action, recv_time_ns) WHERE recv_time_ns IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_events_decode_errors ON raw_events(decode_ok, decode_err) WHERE decode_ok = FALSE;
+CREATE INDEX IF NOT EXISTS idx_raw_events_wallet_role ON raw_events(wallet, role);
+
+CREATE TABLE IF NOT EXISTS trades (
+  trade_id INTEGER PRIMARY KEY AUTOINCREMENT,
+  wallet TEXT NOT NULL REFERENCES wallets(wallet),
+  mint TEXT NOT NULL,
+  open_sig TEXT NOT NULL,
+  open_ts_ns INTEGER NOT NULL,
+  open_qty REAL NOT NULL,
+  open_cost_sol REAL NOT NULL,
+  close_sig TEXT,
+  close_ts_ns INTEGER,
+  close_qty REAL,
+  close_proceeds_sol REAL,
+  hold_ms INTEGER,
+  pnl_sol REAL,
+  pnl_x REAL,
+  was_win BOOLEAN,
+  meta_json TEXT,
+  UNIQUE(open_sig, wallet)
+);
+CREATE INDEX IF NOT EXISTS idx_trades_wallet_open ON trades(wallet, open_ts_ns);
+CREATE INDEX IF NOT EXISTS idx_trades_mint_open ON trades(mint, open_ts_ns);
+
+CREATE TABLE IF NOT EXISTS wallet_patterns (
+  wallet TEXT PRIMARY KEY REFERENCES wallets(wallet),
+  sample_days INTEGER,
+  sample_trades INTEGER,
+  win_rate REAL,
+  avg_daily_trades REAL,
+  avg_entry_sol REAL,
+  p50_hold_ms INTEGER, p90_hold_ms INTEGER,
+  p50_target_x REAL, p90_target_x REAL,
+  typical_exit_style TEXT,
+  slippage_pctl_95 REAL,
+  followership_score REAL,
+  leader_score REAL,
+  preferred_time_window TEXT,
+  updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS follow_edges (
+  leader TEXT NOT NULL REFERENCES wallets(wallet),
+  follower TEXT NOT NULL REFERENCES wallets(wallet),
+  lag_ms_p50 INTEGER,
+  lag_ms_p90 INTEGER,
+  follow_prob REAL,
+  avg_follower_size_sol REAL,
+  PRIMARY KEY (leader, follower)
+);
@@ -0,0 +1,17 @@
+-- Wallets promoted from the discovery database by `discovery -- promote`.
+-- Only these are ever demoted by the job; hand-seeded wallets are left alone.
+
+ALTER TABLE wallets ADD COLUMN auto_promoted BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Every promotion and demotion, with the stats it was decided on
+CREATE TABLE IF NOT EXISTS wallet_promotions (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  wallet TEXT NOT NULL REFERENCES wallets(wallet),
+  action TEXT NOT NULL,  -- promote | demote
+  reason TEXT NOT NULL,
+  profit_score REAL,
+  total_trades INTEGER,
+  wallet_class TEXT,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS idx_wallet_promotions_wallet ON wallet_promotions(wallet, created_at);
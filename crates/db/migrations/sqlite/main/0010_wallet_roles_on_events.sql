@@ -0,0 +1,4 @@
+-- The wallet's roles from wallet_roles when the event was recorded, comma
+-- separated (e.g. 'liquidity,admin'); NULL for wallets without roles
+
+ALTER TABLE raw_events ADD COLUMN wallet_roles TEXT;
@@ -0,0 +1,58 @@
+-- Mints whose every trade is recorded, whoever the trader
+-- (config `watchlist.mints` plus `cli watch add`)
+
+CREATE TABLE IF NOT EXISTS watched_mints (
+  mint TEXT PRIMARY KEY,
+  note TEXT,
+  source TEXT NOT NULL DEFAULT 'cli',  -- cli | config
+  added_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Traders on watched mints usually aren't in `wallets`. SQLite can't drop a
+-- foreign key, so raw_events is rebuilt without it.
+CREATE TABLE raw_events_new (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  ts_ns INTEGER NOT NULL,
+  slot INTEGER,
+  sig TEXT,
+  wallet TEXT NOT NULL,
+  alias TEXT,
+  program TEXT NOT NULL,
+  action TEXT NOT NULL,
+  mint TEXT,
+  base_mint TEXT, quote_mint TEXT,
+  amount_in REAL, amount_out REAL,
+  price_est REAL,
+  fee_sol REAL,
+  ix_accounts_json TEXT,
+  meta_json TEXT,
+  leader_wallet TEXT,
+  block_time TEXT,
+  recv_time_ns INTEGER,
+  ix_index INTEGER,
+  decode_ok BOOLEAN NOT NULL DEFAULT TRUE,
+  decode_err TEXT,
+  pre_balance_sol REAL,
+  post_balance_sol REAL,
+  balance_change_sol REAL,
+  role TEXT,
+  created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  wallet_roles TEXT,
+  UNIQUE(sig, wallet, action)
+);
+INSERT INTO raw_events_new SELECT
+  id, ts_ns, slot, sig, wallet, alias, program, action, mint, base_mint, quote_mint,
+  amount_in, amount_out, price_est, fee_sol, ix_accounts_json, meta_json, leader_wallet,
+  block_time, recv_time_ns, ix_index, decode_ok, decode_err,
+  pre_balance_sol, post_balance_sol, balance_change_sol, role, created_at, wallet_roles
+FROM raw_events;
+DROP TABLE raw_events;
+ALTER TABLE raw_events_new RENAME TO raw_events;
+
+CREATE INDEX IF NOT EXISTS idx_raw_events_wallet_ts ON raw_events(wallet, ts_ns);
+CREATE INDEX IF NOT EXISTS idx_raw_events_mint_ts ON raw_events(mint, ts_ns);
+CREATE INDEX IF NOT EXISTS idx_raw_events_alias ON raw_events(alias) WHERE alias IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_raw_events_balance_change ON raw_events(wallet, balance_change_sol) WHERE balance_change_sol IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_events_action_recv_time ON raw_events(action, recv_time_ns) WHERE recv_time_ns IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_events_decode_errors ON raw_events(decode_ok, decode_err) WHERE decode_ok = FALSE;
+CREATE INDEX IF NOT EXISTS idx_raw_events_wallet_role ON raw_events(wallet, role);
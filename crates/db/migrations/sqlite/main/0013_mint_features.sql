@@ -0,0 +1,27 @@
+-- Rug-risk and holder-concentration features of every mint the ingestor saw
+-- launch (see db::mint_features). Shares are of the 1B-token supply.
+
+CREATE TABLE IF NOT EXISTS mint_features (
+  mint TEXT PRIMARY KEY,
+  creator TEXT NOT NULL,
+  created_at TEXT NOT NULL,
+  created_slot INTEGER NOT NULL,
+  creation_slot_tokens INTEGER NOT NULL DEFAULT 0,  -- raw units bought in the creation slot
+  creation_slot_share REAL NOT NULL DEFAULT 0,
+  top10_share REAL NOT NULL DEFAULT 0,              -- bonding curve excluded
+  creator_share REAL NOT NULL DEFAULT 0,
+  unique_buyers INTEGER NOT NULL DEFAULT 0,
+  first_sell_at TEXT,
+  secs_to_first_sell REAL,
+  updated_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS idx_mint_features_created_at ON mint_features(created_at);
+
+-- Balances behind top10_share/unique_buyers, kept while the mint is followed
+CREATE TABLE IF NOT EXISTS mint_holders (
+  mint TEXT NOT NULL,
+  owner TEXT NOT NULL,
+  balance INTEGER NOT NULL DEFAULT 0,
+  bought BOOLEAN NOT NULL DEFAULT FALSE,
+  PRIMARY KEY (mint, owner)
+);
@@ -0,0 +1,444 @@
+//! Wallet classification - tell copyable traders from bots
+//!
+//! Signals that need other wallets' trades in the same slot (sniping a
+//! CREATE, sandwiching someone) are picked up by a `SlotWindow` over the
+//! decoded stream and counted on `wallet_stats`; `ClassThresholds` then turns
+//! those counters and the wallet's trade rate into its `wallet_class`.
+
+use crate::scoring::fetch_for_wallets;
+use crate::{Connection, Pool};
+use anyhow::{bail, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+
+/// Slots a trade is held before its slot is evaluated, so transactions of a
+/// slot that arrive a little late are still seen together
+pub(crate) const SLOT_LAG: u64 = 2;
+
+/// Wallets reclassified per transaction by `reclassify_all`
+const RECLASSIFY_BATCH: i64 = 5_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WalletClass {
+    /// None of the bot patterns below
+    Manual,
+    /// Buys in the same slot the mint was created
+    Sniper,
+    /// Buys before and sells after another wallet's buy in the same slot
+    Sandwicher,
+    /// Trades more often per active day than a person could
+    HfBot,
+}
+
+impl WalletClass {
+    /// Classes whose trades can realistically be copied
+    pub const COPYABLE: &'static [WalletClass] = &[WalletClass::Manual];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WalletClass::Manual => "manual",
+            WalletClass::Sniper => "sniper",
+            WalletClass::Sandwicher => "sandwicher",
+            WalletClass::HfBot => "hf_bot",
+        }
+    }
+}
+
+impl FromStr for WalletClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "manual" => WalletClass::Manual,
+            "sniper" => WalletClass::Sniper,
+            "sandwicher" => WalletClass::Sandwicher,
+            "hf_bot" => WalletClass::HfBot,
+            other => bail!("Unknown wallet class '{}'", other),
+        })
+    }
+}
+
+/// `AND wallet_class IN (...)` for a query on `wallet_stats`, or nothing
+/// without classes. Class names are fixed strings, so they go into the SQL directly.
+pub(crate) fn class_filter(classes: &[WalletClass]) -> String {
+    if classes.is_empty() {
+        return String::new();
+    }
+    let names: Vec<String> = classes.iter().map(|c| format!("'{}'", c.as_str())).collect();
+    format!("AND wallet_class IN ({})", names.join(", "))
+}
+
+/// Bot signals found for one wallet
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ClassSignals {
+    /// BUYs landing in the slot that created the mint (the creator's own excluded)
+    pub sniper_buys: i32,
+    /// (slot, mint)s where the wallet bracketed another wallet's BUY
+    pub sandwiches: i32,
+}
+
+#[derive(Debug, Clone)]
+struct SlotTrade {
+    wallet: String,
+    mint: String,
+    action: SlotAction,
+    tx_index: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SlotAction {
+    Buy,
+    Sell,
+    Create,
+}
+
+/// Trades of the last few slots, evaluated for same-slot bot patterns once
+/// their slot is `SLOT_LAG` behind the newest one seen
+#[derive(Debug, Default)]
+pub struct SlotWindow {
+    slots: BTreeMap<u64, Vec<SlotTrade>>,
+    newest: u64,
+    /// Slots below this were already evaluated
+    settled_below: u64,
+}
+
+impl SlotWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one decoded trade; `tx_index` is its transaction's position in the block
+    pub fn record(&mut self, wallet: &str, action: &str, mint: &str, slot: u64, tx_index: u64) {
+        let action = match action {
+            "BUY" => SlotAction::Buy,
+            "SELL" => SlotAction::Sell,
+            "CREATE" => SlotAction::Create,
+            _ => return,
+        };
+        // Its slot was already evaluated; a trade this late is dropped
+        if slot < self.settled_below {
+            return;
+        }
+        self.newest = self.newest.max(slot);
+        self.slots.entry(slot).or_default().push(SlotTrade {
+            wallet: wallet.to_string(),
+            mint: mint.to_string(),
+            action,
+            tx_index,
+        });
+    }
+
+    /// Evaluate and drop the slots that are settled (all of them with `all`),
+    /// adding what they show to `out`
+    pub fn drain(&mut self, all: bool, out: &mut HashMap<String, ClassSignals>) {
+        let keep_from = if all { self.newest + 1 } else { (self.newest + 1).saturating_sub(SLOT_LAG) };
+        self.settled_below = self.settled_below.max(keep_from);
+        let kept = self.slots.split_off(&keep_from);
+        for trades in std::mem::replace(&mut self.slots, kept).into_values() {
+            evaluate_slot(trades, out);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+/// Find snipes and sandwiches among one slot's trades
+fn evaluate_slot(mut trades: Vec<SlotTrade>, out: &mut HashMap<String, ClassSignals>) {
+    trades.sort_by_key(|t| t.tx_index);
+    let mut by_mint: HashMap<&str, Vec<&SlotTrade>> = HashMap::new();
+    for trade in &trades {
+        by_mint.entry(trade.mint.as_str()).or_default().push(trade);
+    }
+
+    for trades in by_mint.values() {
+        let creators: HashSet<&str> = trades
+            .iter()
+            .filter(|t| t.action == SlotAction::Create)
+            .map(|t| t.wallet.as_str())
+            .collect();
+        if !creators.is_empty() {
+            for t in trades.iter().filter(|t| t.action == SlotAction::Buy) {
+                if !creators.contains(t.wallet.as_str()) {
+                    out.entry(t.wallet.clone()).or_default().sniper_buys += 1;
+                }
+            }
+        }
+
+        // A BUY by W, then someone else's BUY, then a SELL by W
+        let mut sandwichers: HashSet<&str> = HashSet::new();
+        for (i, first) in trades.iter().enumerate() {
+            if first.action != SlotAction::Buy || sandwichers.contains(first.wallet.as_str()) {
+                continue;
+            }
+            let rest = &trades[i + 1..];
+            let Some(victim) = rest
+                .iter()
+                .position(|t| t.action == SlotAction::Buy && t.wallet != first.wallet)
+            else {
+                continue;
+            };
+            if rest[victim + 1..]
+                .iter()
+                .any(|t| t.action == SlotAction::Sell && t.wallet == first.wallet)
+            {
+                sandwichers.insert(first.wallet.as_str());
+            }
+        }
+        for wallet in sandwichers {
+            out.entry(wallet.to_string()).or_default().sandwiches += 1;
+        }
+    }
+}
+
+/// What a wallet is classified from
+#[derive(Debug, Clone, Copy, Default, sqlx::FromRow)]
+pub struct ClassCounters {
+    pub total_trades: i32,
+    pub buy_count: i32,
+    pub sniper_buys: i32,
+    pub sandwiches: i32,
+    /// Days with at least one trade in `wallet_daily_stats`
+    pub active_days: i64,
+}
+
+/// When a wallet counts as which kind of bot
+#[derive(Debug, Clone)]
+pub struct ClassThresholds {
+    /// Sandwiches before a wallet is a sandwicher
+    pub min_sandwiches: i32,
+    /// Sniper buys before a wallet can be a sniper...
+    pub min_sniper_buys: i32,
+    /// ...and the share of its buys they must make up
+    pub sniper_buy_share: f64,
+    /// Average trades per active day from which a wallet is a bot
+    pub hf_trades_per_day: f64,
+}
+
+impl Default for ClassThresholds {
+    fn default() -> Self {
+        Self {
+            min_sandwiches: 2,
+            min_sniper_buys: 3,
+            sniper_buy_share: 0.5,
+            hf_trades_per_day: 200.0,
+        }
+    }
+}
+
+impl ClassThresholds {
+    /// The first matching class, checked from most to least harmful to copy
+    pub fn classify(&self, c: &ClassCounters) -> WalletClass {
+        if c.sandwiches >= self.min_sandwiches.max(1) {
+            return WalletClass::Sandwicher;
+        }
+        if c.sniper_buys >= self.min_sniper_buys.max(1)
+            && c.sniper_buys as f64 >= self.sniper_buy_share * c.buy_count as f64
+        {
+            return WalletClass::Sniper;
+        }
+        if c.active_days > 0 && c.total_trades as f64 / c.active_days as f64 >= self.hf_trades_per_day {
+            return WalletClass::HfBot;
+        }
+        WalletClass::Manual
+    }
+}
+
+/// Postgres: add the signals to `wallet_stats` in one UNNEST update
+#[cfg(feature = "postgres")]
+pub(crate) async fn add_signals(conn: &mut Connection, signals: &HashMap<String, ClassSignals>) -> Result<()> {
+    if signals.is_empty() {
+        return Ok(());
+    }
+    let wallets: Vec<&str> = signals.keys().map(String::as_str).collect();
+    let snipes: Vec<i32> = signals.values().map(|s| s.sniper_buys).collect();
+    let sandwiches: Vec<i32> = signals.values().map(|s| s.sandwiches).collect();
+    sqlx::query(
+        r#"
+        UPDATE wallet_stats w SET
+            sniper_buys = w.sniper_buys + s.sniper_buys,
+            sandwiches = w.sandwiches + s.sandwiches
+        FROM UNNEST($1::text[], $2::int4[], $3::int4[]) AS s(wallet, sniper_buys, sandwiches)
+        WHERE w.wallet = s.wallet
+        "#,
+    )
+    .bind(&wallets)
+    .bind(&snipes)
+    .bind(&sandwiches)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// SQLite: one update per wallet
+#[cfg(feature = "sqlite")]
+pub(crate) async fn add_signals(conn: &mut Connection, signals: &HashMap<String, ClassSignals>) -> Result<()> {
+    for (wallet, s) in signals {
+        sqlx::query(
+            "UPDATE wallet_stats SET sniper_buys = sniper_buys + $2, sandwiches = sandwiches + $3 WHERE wallet = $1",
+        )
+        .bind(wallet)
+        .bind(s.sniper_buys)
+        .bind(s.sandwiches)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+#[derive(sqlx::FromRow)]
+struct CounterRow {
+    wallet: String,
+    #[sqlx(flatten)]
+    counters: ClassCounters,
+}
+
+/// Recompute `wallet_class` of `wallets` from their stored counters
+pub(crate) async fn reclassify(
+    conn: &mut Connection,
+    thresholds: &ClassThresholds,
+    wallets: &[String],
+) -> Result<()> {
+    if wallets.is_empty() {
+        return Ok(());
+    }
+    let rows: Vec<CounterRow> = fetch_for_wallets(
+        conn,
+        r#"SELECT wallet, total_trades, buy_count, sniper_buys, sandwiches,
+                  (SELECT COUNT(*) FROM wallet_daily_stats d
+                   WHERE d.wallet = wallet_stats.wallet AND d.trades > 0) AS active_days
+           FROM wallet_stats WHERE {wallets}"#,
+        wallets,
+    )
+    .await?;
+    let classes: Vec<(String, WalletClass)> = rows
+        .into_iter()
+        .map(|row| (row.wallet, thresholds.classify(&row.counters)))
+        .collect();
+    write_classes(conn, &classes).await
+}
+
+#[cfg(feature = "postgres")]
+async fn write_classes(conn: &mut Connection, classes: &[(String, WalletClass)]) -> Result<()> {
+    let wallets: Vec<&str> = classes.iter().map(|(w, _)| w.as_str()).collect();
+    let names: Vec<&str> = classes.iter().map(|(_, c)| c.as_str()).collect();
+    sqlx::query(
+        r#"
+        UPDATE wallet_stats w SET wallet_class = s.wallet_class
+        FROM UNNEST($1::text[], $2::text[]) AS s(wallet, wallet_class)
+        WHERE w.wallet = s.wallet AND w.wallet_class <> s.wallet_class
+        "#,
+    )
+    .bind(&wallets)
+    .bind(&names)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+async fn write_classes(conn: &mut Connection, classes: &[(String, WalletClass)]) -> Result<()> {
+    for (wallet, class) in classes {
+        sqlx::query("UPDATE wallet_stats SET wallet_class = $2 WHERE wallet = $1 AND wallet_class <> $2")
+            .bind(wallet)
+            .bind(class.as_str())
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Reclassify every wallet, e.g. after changing the thresholds
+pub async fn reclassify_all(pool: &Pool, thresholds: &ClassThresholds) -> Result<usize> {
+    let mut after = String::new();
+    let mut total = 0;
+    loop {
+        let wallets: Vec<String> = sqlx::query_scalar(
+            "SELECT wallet FROM wallet_stats WHERE wallet > $1 ORDER BY wallet LIMIT $2",
+        )
+        .bind(&after)
+        .bind(RECLASSIFY_BATCH)
+        .fetch_all(pool)
+        .await?;
+        let Some(last) = wallets.last() else { break };
+        after = last.clone();
+
+        let mut tx = pool.begin().await?;
+        reclassify(&mut tx, thresholds, &wallets).await?;
+        tx.commit().await?;
+        total += wallets.len();
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drained(window: &mut SlotWindow) -> HashMap<String, ClassSignals> {
+        let mut out = HashMap::new();
+        window.drain(true, &mut out);
+        out
+    }
+
+    #[test]
+    fn buys_in_the_create_slot_are_snipes_except_the_creators() {
+        let mut window = SlotWindow::new();
+        window.record("dev", "CREATE", "mint", 10, 0);
+        window.record("dev", "BUY", "mint", 10, 0);
+        window.record("sniper", "BUY", "mint", 10, 3);
+        window.record("late", "BUY", "mint", 11, 1);
+
+        let out = drained(&mut window);
+        assert_eq!(out["sniper"].sniper_buys, 1);
+        assert!(!out.contains_key("dev") && !out.contains_key("late"));
+        assert!(window.is_empty());
+    }
+
+    #[test]
+    fn bracketing_another_buy_in_one_slot_is_a_sandwich() {
+        let mut window = SlotWindow::new();
+        // Arrival order doesn't matter, the block position does
+        window.record("mev", "SELL", "mint", 20, 7);
+        window.record("victim", "BUY", "mint", 20, 5);
+        window.record("mev", "BUY", "mint", 20, 4);
+        // Buy and sell with nobody in between
+        window.record("flipper", "BUY", "other", 20, 1);
+        window.record("flipper", "SELL", "other", 20, 2);
+
+        let out = drained(&mut window);
+        assert_eq!(out["mev"].sandwiches, 1);
+        assert!(!out.contains_key("victim") && !out.contains_key("flipper"));
+    }
+
+    #[test]
+    fn slots_settle_once_they_lag_the_newest() {
+        let mut window = SlotWindow::new();
+        window.record("dev", "CREATE", "mint", 30, 0);
+        window.record("sniper", "BUY", "mint", 30, 1);
+        let mut out = HashMap::new();
+        window.drain(false, &mut out);
+        assert!(out.is_empty());
+
+        window.record("other", "BUY", "x", 30 + SLOT_LAG, 0);
+        window.drain(false, &mut out);
+        assert_eq!(out["sniper"].sniper_buys, 1);
+        assert!(!window.is_empty());
+    }
+
+    #[test]
+    fn thresholds_pick_the_most_harmful_class() {
+        let t = ClassThresholds::default();
+        let person = ClassCounters { total_trades: 40, buy_count: 20, active_days: 5, ..Default::default() };
+        assert_eq!(t.classify(&person), WalletClass::Manual);
+        assert_eq!(t.classify(&ClassCounters { sniper_buys: 15, ..person }), WalletClass::Sniper);
+        // Occasional snipes among many manual buys
+        assert_eq!(t.classify(&ClassCounters { sniper_buys: 3, ..person }), WalletClass::Manual);
+        assert_eq!(
+            t.classify(&ClassCounters { sniper_buys: 15, sandwiches: 2, ..person }),
+            WalletClass::Sandwicher
+        );
+        assert_eq!(t.classify(&ClassCounters { total_trades: 2_000, ..person }), WalletClass::HfBot);
+    }
+}
@@ -0,0 +1,316 @@
+//! Sybil clusters - wallets that buy together and share a funder
+//!
+//! Coordinated launches show up as several wallets buying the same mint in
+//! the same slot (a bundle). Buyers of a bundle are linked when they got SOL
+//! from the same funder, or from one another; linked wallets go into one
+//! `wallet_clusters` cluster, so discovery ranks and promotes one of them
+//! instead of each as an independent trader.
+//!
+//! Funding comes from `wallet_funding`: System Program transfers to a trader
+//! inside the transactions the ingestor sees.
+
+use crate::classify::SLOT_LAG;
+use crate::scoring::fetch_for_wallets;
+use crate::Connection;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// `AND` clause for a query on `wallet_stats` keeping one wallet per cluster:
+/// the best-scoring one (ties go to the smaller address)
+pub(crate) const ONE_PER_CLUSTER: &str = r#"
+    AND NOT EXISTS (
+        SELECT 1 FROM wallet_clusters mine
+        JOIN wallet_clusters peer ON peer.cluster_id = mine.cluster_id AND peer.wallet <> mine.wallet
+        JOIN wallet_stats better ON better.wallet = peer.wallet
+        WHERE mine.wallet = wallet_stats.wallet
+          AND (better.profit_score > wallet_stats.profit_score
+               OR (better.profit_score = wallet_stats.profit_score AND better.wallet < wallet_stats.wallet))
+    )"#;
+
+/// `AND` clause for a query on `wallet_stats` dropping wallets whose cluster
+/// already has a tracked member
+pub(crate) const NO_TRACKED_PEER: &str = r#"
+    AND NOT EXISTS (
+        SELECT 1 FROM wallet_clusters mine
+        JOIN wallet_clusters peer ON peer.cluster_id = mine.cluster_id AND peer.wallet <> mine.wallet
+        JOIN wallet_stats tracked ON tracked.wallet = peer.wallet AND tracked.is_tracked
+        WHERE mine.wallet = wallet_stats.wallet
+    )"#;
+
+/// SOL `funder` sent to `wallet` in a transaction where `wallet` bought
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundingEdge {
+    pub wallet: String,
+    pub funder: String,
+    pub lamports: u64,
+    pub seen_at: DateTime<Utc>,
+}
+
+/// Distinct wallets that bought `mint` in `slot`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bundle {
+    pub mint: String,
+    pub slot: u64,
+    pub wallets: Vec<String>,
+}
+
+/// Buyers of one bundle linked by funding
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkedGroup {
+    /// Sorted
+    pub wallets: Vec<String>,
+    /// A funder shared by the group, if it isn't one of them
+    pub funder: Option<String>,
+}
+
+/// BUYs of the last few slots by (slot, mint), handed out as bundles once
+/// their slot is `SLOT_LAG` behind the newest one seen (like `SlotWindow`)
+#[derive(Debug, Default)]
+pub struct BundleWindow {
+    slots: BTreeMap<u64, HashMap<String, Vec<String>>>,
+    newest: u64,
+    /// Slots below this were already handed out
+    settled_below: u64,
+}
+
+impl BundleWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_buy(&mut self, wallet: &str, mint: &str, slot: u64) {
+        if slot < self.settled_below {
+            return;
+        }
+        self.newest = self.newest.max(slot);
+        let buyers = self.slots.entry(slot).or_default().entry(mint.to_string()).or_default();
+        if !buyers.iter().any(|w| w == wallet) {
+            buyers.push(wallet.to_string());
+        }
+    }
+
+    /// Drop the settled slots (all of them with `all`), adding the (mint, slot)s
+    /// with at least two buyers to `out`
+    pub fn drain(&mut self, all: bool, out: &mut Vec<Bundle>) {
+        let keep_from = if all { self.newest + 1 } else { (self.newest + 1).saturating_sub(SLOT_LAG) };
+        self.settled_below = self.settled_below.max(keep_from);
+        let kept = self.slots.split_off(&keep_from);
+        for (slot, mints) in std::mem::replace(&mut self.slots, kept) {
+            for (mint, wallets) in mints {
+                if wallets.len() >= 2 {
+                    out.push(Bundle { mint, slot, wallets });
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+/// Split a bundle's buyers into groups linked by `funders` (wallet -> who
+/// funded it): buyers sharing a funder, or funded by another buyer. Buyers
+/// linked to no one are left out.
+pub fn link_buyers(bundle: &Bundle, funders: &HashMap<String, Vec<String>>) -> Vec<LinkedGroup> {
+    let wallets = &bundle.wallets;
+    let index: HashMap<&str, usize> = wallets.iter().enumerate().map(|(i, w)| (w.as_str(), i)).collect();
+    let mut parent: Vec<usize> = (0..wallets.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    // funder -> first buyer it funded
+    let mut first_funded: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut shared: Vec<&str> = Vec::new();
+    for (i, wallet) in wallets.iter().enumerate() {
+        for funder in funders.get(wallet).into_iter().flatten() {
+            let linked_to = match index.get(funder.as_str()) {
+                Some(&j) => Some(j),
+                None => match first_funded.get(funder.as_str()) {
+                    Some(&j) => {
+                        shared.push(funder);
+                        Some(j)
+                    }
+                    None => {
+                        first_funded.insert(funder, i);
+                        None
+                    }
+                },
+            };
+            if let Some(j) = linked_to {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[a] = b;
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for (i, wallet) in wallets.iter().enumerate() {
+        let r = root(&mut parent, i);
+        groups.entry(r).or_default().push(wallet.clone());
+    }
+    groups
+        .into_values()
+        .filter(|members| members.len() >= 2)
+        .map(|mut members| {
+            members.sort();
+            let funder = shared
+                .iter()
+                .filter(|f| members.iter().any(|m| first_funded.get(**f).is_some_and(|&i| &wallets[i] == m)))
+                .min()
+                .map(|f| f.to_string());
+            LinkedGroup { wallets: members, funder }
+        })
+        .collect()
+}
+
+/// Store funding edges, then cluster the linked buyers of each bundle.
+/// Returns the wallets that joined a cluster or moved to another one.
+pub(crate) async fn write(conn: &mut Connection, funding: &[FundingEdge], bundles: &[Bundle], now: DateTime<Utc>) -> Result<usize> {
+    for edge in funding {
+        sqlx::query(
+            r#"
+            INSERT INTO wallet_funding (wallet, funder, lamports, first_seen) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (wallet, funder) DO UPDATE SET lamports = wallet_funding.lamports + excluded.lamports
+            "#,
+        )
+        .bind(&edge.wallet)
+        .bind(&edge.funder)
+        .bind(edge.lamports as i64)
+        .bind(edge.seen_at)
+        .execute(&mut *conn)
+        .await?;
+    }
+    if bundles.is_empty() {
+        return Ok(0);
+    }
+
+    let buyers: Vec<String> = bundles
+        .iter()
+        .flat_map(|b| b.wallets.iter().cloned())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let mut funders: HashMap<String, Vec<String>> = HashMap::new();
+    let rows: Vec<(String, String)> =
+        fetch_for_wallets(conn, "SELECT wallet, funder FROM wallet_funding WHERE {wallets}", &buyers).await?;
+    for (wallet, funder) in rows {
+        funders.entry(wallet).or_default().push(funder);
+    }
+
+    let mut clustered = 0;
+    for bundle in bundles {
+        for group in link_buyers(bundle, &funders) {
+            clustered += merge(conn, &group, bundle, now).await?;
+        }
+    }
+    Ok(clustered)
+}
+
+/// Put a group into one cluster, merging the clusters its members were in.
+/// The surviving cluster keeps the smallest id.
+async fn merge(conn: &mut Connection, group: &LinkedGroup, bundle: &Bundle, now: DateTime<Utc>) -> Result<usize> {
+    let current: Vec<(String, String)> =
+        fetch_for_wallets(conn, "SELECT wallet, cluster_id FROM wallet_clusters WHERE {wallets}", &group.wallets).await?;
+    let current: HashMap<String, String> = current.into_iter().collect();
+    let cluster_id = current.values().chain(&group.wallets).min().cloned().unwrap_or_default();
+
+    let mut changed = 0;
+    let absorbed: HashSet<&String> = current.values().filter(|id| **id != cluster_id).collect();
+    for old in absorbed {
+        changed += sqlx::query("UPDATE wallet_clusters SET cluster_id = $1 WHERE cluster_id = $2")
+            .bind(&cluster_id)
+            .bind(old)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected() as usize;
+    }
+    for wallet in group.wallets.iter().filter(|w| !current.contains_key(*w)) {
+        sqlx::query(
+            r#"
+            INSERT INTO wallet_clusters (wallet, cluster_id, funder, mint, slot, linked_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+        )
+        .bind(wallet)
+        .bind(&cluster_id)
+        .bind(&group.funder)
+        .bind(&bundle.mint)
+        .bind(bundle.slot as i64)
+        .bind(now)
+        .execute(&mut *conn)
+        .await?;
+        changed += 1;
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle(wallets: &[&str]) -> Bundle {
+        Bundle { mint: "Mint".into(), slot: 1, wallets: wallets.iter().map(|w| w.to_string()).collect() }
+    }
+
+    fn funded(edges: &[(&str, &str)]) -> HashMap<String, Vec<String>> {
+        let mut funders: HashMap<String, Vec<String>> = HashMap::new();
+        for (wallet, funder) in edges {
+            funders.entry(wallet.to_string()).or_default().push(funder.to_string());
+        }
+        funders
+    }
+
+    #[test]
+    fn only_co_funded_buyers_are_linked() {
+        // A and B share Boss, C is funded by A, D has its own funder, E none
+        let groups = link_buyers(
+            &bundle(&["A", "B", "C", "D", "E"]),
+            &funded(&[("A", "Boss"), ("B", "Boss"), ("C", "A"), ("D", "Other")]),
+        );
+        assert_eq!(
+            groups,
+            vec![LinkedGroup { wallets: vec!["A".into(), "B".into(), "C".into()], funder: Some("Boss".into()) }]
+        );
+
+        // Two buyers funded by one another have no outside funder
+        let groups = link_buyers(&bundle(&["A", "B"]), &funded(&[("B", "A")]));
+        assert_eq!(groups, vec![LinkedGroup { wallets: vec!["A".into(), "B".into()], funder: None }]);
+        assert!(link_buyers(&bundle(&["A", "B"]), &HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn bundles_come_out_once_their_slot_settles() {
+        let mut window = BundleWindow::new();
+        window.record_buy("A", "Mint", 10);
+        window.record_buy("B", "Mint", 10);
+        window.record_buy("A", "Mint", 10);
+        window.record_buy("C", "Other", 10);
+        window.record_buy("D", "Mint", 11);
+
+        let mut out = Vec::new();
+        window.drain(false, &mut out);
+        assert!(out.is_empty());
+        window.record_buy("E", "Mint", 12);
+        window.drain(false, &mut out);
+        // A single buyer (Other) is no bundle
+        assert_eq!(out, vec![bundle_at(&["A", "B"], 10)]);
+
+        // Too late for slot 10
+        window.record_buy("F", "Mint", 10);
+        out.clear();
+        window.drain(true, &mut out);
+        assert!(out.is_empty());
+        assert!(window.is_empty());
+    }
+
+    fn bundle_at(wallets: &[&str], slot: u64) -> Bundle {
+        Bundle { slot, ..bundle(wallets) }
+    }
+}
@@ -0,0 +1,316 @@
+//! Creator (dev) wallets: who launched each mint, and whether they dumped it
+//!
+//! A mint's creator is its CREATE signer. Every token the creator later sells,
+//! or moves out of its wallet any other way (a drop in its balance beyond what
+//! it sold), counts toward its outflow. The first time the outflow reaches
+//! `dump_fraction` of the supply the tracker reports a `DevDump`.
+//!
+//! The ingestor only subscribes to Pump.fun transactions, so a creator's
+//! balance is only seen inside those. A plain SPL transfer out of its wallet
+//! is never observed: a dev who moves its tokens to another wallet and sells
+//! from there is not detected as dumping.
+//!
+//! Mints stay in memory for `window` after creation; `mint_creators` keeps
+//! them for good.
+
+use crate::mints::{MintActivity, PUMP_TOKEN_SUPPLY};
+use crate::raw_events::RawEvent;
+use crate::Pool;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+
+/// One row of `mint_creators`
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct MintCreator {
+    pub mint: String,
+    pub creator: String,
+    pub create_sig: String,
+    pub created_at: DateTime<Utc>,
+    /// Raw token units sold on the curve
+    pub tokens_sold: i64,
+    /// Raw token units that left the creator's wallet other than by selling
+    pub tokens_transferred: i64,
+    pub dumped_at: Option<DateTime<Utc>>,
+    pub dump_sig: Option<String>,
+}
+
+impl MintCreator {
+    pub fn supply_fraction_out(&self) -> f64 {
+        (self.tokens_sold + self.tokens_transferred) as f64 / PUMP_TOKEN_SUPPLY as f64
+    }
+}
+
+/// The creator crossed the dump threshold in transaction `sig`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DevDump {
+    pub mint: String,
+    pub creator: String,
+    pub sig: String,
+    pub program: String,
+    pub slot: u64,
+    pub at: DateTime<Utc>,
+    /// Moved out in this transaction
+    pub sold: u64,
+    pub transferred: u64,
+    pub sol: Option<f64>,
+    /// Share of the supply moved out since creation
+    pub supply_fraction: f64,
+}
+
+impl DevDump {
+    /// The `DEV_SELL` raw event recorded for the dump
+    pub fn event(&self) -> RawEvent {
+        RawEvent {
+            ts_ns: self.at.timestamp_nanos_opt().unwrap_or_default(),
+            slot: Some(self.slot as i64),
+            sig: Some(self.sig.clone()),
+            wallet: self.creator.clone(),
+            alias: None,
+            program: self.program.clone(),
+            action: "DEV_SELL".to_string(),
+            mint: Some(self.mint.clone()),
+            base_mint: None,
+            quote_mint: None,
+            amount_in: Some((self.sold + self.transferred) as f64),
+            amount_out: self.sol,
+            price_est: None,
+            fee_sol: None,
+            ix_accounts_json: None,
+            meta_json: Some(json!({
+                "sold_tokens": self.sold,
+                "transferred_tokens": self.transferred,
+                "supply_fraction_out": self.supply_fraction,
+            })),
+            leader_wallet: None,
+            block_time: None,
+            recv_time_ns: self.at.timestamp_nanos_opt(),
+            ix_index: None,
+            decode_ok: true,
+            decode_err: None,
+            pre_balance_sol: None,
+            post_balance_sol: None,
+            balance_change_sol: None,
+            role: Some("signer".to_string()),
+            wallet_roles: None,
+        }
+    }
+}
+
+/// In-memory creators of recently launched mints
+pub struct CreatorTracker {
+    mints: HashMap<String, MintCreator>,
+    /// Changed since the last flush
+    dirty: HashSet<String>,
+    dump_fraction: f64,
+    window: Duration,
+}
+
+impl CreatorTracker {
+    pub fn new(dump_fraction: f64, window: Duration) -> Self {
+        Self {
+            mints: HashMap::new(),
+            dirty: HashSet::new(),
+            dump_fraction,
+            window,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.mints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mints.is_empty()
+    }
+
+    pub fn get(&self, mint: &str) -> Option<&MintCreator> {
+        self.mints.get(mint)
+    }
+
+    /// Pick up mints created within the window from `mint_creators` (after a restart)
+    pub async fn load(&mut self, pool: &Pool, now: DateTime<Utc>) -> Result<usize> {
+        let rows: Vec<MintCreator> = sqlx::query_as(
+            r#"
+            SELECT mint, creator, create_sig, created_at, tokens_sold, tokens_transferred, dumped_at, dump_sig
+            FROM mint_creators WHERE created_at >= $1
+            "#,
+        )
+        .bind(now - self.window)
+        .fetch_all(pool)
+        .await?;
+        let loaded = rows.len();
+        for row in rows {
+            self.mints.insert(row.mint.clone(), row);
+        }
+        Ok(loaded)
+    }
+
+    /// Note a CREATE and the creator's outflow in one transaction
+    pub fn record(&mut self, activity: &MintActivity) -> Option<DevDump> {
+        if let Some(creator) = &activity.creator {
+            self.mints.entry(activity.mint.clone()).or_insert_with(|| MintCreator {
+                mint: activity.mint.clone(),
+                creator: creator.clone(),
+                create_sig: activity.sig.clone(),
+                created_at: activity.seen_at,
+                tokens_sold: 0,
+                tokens_transferred: 0,
+                dumped_at: None,
+                dump_sig: None,
+            });
+            self.dirty.insert(activity.mint.clone());
+        }
+
+        let state = self.mints.get_mut(&activity.mint)?;
+        let sold = activity.sold_by(&state.creator);
+        let balance_drop = activity
+            .balance_of(&state.creator)
+            .map(|b| b.pre.saturating_sub(b.post))
+            .unwrap_or(0);
+        let transferred = balance_drop.saturating_sub(sold);
+        if sold == 0 && transferred == 0 {
+            return None;
+        }
+
+        state.tokens_sold += sold as i64;
+        state.tokens_transferred += transferred as i64;
+        self.dirty.insert(activity.mint.clone());
+
+        let supply_fraction = state.supply_fraction_out();
+        if state.dumped_at.is_some() || supply_fraction < self.dump_fraction {
+            return None;
+        }
+        state.dumped_at = Some(activity.seen_at);
+        state.dump_sig = Some(activity.sig.clone());
+
+        let sol = activity
+            .fills
+            .iter()
+            .filter(|f| !f.is_buy && f.wallet == state.creator)
+            .filter_map(|f| f.sol)
+            .reduce(|a, b| a + b);
+        Some(DevDump {
+            mint: activity.mint.clone(),
+            creator: state.creator.clone(),
+            sig: activity.sig.clone(),
+            program: activity.program.clone(),
+            slot: activity.slot,
+            at: activity.seen_at,
+            sold,
+            transferred,
+            sol,
+            supply_fraction,
+        })
+    }
+
+    /// Write changed mints to `mint_creators` and forget the ones past the window.
+    /// Returns rows written.
+    pub async fn flush(&mut self, pool: &Pool, now: DateTime<Utc>) -> Result<usize> {
+        if !self.dirty.is_empty() {
+            let mut tx = pool.begin().await?;
+            for mint in &self.dirty {
+                let Some(c) = self.mints.get(mint) else {
+                    continue;
+                };
+                sqlx::query(
+                    r#"
+                    INSERT INTO mint_creators
+                        (mint, creator, create_sig, created_at, tokens_sold, tokens_transferred, dumped_at, dump_sig)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                    ON CONFLICT (mint) DO UPDATE SET
+                        tokens_sold = excluded.tokens_sold,
+                        tokens_transferred = excluded.tokens_transferred,
+                        dumped_at = excluded.dumped_at,
+                        dump_sig = excluded.dump_sig
+                    "#,
+                )
+                .bind(&c.mint)
+                .bind(&c.creator)
+                .bind(&c.create_sig)
+                .bind(c.created_at)
+                .bind(c.tokens_sold)
+                .bind(c.tokens_transferred)
+                .bind(c.dumped_at)
+                .bind(&c.dump_sig)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+        }
+
+        let written = self.dirty.len();
+        self.dirty.clear();
+        let cutoff = now - self.window;
+        self.mints.retain(|_, c| c.created_at >= cutoff);
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mints::{BalanceChange, MintFill};
+    use chrono::TimeZone;
+
+    const DEV: &str = "Dev1111111111111111111111111111111111111111";
+    const PERCENT: u64 = PUMP_TOKEN_SUPPLY / 100;
+
+    fn activity(sig: &str, creator: Option<&str>, fills: Vec<MintFill>, balances: Vec<BalanceChange>) -> MintActivity {
+        MintActivity {
+            mint: "Mint".to_string(),
+            sig: sig.to_string(),
+            program: "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P".to_string(),
+            slot: 1,
+            seen_at: Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap(),
+            creator: creator.map(str::to_string),
+            fills,
+            balances,
+        }
+    }
+
+    fn fill(wallet: &str, is_buy: bool, tokens: u64) -> MintFill {
+        MintFill { wallet: wallet.to_string(), is_buy, tokens, sol: Some(1.0) }
+    }
+
+    fn balance(owner: &str, pre: u64, post: u64) -> BalanceChange {
+        BalanceChange { owner: owner.to_string(), pre, post }
+    }
+
+    #[test]
+    fn sells_and_transfers_add_up_to_one_dump_alert() {
+        let mut tracker = CreatorTracker::new(0.04, Duration::hours(24));
+        // Creator buys 10% at launch
+        let create = activity("c", Some(DEV), vec![fill(DEV, true, 10 * PERCENT)], vec![balance(DEV, 0, 10 * PERCENT)]);
+        assert_eq!(tracker.record(&create), None);
+
+        // Someone else selling doesn't count
+        let other = activity("o", None, vec![fill("Other", false, 3 * PERCENT)], vec![]);
+        assert_eq!(tracker.record(&other), None);
+
+        // 2% sold, then 2% sent away alongside a 0.5% sale
+        let sell = activity("s1", None, vec![fill(DEV, false, 2 * PERCENT)], vec![balance(DEV, 10 * PERCENT, 8 * PERCENT)]);
+        assert_eq!(tracker.record(&sell), None);
+        let dump = activity(
+            "s2",
+            None,
+            vec![fill(DEV, false, PERCENT / 2)],
+            vec![balance(DEV, 8 * PERCENT, 8 * PERCENT - PERCENT / 2 - 2 * PERCENT)],
+        );
+        let alert = tracker.record(&dump).unwrap();
+        assert_eq!((alert.sold, alert.transferred), (PERCENT / 2, 2 * PERCENT));
+        assert!((alert.supply_fraction - 0.045).abs() < 1e-12, "{}", alert.supply_fraction);
+    }
+
+    #[test]
+    fn alerts_once_past_the_threshold() {
+        let mut tracker = CreatorTracker::new(0.05, Duration::hours(24));
+        tracker.record(&activity("c", Some(DEV), vec![], vec![]));
+        let alert = tracker.record(&activity("s1", None, vec![fill(DEV, false, 6 * PERCENT)], vec![])).unwrap();
+        assert_eq!(alert.event().action, "DEV_SELL");
+        assert_eq!(alert.event().amount_in, Some((6 * PERCENT) as f64));
+        assert_eq!(tracker.record(&activity("s2", None, vec![fill(DEV, false, PERCENT)], vec![])), None);
+        assert_eq!(tracker.get("Mint").unwrap().dump_sig.as_deref(), Some("s1"));
+    }
+}
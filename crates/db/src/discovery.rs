@@ -1,8 +1,13 @@
 //! Discovery database - track ALL wallets for profitability analysis
 
 use anyhow::Result;
-use chrono::{DateTime, Utc};
-use sqlx::PgPool;
+use chrono::{DateTime, Duration, NaiveDate, SubsecRound, Utc};
+use crate::classify::{self, ClassSignals, ClassThresholds, SlotWindow, WalletClass};
+use crate::clusters::{self, Bundle, BundleWindow, FundingEdge};
+use crate::scoring::{self, RiskAdjusted, ScoreComponents, ScoringModel};
+use crate::{Connection, Pool};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 /// Wallet statistics for discovery
 #[derive(Debug, Clone, sqlx::FromRow)]
@@ -22,223 +27,1223 @@ pub struct WalletStats {
     pub win_rate: f64,
     pub is_tracked: bool,
     pub profit_score: f64,
+    /// `WalletClass::as_str` of the wallet's classification
+    pub wallet_class: String,
+    /// What `profit_score` was computed from
+    #[sqlx(flatten)]
+    pub components: ScoreComponents,
 }
 
-/// Update wallet stats after a trade
-/// Returns true if this is a new wallet being discovered
-pub async fn update_wallet_stats(
-    pool: &PgPool,
-    wallet: &str,
-    action: &str,
-    sol_amount: Option<f64>,
-    mint: Option<&str>,
-) -> Result<bool> {
-    let sol_amt = sol_amount.unwrap_or(0.0);
-    
-    // Check if wallet exists before insert
-    let exists: Option<i32> = sqlx::query_scalar("SELECT 1 FROM wallet_stats WHERE wallet = $1")
-        .bind(wallet)
-        .fetch_optional(pool)
-        .await?;
-    
-    let is_new_wallet = exists.is_none();
-    
-    match action {
-        "BUY" => {
-            sqlx::query(
-                r#"
-                INSERT INTO wallet_stats (wallet, first_seen, last_seen, total_trades, buy_count, total_sol_in)
-                VALUES ($1, NOW(), NOW(), 1, 1, $2)
-                ON CONFLICT (wallet) DO UPDATE SET
-                    last_seen = NOW(),
-                    total_trades = wallet_stats.total_trades + 1,
-                    buy_count = wallet_stats.buy_count + 1,
-                    total_sol_in = wallet_stats.total_sol_in + $2
-                "#
-            )
-            .bind(wallet)
-            .bind(sol_amt)
-            .execute(pool)
-            .await?;
-            
-            // Track open position
-            if let Some(m) = mint {
-                sqlx::query(
-                    r#"
-                    INSERT INTO positions (wallet, mint, bought_at, token_amount, sol_spent, avg_buy_price)
-                    VALUES ($1, $2, NOW(), 0, $3, 0)
-                    ON CONFLICT (wallet, mint, bought_at) DO UPDATE SET
-                        sol_spent = positions.sol_spent + $3
-                    "#
-                )
-                .bind(wallet)
-                .bind(m)
-                .bind(sol_amt)
-                .execute(pool)
-                .await?;
+/// Per-wallet counters accumulated between flushes
+#[derive(Debug, Clone)]
+struct WalletDelta {
+    first_seen: DateTime<Utc>,
+    last_seen: DateTime<Utc>,
+    trades: i32,
+    buys: i32,
+    sells: i32,
+    creates: i32,
+    sol_in: f64,
+    sol_out: f64,
+}
+
+/// One wallet's counters for one UTC day (`wallet_daily_stats`)
+#[derive(Debug, Default, Clone, PartialEq)]
+struct DailyDelta {
+    trades: i32,
+    buys: i32,
+    sells: i32,
+    sol_in: f64,
+    sol_out: f64,
+    /// Realized P&L, booked on the day of the SELL
+    pnl: f64,
+}
+
+/// A BUY or SELL of one (wallet, mint) waiting to be matched against its lots
+#[derive(Debug, Clone, Copy)]
+struct PendingFill {
+    at: DateTime<Utc>,
+    is_buy: bool,
+    /// None when the instruction's token amount couldn't be decoded
+    tokens: Option<f64>,
+    sol: f64,
+}
+
+/// One BUY lot of (wallet, mint), as stored in `positions`.
+///
+/// A lot with `token_amount == 0` has an unknown size (opened before token
+/// quantities were tracked, or from an undecoded amount); the next SELL closes
+/// it whole, like every lot used to be.
+#[derive(Debug, Clone, PartialEq)]
+struct Lot {
+    /// Key in `positions`: the BUY's time, pushed a few microseconds later
+    /// when another lot of the pair already has it
+    bought_at: DateTime<Utc>,
+    /// When the BUY happened, for matching sells (`bought_at` for stored lots)
+    opened_at: DateTime<Utc>,
+    token_amount: f64,
+    tokens_remaining: f64,
+    sol_spent: f64,
+    sol_received: f64,
+    realized_pnl: f64,
+    sold_at: Option<DateTime<Utc>>,
+    is_closed: bool,
+    /// Changed by this flush and needs writing back
+    dirty: bool,
+}
+
+impl Lot {
+    fn open(at: DateTime<Utc>, tokens: f64, sol: f64) -> Self {
+        Self {
+            bought_at: at,
+            opened_at: at,
+            token_amount: tokens,
+            tokens_remaining: tokens,
+            sol_spent: sol,
+            sol_received: 0.0,
+            realized_pnl: 0.0,
+            sold_at: None,
+            is_closed: false,
+            dirty: true,
+        }
+    }
+
+    fn has_size(&self) -> bool {
+        self.token_amount > 0.0
+    }
+
+    fn avg_buy_price(&self) -> f64 {
+        if self.has_size() {
+            self.sol_spent / self.token_amount
+        } else {
+            0.0
+        }
+    }
+
+    /// Cost basis of the tokens still held
+    fn open_cost(&self) -> f64 {
+        if self.has_size() {
+            self.sol_spent * self.tokens_remaining / self.token_amount
+        } else {
+            self.sol_spent
+        }
+    }
+}
+
+/// What matching one flush's fills realized for a wallet
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Realized {
+    pnl: f64,
+    /// `pnl` split by the UTC day of the SELL that realized it
+    daily_pnl: HashMap<NaiveDate, f64>,
+    wins: i32,
+    losses: i32,
+    opened: usize,
+    closed: usize,
+    /// Sells (or the part of a sell) with no earlier open lot to take tokens
+    /// from, i.e. inventory bought before the wallet was seen. Their proceeds
+    /// are kept out of P&L.
+    unmatched_sells: i32,
+    unmatched_sol: f64,
+}
+
+/// Remaining tokens below this fraction of a lot count as fully sold
+const DUST: f64 = 1e-9;
+
+/// `at`, or the first microsecond after it that no lot uses as its key.
+/// Instructions of one transaction share a time, so a BUY, a SELL and another
+/// BUY there would otherwise land on one `positions` row.
+fn free_key(lots: &[Lot], at: DateTime<Utc>) -> DateTime<Utc> {
+    let mut key = at;
+    while lots.iter().any(|l| l.bought_at == key) {
+        key += Duration::microseconds(1);
+    }
+    key
+}
+
+/// Match one (wallet, mint)'s fills, in time order, against its open lots
+/// (oldest first). Every BUY opens its own lot. A SELL takes tokens from the
+/// oldest lots bought before it; a lot it only partly consumes stays open with
+/// the rest, and each lot realizes the sold share of its cost against the same
+/// share of proceeds. A lot counts as a win or loss once, when its last tokens
+/// are sold.
+///
+/// `lots` may also hold closed lots; they are never matched, but their keys
+/// are taken.
+fn apply_fills(lots: &mut Vec<Lot>, fills: &[PendingFill], out: &mut Realized) {
+    for fill in fills {
+        if fill.is_buy {
+            let key = free_key(lots, fill.at);
+            let at = lots.partition_point(|l| l.bought_at <= key);
+            lots.insert(at, Lot { bought_at: key, ..Lot::open(fill.at, fill.tokens.unwrap_or(0.0), fill.sol) });
+            out.opened += 1;
+            continue;
+        }
+
+        let sell_tokens = fill.tokens.filter(|t| *t > 0.0);
+        let mut tokens_left = sell_tokens.unwrap_or(0.0);
+        let mut proceeds_left = fill.sol;
+        let mut matched = false;
+
+        for lot in lots.iter_mut().filter(|l| !l.is_closed && l.opened_at <= fill.at) {
+            let (proceeds, cost) = match sell_tokens {
+                Some(sold) if lot.has_size() => {
+                    let take = tokens_left.min(lot.tokens_remaining);
+                    let cost = lot.sol_spent * take / lot.token_amount;
+                    lot.tokens_remaining -= take;
+                    tokens_left -= take;
+                    (fill.sol * take / sold, cost)
+                }
+                // Either side's size is unknown: close this lot whole with
+                // whatever is left of the sell
+                _ => {
+                    let cost = lot.open_cost();
+                    lot.tokens_remaining = 0.0;
+                    tokens_left = 0.0;
+                    (proceeds_left, cost)
+                }
+            };
+            proceeds_left -= proceeds;
+            matched = true;
+
+            lot.sold_at = Some(fill.at);
+            lot.sol_received += proceeds;
+            lot.realized_pnl += proceeds - cost;
+            lot.dirty = true;
+            out.pnl += proceeds - cost;
+            *out.daily_pnl.entry(fill.at.date_naive()).or_default() += proceeds - cost;
+
+            if lot.tokens_remaining <= lot.token_amount * DUST {
+                lot.tokens_remaining = 0.0;
+                lot.is_closed = true;
+                out.closed += 1;
+                if lot.realized_pnl > 0.0 {
+                    out.wins += 1;
+                } else {
+                    out.losses += 1;
+                }
+            }
+
+            if tokens_left <= sell_tokens.unwrap_or(0.0) * DUST {
+                break;
             }
         }
-        "SELL" => {
-            sqlx::query(
-                r#"
-                INSERT INTO wallet_stats (wallet, first_seen, last_seen, total_trades, sell_count, total_sol_out)
-                VALUES ($1, NOW(), NOW(), 1, 1, $2)
-                ON CONFLICT (wallet) DO UPDATE SET
-                    last_seen = NOW(),
-                    total_trades = wallet_stats.total_trades + 1,
-                    sell_count = wallet_stats.sell_count + 1,
-                    total_sol_out = wallet_stats.total_sol_out + $2,
-                    net_pnl_sol = wallet_stats.total_sol_out + $2 - wallet_stats.total_sol_in
-                "#
-            )
-            .bind(wallet)
-            .bind(sol_amt)
-            .execute(pool)
-            .await?;
-            
-            // Update position P&L
-            if let Some(m) = mint {
-                update_position_pnl(pool, wallet, m, sol_amt).await?;
+
+        let partly_unmatched = sell_tokens.is_some_and(|sold| tokens_left > sold * DUST);
+        if !matched || partly_unmatched {
+            out.unmatched_sells += 1;
+            out.unmatched_sol += proceeds_left;
+        }
+    }
+}
+
+/// Result of one aggregator flush
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FlushStats {
+    pub wallets: usize,
+    pub new_wallets: usize,
+    pub positions_opened: usize,
+    pub positions_closed: usize,
+    pub unmatched_sells: usize,
+    /// Wallets with new sniper or sandwich signals
+    pub signalled_wallets: usize,
+    /// Wallets that joined (or moved to another) sybil cluster
+    pub clustered_wallets: usize,
+}
+
+/// In-memory accumulator for discovery stats.
+///
+/// Trades are recorded as they are decoded and written out in a handful of
+/// set-based statements per flush instead of several round trips per trade.
+#[derive(Debug)]
+pub struct DiscoveryAggregator {
+    wallets: HashMap<String, WalletDelta>,
+    // (wallet, mint) -> buys and sells in the order they were recorded
+    fills: HashMap<(String, String), Vec<PendingFill>>,
+    days: HashMap<(String, NaiveDate), DailyDelta>,
+    scoring: Arc<dyn ScoringModel>,
+    window: SlotWindow,
+    // Bot signals of settled slots, not yet written
+    signals: HashMap<String, ClassSignals>,
+    classes: ClassThresholds,
+    bundle_window: BundleWindow,
+    // Same-slot buys of settled slots and funding seen, not yet written
+    bundles: Vec<Bundle>,
+    funding: Vec<FundingEdge>,
+}
+
+impl Default for DiscoveryAggregator {
+    fn default() -> Self {
+        Self::with_scoring(Arc::new(RiskAdjusted::default()))
+    }
+}
+
+impl DiscoveryAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Aggregator whose flushes rank wallets with `scoring`
+    pub fn with_scoring(scoring: Arc<dyn ScoringModel>) -> Self {
+        Self {
+            wallets: HashMap::new(),
+            fills: HashMap::new(),
+            days: HashMap::new(),
+            scoring,
+            window: SlotWindow::new(),
+            signals: HashMap::new(),
+            classes: ClassThresholds::default(),
+            bundle_window: BundleWindow::new(),
+            bundles: Vec::new(),
+            funding: Vec::new(),
+        }
+    }
+
+    /// Classify wallets with `classes` instead of the default thresholds
+    pub fn with_class_thresholds(mut self, classes: ClassThresholds) -> Self {
+        self.classes = classes;
+        self
+    }
+
+    /// Number of wallets with pending deltas
+    pub fn len(&self) -> usize {
+        self.wallets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wallets.is_empty()
+    }
+
+    /// Anything for `flush` to write: wallet deltas, or settled signals,
+    /// bundles or funding with no trade pending alongside them
+    pub fn has_pending(&self) -> bool {
+        !self.is_empty() || !self.signals.is_empty() || !self.bundles.is_empty() || !self.funding.is_empty()
+    }
+
+    /// Record one decoded trade
+    pub fn record(
+        &mut self,
+        wallet: &str,
+        action: &str,
+        sol_amount: Option<f64>,
+        token_amount: Option<f64>,
+        mint: Option<&str>,
+        at: DateTime<Utc>,
+    ) {
+        if !matches!(action, "BUY" | "SELL" | "CREATE") {
+            return;
+        }
+        let sol_amt = sol_amount.unwrap_or(0.0);
+        // Postgres keeps microseconds; truncating here keeps a lot's key the
+        // same whether it comes from this batch or is read back later
+        let at = at.trunc_subsecs(6);
+
+        let delta = self.wallets.entry(wallet.to_string()).or_insert(WalletDelta {
+            first_seen: at,
+            last_seen: at,
+            trades: 0,
+            buys: 0,
+            sells: 0,
+            creates: 0,
+            sol_in: 0.0,
+            sol_out: 0.0,
+        });
+        delta.first_seen = delta.first_seen.min(at);
+        delta.last_seen = delta.last_seen.max(at);
+        delta.trades += 1;
+
+        let day = self.days.entry((wallet.to_string(), at.date_naive())).or_default();
+        day.trades += 1;
+
+        let is_buy = match action {
+            "BUY" => {
+                delta.buys += 1;
+                delta.sol_in += sol_amt;
+                day.buys += 1;
+                day.sol_in += sol_amt;
+                true
+            }
+            "SELL" => {
+                delta.sells += 1;
+                delta.sol_out += sol_amt;
+                day.sells += 1;
+                day.sol_out += sol_amt;
+                false
             }
+            _ => {
+                delta.creates += 1;
+                return;
+            }
+        };
+        if let Some(m) = mint {
+            self.fills
+                .entry((wallet.to_string(), m.to_string()))
+                .or_default()
+                .push(PendingFill { at, is_buy, tokens: token_amount, sol: sol_amt });
         }
-        "CREATE" => {
-            sqlx::query(
-                r#"
-                INSERT INTO wallet_stats (wallet, first_seen, last_seen, total_trades, create_count)
-                VALUES ($1, NOW(), NOW(), 1, 1)
-                ON CONFLICT (wallet) DO UPDATE SET
-                    last_seen = NOW(),
-                    total_trades = wallet_stats.total_trades + 1,
-                    create_count = wallet_stats.create_count + 1
-                "#
-            )
-            .bind(wallet)
-            .execute(pool)
-            .await?;
+    }
+
+    /// Note where a recorded trade landed, for the same-slot bot signals
+    /// (sniping, sandwiching) its wallet is classified by and the bundles
+    /// sybil clusters are found in
+    pub fn record_slot(&mut self, wallet: &str, action: &str, mint: &str, slot: u64, tx_index: u64) {
+        self.window.record(wallet, action, mint, slot, tx_index);
+        if action == "BUY" {
+            self.bundle_window.record_buy(wallet, mint, slot);
         }
-        _ => {}
     }
-    
-    // Recalculate profit score
-    recalculate_profit_score(pool, wallet).await?;
-    
-    Ok(is_new_wallet)
+
+    /// Note SOL `funder` sent to `wallet` in a transaction where it bought
+    pub fn record_funding(&mut self, wallet: &str, funder: &str, lamports: u64, at: DateTime<Utc>) {
+        self.funding.push(FundingEdge {
+            wallet: wallet.to_string(),
+            funder: funder.to_string(),
+            lamports,
+            seen_at: at.trunc_subsecs(6),
+        });
+    }
+
+    /// Evaluate every slot still held back, e.g. before the final flush
+    pub fn settle_slots(&mut self) {
+        self.window.drain(true, &mut self.signals);
+        self.bundle_window.drain(true, &mut self.bundles);
+    }
+
+    /// Write all pending deltas in one transaction.
+    /// Pending state is only cleared on success, so a failed flush is retried.
+    pub async fn flush(&mut self, pool: &Pool) -> Result<FlushStats> {
+        if !self.has_pending() {
+            return Ok(FlushStats::default());
+        }
+        self.window.drain(false, &mut self.signals);
+        self.bundle_window.drain(false, &mut self.bundles);
+
+        // Stable, so same-instant fills keep the order they were recorded in
+        for fills in self.fills.values_mut() {
+            fills.sort_by_key(|f| f.at);
+        }
+
+        let mut tx = pool.begin().await?;
+        let stats = self.write(&mut tx).await?;
+        tx.commit().await?;
+
+        self.wallets.clear();
+        self.fills.clear();
+        self.days.clear();
+        self.signals.clear();
+        self.bundles.clear();
+        self.funding.clear();
+        Ok(stats)
+    }
+
+    async fn write(&self, conn: &mut Connection) -> Result<FlushStats> {
+        let mut stats = FlushStats {
+            wallets: self.wallets.len(),
+            ..FlushStats::default()
+        };
+
+        // 1) FIFO-match the batch against the open lots of every touched (wallet, mint)
+        let pairs: Vec<&(String, String)> = self.fills.keys().collect();
+        let earliest = self.fills.values().flatten().map(|f| f.at).min();
+        let mut lots = load_lots(conn, &pairs, earliest).await?;
+        let mut realized: HashMap<&str, Realized> = HashMap::new();
+        for (key, fills) in &self.fills {
+            let out = realized.entry(key.0.as_str()).or_default();
+            apply_fills(lots.entry(key.clone()).or_default(), fills, out);
+        }
+        for out in realized.values() {
+            stats.positions_opened += out.opened;
+            stats.positions_closed += out.closed;
+            stats.unmatched_sells += out.unmatched_sells as usize;
+        }
+
+        // 2) Wallet counters plus what the matching realized
+        stats.new_wallets = upsert_wallet_stats(conn, &self.wallets, &realized).await?;
+
+        // 3) Daily rollups
+        let mut days = self.days.clone();
+        for (wallet, out) in &realized {
+            for (date, pnl) in &out.daily_pnl {
+                days.entry((wallet.to_string(), *date)).or_default().pnl += pnl;
+            }
+        }
+        upsert_daily_stats(conn, &days).await?;
+
+        // 4) New and changed lots
+        let changed: Vec<(&(String, String), &Lot)> = lots
+            .iter()
+            .flat_map(|(key, lots)| lots.iter().filter(|l| l.dirty).map(move |l| (key, l)))
+            .collect();
+        write_lots(conn, &changed).await?;
+
+        // 5) Bot signals, then the class of every touched or signalled wallet
+        classify::add_signals(conn, &self.signals).await?;
+        let mut touched: HashSet<&str> = self.wallets.keys().map(String::as_str).collect();
+        touched.extend(self.signals.keys().map(String::as_str));
+        let touched: Vec<String> = touched.into_iter().map(str::to_string).collect();
+        classify::reclassify(conn, &self.classes, &touched).await?;
+        stats.signalled_wallets = self.signals.len();
+
+        // 6) Profit score for every touched wallet; components only move on a sell
+        let wallets: Vec<String> = self.wallets.keys().cloned().collect();
+        let sold: HashSet<&str> = self
+            .wallets
+            .iter()
+            .filter(|(_, delta)| delta.sells > 0)
+            .map(|(wallet, _)| wallet.as_str())
+            .collect();
+        scoring::rescore(conn, self.scoring.as_ref(), &wallets, &sold, Utc::now()).await?;
+
+        // 7) Funding seen, and the sybil clusters of settled bundles
+        stats.clustered_wallets = clusters::write(conn, &self.funding, &self.bundles, Utc::now()).await?;
+
+        Ok(stats)
+    }
 }
 
-/// Update position P&L when selling
-pub async fn update_position_pnl(
-    pool: &PgPool,
-    wallet: &str,
-    mint: &str,
+type LotsByPair = HashMap<(String, String), Vec<Lot>>;
+
+#[derive(sqlx::FromRow)]
+struct LotRow {
+    wallet: String,
+    mint: String,
+    bought_at: DateTime<Utc>,
+    token_amount: f64,
+    tokens_remaining: f64,
+    sol_spent: f64,
     sol_received: f64,
-) -> Result<()> {
-    // Find oldest open position for this mint (FIFO)
-    let position = sqlx::query_as::<_, (DateTime<Utc>, f64)>(
-        "SELECT bought_at, sol_spent FROM positions 
-         WHERE wallet = $1 AND mint = $2 AND NOT is_closed 
-         ORDER BY bought_at ASC LIMIT 1"
+    realized_pnl: f64,
+    sold_at: Option<DateTime<Utc>>,
+    is_closed: bool,
+}
+
+const LOT_COLUMNS: &str = r#"
+    p.wallet, p.mint, p.bought_at, p.token_amount, p.tokens_remaining, p.sol_spent,
+    COALESCE(p.sol_received, 0.0) AS sol_received, COALESCE(p.realized_pnl, 0.0) AS realized_pnl,
+    p.sold_at, p.is_closed
+"#;
+
+fn group_lots(rows: Vec<LotRow>) -> LotsByPair {
+    let mut lots = LotsByPair::new();
+    for row in rows {
+        lots.entry((row.wallet, row.mint)).or_default().push(Lot {
+            bought_at: row.bought_at,
+            opened_at: row.bought_at,
+            token_amount: row.token_amount,
+            tokens_remaining: row.tokens_remaining,
+            sol_spent: row.sol_spent,
+            sol_received: row.sol_received,
+            realized_pnl: row.realized_pnl,
+            sold_at: row.sold_at,
+            is_closed: row.is_closed,
+            dirty: false,
+        });
+    }
+    lots
+}
+
+/// Open lots of the given pairs, oldest first, plus the closed ones bought
+/// since `closed_since` whose keys new lots must not reuse
+#[cfg(feature = "postgres")]
+async fn load_lots(
+    conn: &mut Connection,
+    pairs: &[&(String, String)],
+    closed_since: Option<DateTime<Utc>>,
+) -> Result<LotsByPair> {
+    if pairs.is_empty() {
+        return Ok(LotsByPair::new());
+    }
+    let (wallets, mints): (Vec<&str>, Vec<&str>) =
+        pairs.iter().map(|(w, m)| (w.as_str(), m.as_str())).unzip();
+
+    let rows: Vec<LotRow> = sqlx::query_as(&format!(
+        r#"
+        SELECT {}
+        FROM positions p
+        JOIN UNNEST($1::text[], $2::text[]) AS k(wallet, mint)
+          ON k.wallet = p.wallet AND k.mint = p.mint
+        WHERE NOT p.is_closed OR p.bought_at >= $3
+        ORDER BY p.bought_at
+        "#,
+        LOT_COLUMNS
+    ))
+    .bind(&wallets)
+    .bind(&mints)
+    .bind(closed_since)
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(group_lots(rows))
+}
+
+/// Open lots of the given pairs, oldest first, plus the closed ones bought
+/// since `closed_since` (one query per pair; no arrays in SQLite)
+#[cfg(feature = "sqlite")]
+async fn load_lots(
+    conn: &mut Connection,
+    pairs: &[&(String, String)],
+    closed_since: Option<DateTime<Utc>>,
+) -> Result<LotsByPair> {
+    let mut rows: Vec<LotRow> = Vec::new();
+    for (wallet, mint) in pairs {
+        rows.extend(
+            sqlx::query_as::<_, LotRow>(&format!(
+                r#"
+                SELECT {}
+                FROM positions p
+                WHERE p.wallet = $1 AND p.mint = $2 AND (NOT p.is_closed OR p.bought_at >= $3)
+                ORDER BY p.bought_at
+                "#,
+                LOT_COLUMNS
+            ))
+            .bind(wallet)
+            .bind(mint)
+            .bind(closed_since)
+            .fetch_all(&mut *conn)
+            .await?,
+        );
+    }
+    Ok(group_lots(rows))
+}
+
+/// Postgres: one UNNEST upsert for all wallets; returns how many were new
+#[cfg(feature = "postgres")]
+async fn upsert_wallet_stats(
+    conn: &mut Connection,
+    deltas: &HashMap<String, WalletDelta>,
+    realized: &HashMap<&str, Realized>,
+) -> Result<usize> {
+    let mut wallets = Vec::with_capacity(deltas.len());
+    let mut first_seen = Vec::with_capacity(deltas.len());
+    let mut last_seen = Vec::with_capacity(deltas.len());
+    let mut trades = Vec::with_capacity(deltas.len());
+    let mut buys = Vec::with_capacity(deltas.len());
+    let mut sells = Vec::with_capacity(deltas.len());
+    let mut creates = Vec::with_capacity(deltas.len());
+    let mut sol_in = Vec::with_capacity(deltas.len());
+    let mut sol_out = Vec::with_capacity(deltas.len());
+    let mut pnl = Vec::with_capacity(deltas.len());
+    let mut wins = Vec::with_capacity(deltas.len());
+    let mut losses = Vec::with_capacity(deltas.len());
+    let mut unmatched = Vec::with_capacity(deltas.len());
+    let mut unmatched_sol = Vec::with_capacity(deltas.len());
+    let no_realized = Realized::default();
+    for (wallet, d) in deltas {
+        let r = realized.get(wallet.as_str()).unwrap_or(&no_realized);
+        wallets.push(wallet.clone());
+        first_seen.push(d.first_seen);
+        last_seen.push(d.last_seen);
+        trades.push(d.trades);
+        buys.push(d.buys);
+        sells.push(d.sells);
+        creates.push(d.creates);
+        sol_in.push(d.sol_in);
+        sol_out.push(d.sol_out);
+        pnl.push(r.pnl);
+        wins.push(r.wins);
+        losses.push(r.losses);
+        unmatched.push(r.unmatched_sells);
+        unmatched_sol.push(r.unmatched_sol);
+    }
+
+    let inserted: Vec<bool> = sqlx::query_scalar(
+        r#"
+        INSERT INTO wallet_stats (
+            wallet, first_seen, last_seen, total_trades, buy_count, sell_count,
+            create_count, total_sol_in, total_sol_out, net_pnl_sol,
+            realized_wins, realized_losses, win_rate, unmatched_sells, unmatched_sol_out
+        )
+        SELECT d.wallet, d.first_seen, d.last_seen, d.trades, d.buys, d.sells,
+               d.creates, d.sol_in, d.sol_out, d.pnl, d.wins, d.losses,
+               CASE WHEN d.wins + d.losses > 0 THEN d.wins::float8 / (d.wins + d.losses) ELSE 0 END,
+               d.unmatched, d.unmatched_sol
+        FROM UNNEST(
+            $1::text[], $2::timestamptz[], $3::timestamptz[], $4::int[], $5::int[],
+            $6::int[], $7::int[], $8::float8[], $9::float8[], $10::float8[],
+            $11::int[], $12::int[], $13::int[], $14::float8[]
+        ) AS d(wallet, first_seen, last_seen, trades, buys, sells, creates, sol_in, sol_out,
+               pnl, wins, losses, unmatched, unmatched_sol)
+        ON CONFLICT (wallet) DO UPDATE SET
+            last_seen = GREATEST(wallet_stats.last_seen, EXCLUDED.last_seen),
+            total_trades = wallet_stats.total_trades + EXCLUDED.total_trades,
+            buy_count = wallet_stats.buy_count + EXCLUDED.buy_count,
+            sell_count = wallet_stats.sell_count + EXCLUDED.sell_count,
+            create_count = wallet_stats.create_count + EXCLUDED.create_count,
+            total_sol_in = wallet_stats.total_sol_in + EXCLUDED.total_sol_in,
+            total_sol_out = wallet_stats.total_sol_out + EXCLUDED.total_sol_out,
+            net_pnl_sol = wallet_stats.net_pnl_sol + EXCLUDED.net_pnl_sol,
+            realized_wins = wallet_stats.realized_wins + EXCLUDED.realized_wins,
+            realized_losses = wallet_stats.realized_losses + EXCLUDED.realized_losses,
+            win_rate = CASE
+                WHEN wallet_stats.realized_wins + EXCLUDED.realized_wins
+                   + wallet_stats.realized_losses + EXCLUDED.realized_losses > 0
+                THEN (wallet_stats.realized_wins + EXCLUDED.realized_wins)::float8
+                   / (wallet_stats.realized_wins + EXCLUDED.realized_wins
+                    + wallet_stats.realized_losses + EXCLUDED.realized_losses)
+                ELSE 0
+            END,
+            unmatched_sells = wallet_stats.unmatched_sells + EXCLUDED.unmatched_sells,
+            unmatched_sol_out = wallet_stats.unmatched_sol_out + EXCLUDED.unmatched_sol_out
+        RETURNING (xmax = 0)
+        "#
     )
-    .bind(wallet)
-    .bind(mint)
-    .fetch_optional(pool)
+    .bind(&wallets)
+    .bind(&first_seen)
+    .bind(&last_seen)
+    .bind(&trades)
+    .bind(&buys)
+    .bind(&sells)
+    .bind(&creates)
+    .bind(&sol_in)
+    .bind(&sol_out)
+    .bind(&pnl)
+    .bind(&wins)
+    .bind(&losses)
+    .bind(&unmatched)
+    .bind(&unmatched_sol)
+    .fetch_all(&mut *conn)
     .await?;
-    
-    if let Some((bought_at, sol_spent)) = position {
-        let pnl = sol_received - sol_spent;
-        let is_win = pnl > 0.0;
-        
-        // Close position
-        sqlx::query(
+    Ok(inserted.into_iter().filter(|new| *new).count())
+}
+
+/// SQLite: insert new wallets, add deltas to existing ones; returns how many were new
+#[cfg(feature = "sqlite")]
+async fn upsert_wallet_stats(
+    conn: &mut Connection,
+    deltas: &HashMap<String, WalletDelta>,
+    realized: &HashMap<&str, Realized>,
+) -> Result<usize> {
+    let mut new_wallets = 0;
+    let no_realized = Realized::default();
+    for (wallet, d) in deltas {
+        let r = realized.get(wallet.as_str()).unwrap_or(&no_realized);
+
+        let inserted = sqlx::query(
             r#"
-            UPDATE positions SET
-                is_closed = TRUE,
-                sold_at = NOW(),
-                sol_received = $3,
-                realized_pnl = $4
-            WHERE wallet = $1 AND mint = $2 AND bought_at = $5
+            INSERT INTO wallet_stats (
+                wallet, first_seen, last_seen, total_trades, buy_count, sell_count,
+                create_count, total_sol_in, total_sol_out, net_pnl_sol,
+                realized_wins, realized_losses, win_rate, unmatched_sells, unmatched_sol_out
+            ) VALUES (
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12,
+                CASE WHEN $11 + $12 > 0 THEN CAST($11 AS REAL) / ($11 + $12) ELSE 0 END,
+                $13, $14
+            )
+            ON CONFLICT (wallet) DO NOTHING
             "#
         )
         .bind(wallet)
-        .bind(mint)
-        .bind(sol_received)
-        .bind(pnl)
-        .bind(bought_at)
-        .execute(pool)
-        .await?;
-        
-        // Update win/loss stats
-        if is_win {
-            sqlx::query(
-                "UPDATE wallet_stats SET realized_wins = realized_wins + 1 WHERE wallet = $1"
-            )
-            .bind(wallet)
-            .execute(pool)
-            .await?;
-        } else {
-            sqlx::query(
-                "UPDATE wallet_stats SET realized_losses = realized_losses + 1 WHERE wallet = $1"
-            )
-            .bind(wallet)
-            .execute(pool)
-            .await?;
+        .bind(d.first_seen)
+        .bind(d.last_seen)
+        .bind(d.trades)
+        .bind(d.buys)
+        .bind(d.sells)
+        .bind(d.creates)
+        .bind(d.sol_in)
+        .bind(d.sol_out)
+        .bind(r.pnl)
+        .bind(r.wins)
+        .bind(r.losses)
+        .bind(r.unmatched_sells)
+        .bind(r.unmatched_sol)
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        if inserted > 0 {
+            new_wallets += 1;
+            continue;
         }
-        
-        // Update win rate
+
         sqlx::query(
             r#"
             UPDATE wallet_stats SET
-                win_rate = CASE 
-                    WHEN (realized_wins + realized_losses) > 0 
-                    THEN realized_wins::float / (realized_wins + realized_losses)
+                last_seen = MAX(last_seen, $2),
+                total_trades = total_trades + $3,
+                buy_count = buy_count + $4,
+                sell_count = sell_count + $5,
+                create_count = create_count + $6,
+                total_sol_in = total_sol_in + $7,
+                total_sol_out = total_sol_out + $8,
+                net_pnl_sol = net_pnl_sol + $9,
+                realized_wins = realized_wins + $10,
+                realized_losses = realized_losses + $11,
+                win_rate = CASE
+                    WHEN realized_wins + $10 + realized_losses + $11 > 0
+                    THEN CAST(realized_wins + $10 AS REAL) / (realized_wins + $10 + realized_losses + $11)
                     ELSE 0
-                END
+                END,
+                unmatched_sells = unmatched_sells + $12,
+                unmatched_sol_out = unmatched_sol_out + $13
             WHERE wallet = $1
             "#
         )
         .bind(wallet)
-        .execute(pool)
+        .bind(d.last_seen)
+        .bind(d.trades)
+        .bind(d.buys)
+        .bind(d.sells)
+        .bind(d.creates)
+        .bind(d.sol_in)
+        .bind(d.sol_out)
+        .bind(r.pnl)
+        .bind(r.wins)
+        .bind(r.losses)
+        .bind(r.unmatched_sells)
+        .bind(r.unmatched_sol)
+        .execute(&mut *conn)
         .await?;
     }
-    
+    Ok(new_wallets)
+}
+
+const ADD_DAILY_DELTAS: &str = r#"
+    ON CONFLICT (wallet, date) DO UPDATE SET
+        trades = wallet_daily_stats.trades + excluded.trades,
+        buys = wallet_daily_stats.buys + excluded.buys,
+        sells = wallet_daily_stats.sells + excluded.sells,
+        sol_in = wallet_daily_stats.sol_in + excluded.sol_in,
+        sol_out = wallet_daily_stats.sol_out + excluded.sol_out,
+        daily_pnl = wallet_daily_stats.daily_pnl + excluded.daily_pnl
+"#;
+
+/// Postgres: one UNNEST upsert adding every (wallet, day) delta
+#[cfg(feature = "postgres")]
+async fn upsert_daily_stats(conn: &mut Connection, days: &HashMap<(String, NaiveDate), DailyDelta>) -> Result<()> {
+    if days.is_empty() {
+        return Ok(());
+    }
+    let mut wallet = Vec::with_capacity(days.len());
+    let mut date = Vec::with_capacity(days.len());
+    let mut trades = Vec::with_capacity(days.len());
+    let mut buys = Vec::with_capacity(days.len());
+    let mut sells = Vec::with_capacity(days.len());
+    let mut sol_in = Vec::with_capacity(days.len());
+    let mut sol_out = Vec::with_capacity(days.len());
+    let mut pnl = Vec::with_capacity(days.len());
+    for ((w, d), delta) in days {
+        wallet.push(w.as_str());
+        date.push(*d);
+        trades.push(delta.trades);
+        buys.push(delta.buys);
+        sells.push(delta.sells);
+        sol_in.push(delta.sol_in);
+        sol_out.push(delta.sol_out);
+        pnl.push(delta.pnl);
+    }
+
+    sqlx::query(&format!(
+        r#"
+        INSERT INTO wallet_daily_stats (wallet, date, trades, buys, sells, sol_in, sol_out, daily_pnl)
+        SELECT * FROM UNNEST(
+            $1::text[], $2::date[], $3::int[], $4::int[], $5::int[], $6::float8[], $7::float8[], $8::float8[]
+        )
+        {}
+        "#,
+        ADD_DAILY_DELTAS
+    ))
+    .bind(&wallet)
+    .bind(&date)
+    .bind(&trades)
+    .bind(&buys)
+    .bind(&sells)
+    .bind(&sol_in)
+    .bind(&sol_out)
+    .bind(&pnl)
+    .execute(&mut *conn)
+    .await?;
     Ok(())
 }
 
-/// Calculate profitability score for ranking
-pub async fn recalculate_profit_score(pool: &PgPool, wallet: &str) -> Result<()> {
-    sqlx::query(
+/// SQLite: one upsert per (wallet, day)
+#[cfg(feature = "sqlite")]
+async fn upsert_daily_stats(conn: &mut Connection, days: &HashMap<(String, NaiveDate), DailyDelta>) -> Result<()> {
+    for ((wallet, date), delta) in days {
+        sqlx::query(&format!(
+            r#"
+            INSERT INTO wallet_daily_stats (wallet, date, trades, buys, sells, sol_in, sol_out, daily_pnl)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            {}
+            "#,
+            ADD_DAILY_DELTAS
+        ))
+        .bind(wallet)
+        .bind(date)
+        .bind(delta.trades)
+        .bind(delta.buys)
+        .bind(delta.sells)
+        .bind(delta.sol_in)
+        .bind(delta.sol_out)
+        .bind(delta.pnl)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+const UPSERT_LOT: &str = r#"
+    ON CONFLICT (wallet, mint, bought_at) DO UPDATE SET
+        token_amount = excluded.token_amount,
+        tokens_remaining = excluded.tokens_remaining,
+        sol_spent = excluded.sol_spent,
+        avg_buy_price = excluded.avg_buy_price,
+        is_closed = excluded.is_closed,
+        sold_at = excluded.sold_at,
+        sol_received = excluded.sol_received,
+        realized_pnl = excluded.realized_pnl
+"#;
+
+/// Sell-side columns stay NULL until a lot has sold something
+fn sell_columns(lot: &Lot) -> (Option<f64>, Option<f64>) {
+    match lot.sold_at {
+        Some(_) => (Some(lot.sol_received), Some(lot.realized_pnl)),
+        None => (None, None),
+    }
+}
+
+/// Postgres: one UNNEST upsert for every new or changed lot
+#[cfg(feature = "postgres")]
+async fn write_lots(conn: &mut Connection, lots: &[(&(String, String), &Lot)]) -> Result<()> {
+    if lots.is_empty() {
+        return Ok(());
+    }
+    let mut wallet = Vec::with_capacity(lots.len());
+    let mut mint = Vec::with_capacity(lots.len());
+    let mut bought_at = Vec::with_capacity(lots.len());
+    let mut token_amount = Vec::with_capacity(lots.len());
+    let mut tokens_remaining = Vec::with_capacity(lots.len());
+    let mut sol_spent = Vec::with_capacity(lots.len());
+    let mut avg_buy_price = Vec::with_capacity(lots.len());
+    let mut is_closed = Vec::with_capacity(lots.len());
+    let mut sold_at = Vec::with_capacity(lots.len());
+    let mut sol_received = Vec::with_capacity(lots.len());
+    let mut realized_pnl = Vec::with_capacity(lots.len());
+    for ((w, m), lot) in lots {
+        let (received, pnl) = sell_columns(lot);
+        wallet.push(w.as_str());
+        mint.push(m.as_str());
+        bought_at.push(lot.bought_at);
+        token_amount.push(lot.token_amount);
+        tokens_remaining.push(lot.tokens_remaining);
+        sol_spent.push(lot.sol_spent);
+        avg_buy_price.push(lot.avg_buy_price());
+        is_closed.push(lot.is_closed);
+        sold_at.push(lot.sold_at);
+        sol_received.push(received);
+        realized_pnl.push(pnl);
+    }
+
+    sqlx::query(&format!(
         r#"
-        UPDATE wallet_stats SET
-            profit_score = CASE
-                WHEN total_trades >= 10 THEN
-                    (net_pnl_sol * win_rate * total_trades) / 100.0
-                ELSE 0
-            END
-        WHERE wallet = $1
-        "#
-    )
-    .bind(wallet)
-    .execute(pool)
+        INSERT INTO positions (
+            wallet, mint, bought_at, token_amount, tokens_remaining, sol_spent,
+            avg_buy_price, is_closed, sold_at, sol_received, realized_pnl
+        )
+        SELECT * FROM UNNEST(
+            $1::text[], $2::text[], $3::timestamptz[], $4::float8[], $5::float8[], $6::float8[],
+            $7::float8[], $8::bool[], $9::timestamptz[], $10::float8[], $11::float8[]
+        )
+        {}
+        "#,
+        UPSERT_LOT
+    ))
+    .bind(&wallet)
+    .bind(&mint)
+    .bind(&bought_at)
+    .bind(&token_amount)
+    .bind(&tokens_remaining)
+    .bind(&sol_spent)
+    .bind(&avg_buy_price)
+    .bind(&is_closed)
+    .bind(&sold_at)
+    .bind(&sol_received)
+    .bind(&realized_pnl)
+    .execute(&mut *conn)
     .await?;
-    
     Ok(())
 }
 
-/// Get top profitable wallets
-pub async fn get_top_wallets(pool: &PgPool, limit: i32) -> Result<Vec<WalletStats>> {
-    let wallets = sqlx::query_as::<_, WalletStats>(
+/// SQLite: one upsert per new or changed lot
+#[cfg(feature = "sqlite")]
+async fn write_lots(conn: &mut Connection, lots: &[(&(String, String), &Lot)]) -> Result<()> {
+    for ((wallet, mint), lot) in lots {
+        let (received, pnl) = sell_columns(lot);
+        sqlx::query(&format!(
+            r#"
+            INSERT INTO positions (
+                wallet, mint, bought_at, token_amount, tokens_remaining, sol_spent,
+                avg_buy_price, is_closed, sold_at, sol_received, realized_pnl
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            {}
+            "#,
+            UPSERT_LOT
+        ))
+        .bind(wallet)
+        .bind(mint)
+        .bind(lot.bought_at)
+        .bind(lot.token_amount)
+        .bind(lot.tokens_remaining)
+        .bind(lot.sol_spent)
+        .bind(lot.avg_buy_price())
+        .bind(lot.is_closed)
+        .bind(lot.sold_at)
+        .bind(received)
+        .bind(pnl)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Result of a daily-stats rebuild
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RebuildStats {
+    /// (wallet, day) rows rebuilt from positions
+    pub days: usize,
+    /// Rows whose counts were replaced with exact ones from raw_events
+    pub tracked_days: usize,
+}
+
+/// UTC calendar day of a timestamp column
+#[cfg(feature = "postgres")]
+fn utc_date(column: &str) -> String {
+    format!("({} AT TIME ZONE 'UTC')::date", column)
+}
+
+#[cfg(feature = "sqlite")]
+fn utc_date(column: &str) -> String {
+    format!("date({})", column)
+}
+
+/// Recompute `wallet_daily_stats` from scratch.
+///
+/// `positions` only keep one row per lot, so the rebuild counts a lot as one
+/// BUY on the day it was opened and one SELL (with all its proceeds and
+/// realized P&L) on the day of its latest sell; unmatched sells are lost.
+/// With `main` (the copytrader database), tracked wallets' counts and SOL
+/// flows are then replaced with exact ones from their raw_events.
+pub async fn rebuild_daily_stats(pool: &Pool, main: Option<&Pool>) -> Result<RebuildStats> {
+    let mut stats = RebuildStats::default();
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM wallet_daily_stats").execute(&mut *tx).await?;
+    stats.days = sqlx::query(&format!(
+        r#"
+        INSERT INTO wallet_daily_stats (wallet, date, trades, buys, sells, sol_in, sol_out, daily_pnl)
+        SELECT wallet, day, SUM(buys) + SUM(sells), SUM(buys), SUM(sells),
+               SUM(sol_in), SUM(sol_out), SUM(pnl)
+        FROM (
+            SELECT wallet, {} AS day, COUNT(*) AS buys, 0 AS sells,
+                   SUM(sol_spent) AS sol_in, 0.0 AS sol_out, 0.0 AS pnl
+            FROM positions
+            GROUP BY wallet, day
+            UNION ALL
+            SELECT wallet, {} AS day, 0, COUNT(*),
+                   0.0, SUM(COALESCE(sol_received, 0.0)), SUM(COALESCE(realized_pnl, 0.0))
+            FROM positions
+            WHERE sold_at IS NOT NULL
+            GROUP BY wallet, day
+        ) d
+        GROUP BY wallet, day
+        "#,
+        utc_date("bought_at"),
+        utc_date("sold_at")
+    ))
+    .execute(&mut *tx)
+    .await?
+    .rows_affected() as usize;
+
+    if let Some(main) = main {
+        let rows: Vec<(String, i64, i64, i64, i64, f64, f64)> = sqlx::query_as(
+            r#"
+            SELECT wallet, ts_ns / 86400000000000 AS day, COUNT(*) AS trades,
+                   SUM(CASE WHEN action = 'BUY' THEN 1 ELSE 0 END) AS buys,
+                   SUM(CASE WHEN action = 'SELL' THEN 1 ELSE 0 END) AS sells,
+                   SUM(CASE WHEN action = 'BUY' THEN COALESCE(amount_out, 0.0) ELSE 0.0 END) AS sol_in,
+                   SUM(CASE WHEN action = 'SELL' THEN COALESCE(amount_out, 0.0) ELSE 0.0 END) AS sol_out
+            FROM raw_events
+            WHERE action IN ('BUY', 'SELL', 'CREATE') AND (role IS NULL OR role = 'signer')
+              AND wallet IN (SELECT wallet FROM wallets)
+            GROUP BY wallet, ts_ns / 86400000000000
+            "#
+        )
+        .fetch_all(main)
+        .await?;
+
+        for (wallet, day, trades, buys, sells, sol_in, sol_out) in rows {
+            let Some(date) = DateTime::from_timestamp(day * 86_400, 0).map(|d| d.date_naive()) else {
+                continue;
+            };
+            sqlx::query(
+                r#"
+                INSERT INTO wallet_daily_stats (wallet, date, trades, buys, sells, sol_in, sol_out)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (wallet, date) DO UPDATE SET
+                    trades = excluded.trades,
+                    buys = excluded.buys,
+                    sells = excluded.sells,
+                    sol_in = excluded.sol_in,
+                    sol_out = excluded.sol_out
+                "#
+            )
+            .bind(&wallet)
+            .bind(date)
+            .bind(trades as i32)
+            .bind(buys as i32)
+            .bind(sells as i32)
+            .bind(sol_in)
+            .bind(sol_out)
+            .execute(&mut *tx)
+            .await?;
+            stats.tracked_days += 1;
+        }
+    }
+
+    tx.commit().await?;
+    Ok(stats)
+}
+
+/// Get top profitable wallets, only of `classes` if any are given
+/// (e.g. `WalletClass::COPYABLE`). Of a sybil cluster only the best-scoring
+/// wallet is listed.
+pub async fn get_top_wallets(pool: &Pool, limit: i32, classes: Option<&[WalletClass]>) -> Result<Vec<WalletStats>> {
+    let wallets = sqlx::query_as::<_, WalletStats>(&format!(
         r#"
         SELECT wallet, first_seen, last_seen, total_trades, buy_count, sell_count, 
                create_count, total_sol_in, total_sol_out, net_pnl_sol,
-               realized_wins, realized_losses, win_rate, is_tracked, profit_score
+               realized_wins, realized_losses, win_rate, is_tracked, profit_score, wallet_class,
+               closed_trades, sharpe, max_drawdown, median_roi, consistency
         FROM wallet_stats
-        WHERE total_trades >= 10
+        WHERE total_trades >= 10 {} {}
         ORDER BY profit_score DESC
         LIMIT $1
-        "#
-    )
+        "#,
+        classify::class_filter(classes.unwrap_or_default()),
+        clusters::ONE_PER_CLUSTER
+    ))
     .bind(limit)
     .fetch_all(pool)
     .await?;
     
     Ok(wallets)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone};
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap() + Duration::seconds(secs)
+    }
+
+    fn buy(secs: i64, tokens: Option<f64>, sol: f64) -> PendingFill {
+        PendingFill { at: at(secs), is_buy: true, tokens, sol }
+    }
+
+    fn sell(secs: i64, tokens: Option<f64>, sol: f64) -> PendingFill {
+        PendingFill { at: at(secs), is_buy: false, tokens, sol }
+    }
+
+    #[test]
+    fn scaling_out_realizes_pro_rata_and_counts_one_win() {
+        let mut lots = Vec::new();
+        let mut out = Realized::default();
+        apply_fills(
+            &mut lots,
+            &[
+                buy(0, Some(1000.0), 1.0),
+                sell(1, Some(500.0), 2.0),
+                sell(2, Some(500.0), 0.1),
+            ],
+            &mut out,
+        );
+
+        // The first sell doesn't close the lot; the lot wins overall (+1.1)
+        let lot = &lots[0];
+        assert!(lot.is_closed);
+        assert!((lot.realized_pnl - 1.1).abs() < 1e-12);
+        assert!((out.pnl - 1.1).abs() < 1e-12);
+        assert_eq!((out.opened, out.closed, out.wins, out.losses), (1, 1, 1, 0));
+        assert_eq!(out.unmatched_sells, 0);
+        // Both sells fall on the same day
+        assert_eq!(out.daily_pnl.len(), 1);
+        assert!((out.daily_pnl[&at(0).date_naive()] - 1.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn partial_sell_splits_across_lots_oldest_first() {
+        let mut lots = vec![Lot { dirty: false, ..Lot::open(at(0), 100.0, 1.0) }];
+        let mut out = Realized::default();
+        apply_fills(&mut lots, &[buy(1, Some(100.0), 2.0), sell(2, Some(150.0), 3.0)], &mut out);
+
+        // 100 tokens close the 1 SOL lot for 2 SOL, 50 take half of the 2 SOL lot for 1 SOL
+        assert!(lots[0].is_closed && lots[0].dirty);
+        assert!((lots[0].realized_pnl - 1.0).abs() < 1e-12);
+        assert!(!lots[1].is_closed);
+        assert!((lots[1].tokens_remaining - 50.0).abs() < 1e-12);
+        assert!(lots[1].realized_pnl.abs() < 1e-12);
+        assert!((lots[1].open_cost() - 1.0).abs() < 1e-12);
+        assert!((out.pnl - 1.0).abs() < 1e-12);
+        assert_eq!((out.closed, out.wins), (1, 1));
+    }
+
+    #[test]
+    fn sells_without_an_earlier_buy_stay_out_of_pnl() {
+        let mut lots = Vec::new();
+        let mut out = Realized::default();
+        apply_fills(
+            &mut lots,
+            &[
+                sell(0, Some(100.0), 1.0),
+                buy(1, Some(100.0), 1.0),
+                sell(2, Some(300.0), 6.0),
+            ],
+            &mut out,
+        );
+
+        // Second sell: 100 tokens match the lot (2 SOL), 200 were held before tracking (4 SOL)
+        assert!(lots[0].is_closed);
+        assert!((out.pnl - 1.0).abs() < 1e-12);
+        assert_eq!(out.unmatched_sells, 2);
+        assert!((out.unmatched_sol - 5.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn rebuying_in_the_same_instant_opens_a_lot_with_its_own_key() {
+        // A lot sold off in an earlier flush, at the instant of this batch
+        let sold = Lot { is_closed: true, tokens_remaining: 0.0, dirty: false, ..Lot::open(at(0), 50.0, 1.0) };
+        let mut lots = vec![sold.clone()];
+        let mut out = Realized::default();
+        apply_fills(
+            &mut lots,
+            &[
+                buy(0, Some(100.0), 1.0),
+                sell(0, Some(100.0), 2.0),
+                buy(0, Some(100.0), 3.0),
+                sell(0, Some(100.0), 3.0),
+            ],
+            &mut out,
+        );
+
+        // The second round trip is costed at its own 3 SOL: +1 then 0
+        assert_eq!(lots.len(), 3);
+        assert_eq!(lots[0], sold);
+        let keys: Vec<_> = lots.iter().map(|l| l.bought_at).collect();
+        assert_eq!(keys, vec![at(0), at(0) + Duration::microseconds(1), at(0) + Duration::microseconds(2)]);
+        assert!((lots[1].realized_pnl - 1.0).abs() < 1e-12);
+        assert!(lots[2].is_closed && lots[2].realized_pnl.abs() < 1e-12);
+        assert!((out.pnl - 1.0).abs() < 1e-12);
+        assert_eq!((out.opened, out.closed, out.wins, out.losses), (2, 2, 1, 1));
+    }
+
+    #[test]
+    fn unknown_sizes_close_the_oldest_lot_whole() {
+        let mut lots = vec![Lot { dirty: false, ..Lot::open(at(0), 0.0, 1.0) }];
+        let mut out = Realized::default();
+        apply_fills(&mut lots, &[buy(1, Some(100.0), 1.0), sell(2, Some(50.0), 0.5)], &mut out);
+
+        assert!(lots[0].is_closed);
+        assert!((lots[0].realized_pnl + 0.5).abs() < 1e-12);
+        assert_eq!(lots[1].tokens_remaining, 100.0);
+        assert_eq!((out.closed, out.losses, out.unmatched_sells), (1, 1, 0));
+    }
+}
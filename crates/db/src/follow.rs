@@ -0,0 +1,330 @@
+//! Leader→follower detection - which tracked wallets keep buying right after another
+//!
+//! For every mint, each tracked wallet's first BUY in the window is an entry.
+//! An entry in a later slot within `max_lag_ms` of another wallet's entry
+//! follows it; pairs that do this on at least `min_follows` mints become
+//! `follow_edges`, and the follower's entry gets `raw_events.leader_wallet`.
+//! Same-slot entries are never follows (they can't be a reaction; see bundles).
+//! Wallets with an excluded role (e.g. liquidity) take no part at all.
+
+use crate::patterns::percentile;
+use crate::Pool;
+use anyhow::Result;
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+
+const NS_PER_DAY: i64 = 86_400 * 1_000_000_000;
+
+/// A tracked wallet's BUY
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Entry {
+    pub event_id: i64,
+    pub wallet: String,
+    pub mint: String,
+    pub slot: i64,
+    pub ts_ns: i64,
+    pub sol: f64,
+}
+
+/// One `follow_edges` row
+#[derive(Debug, Clone, PartialEq)]
+pub struct FollowEdge {
+    pub leader: String,
+    pub follower: String,
+    /// Mints the follower entered within the lag after the leader
+    pub follows: usize,
+    pub lag_ms_p50: i64,
+    pub lag_ms_p90: i64,
+    /// follows / mints the leader entered
+    pub follow_prob: f64,
+    pub avg_follower_size_sol: f64,
+}
+
+/// Detected edges, plus the leader credited for each following entry
+#[derive(Debug, Default)]
+pub struct FollowGraph {
+    pub edges: Vec<FollowEdge>,
+    /// (follower's raw_events.id, leader)
+    pub leaders: Vec<(i64, String)>,
+}
+
+/// One follow of a (leader, follower) pair
+struct Follow {
+    event_id: i64,
+    lag_ms: i64,
+    sol: f64,
+}
+
+/// Find leader→follower edges among BUYs (any order; only each wallet's first
+/// BUY per mint counts)
+pub fn detect(buys: &[Entry], max_lag_ms: i64, min_follows: usize) -> FollowGraph {
+    let mut by_mint: HashMap<&str, Vec<&Entry>> = HashMap::new();
+    for buy in buys {
+        by_mint.entry(buy.mint.as_str()).or_default().push(buy);
+    }
+
+    let mut mints_entered: HashMap<&str, usize> = HashMap::new();
+    let mut follows: HashMap<(&str, &str), Vec<Follow>> = HashMap::new();
+    for entries in by_mint.values_mut() {
+        entries.sort_by_key(|e| (e.slot, e.ts_ns, e.event_id));
+        let mut seen = HashSet::new();
+        entries.retain(|e| seen.insert(e.wallet.as_str()));
+
+        for (i, leader) in entries.iter().enumerate() {
+            *mints_entered.entry(leader.wallet.as_str()).or_default() += 1;
+            for follower in &entries[i + 1..] {
+                if follower.slot == leader.slot {
+                    continue;
+                }
+                let lag_ms = (follower.ts_ns - leader.ts_ns).max(0) / 1_000_000;
+                if lag_ms > max_lag_ms {
+                    continue;
+                }
+                follows
+                    .entry((leader.wallet.as_str(), follower.wallet.as_str()))
+                    .or_default()
+                    .push(Follow { event_id: follower.event_id, lag_ms, sol: follower.sol });
+            }
+        }
+    }
+
+    let mut graph = FollowGraph::default();
+    // follower's event -> (follow_prob, lag, leader) of the best edge it belongs to
+    let mut best: HashMap<i64, (f64, i64, &str)> = HashMap::new();
+    for ((leader, follower), pair) in &follows {
+        if pair.len() < min_follows.max(1) {
+            continue;
+        }
+        let mut lags: Vec<i64> = pair.iter().map(|f| f.lag_ms).collect();
+        lags.sort_unstable();
+        let follow_prob = pair.len() as f64 / mints_entered[leader] as f64;
+
+        for f in pair {
+            let better = match best.get(&f.event_id) {
+                Some((prob, lag, _)) => follow_prob > *prob || (follow_prob == *prob && f.lag_ms < *lag),
+                None => true,
+            };
+            if better {
+                best.insert(f.event_id, (follow_prob, f.lag_ms, leader));
+            }
+        }
+
+        graph.edges.push(FollowEdge {
+            leader: leader.to_string(),
+            follower: follower.to_string(),
+            follows: pair.len(),
+            lag_ms_p50: percentile(&lags, 0.5).unwrap_or(0),
+            lag_ms_p90: percentile(&lags, 0.9).unwrap_or(0),
+            follow_prob,
+            avg_follower_size_sol: pair.iter().map(|f| f.sol).sum::<f64>() / pair.len() as f64,
+        });
+    }
+
+    graph.edges.sort_by(|a, b| (&a.leader, &a.follower).cmp(&(&b.leader, &b.follower)));
+    graph.leaders = best.into_iter().map(|(id, (_, _, leader))| (id, leader.to_string())).collect();
+    graph.leaders.sort();
+    graph
+}
+
+/// Result of a follow-edge run
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FollowStats {
+    pub entries: usize,
+    pub edges: usize,
+    pub leaders_backfilled: usize,
+}
+
+/// Recompute `follow_edges` from tracked wallets' BUYs in the last
+/// `window_days`, back-fill `leader_wallet` on their entries and refresh the
+/// leader/followership scores in `wallet_patterns`. Wallets with any of
+/// `excluded_roles` are left out.
+pub async fn detect_follow_edges(
+    pool: &Pool,
+    window_days: u32,
+    max_lag_ms: i64,
+    min_follows: usize,
+    excluded_roles: &[String],
+) -> Result<FollowStats> {
+    let since_ns = Utc::now().timestamp_nanos_opt().unwrap_or(i64::MAX) - window_days as i64 * NS_PER_DAY;
+    // Rows recorded before roles existed (role IS NULL) are trusted as the wallet's own
+    let buys: Vec<Entry> = sqlx::query_as(
+        r#"
+        SELECT e.id AS event_id, e.wallet, e.mint, e.slot, e.ts_ns, COALESCE(e.amount_out, 0.0) AS sol
+        FROM raw_events e
+        JOIN wallets w ON w.wallet = e.wallet AND w.is_tracked
+        WHERE e.action = 'BUY' AND e.mint IS NOT NULL AND e.slot IS NOT NULL AND e.ts_ns >= $1
+          AND (e.role IS NULL OR e.role = 'signer')
+        "#
+    )
+    .bind(since_ns)
+    .fetch_all(pool)
+    .await?;
+    let excluded: HashSet<String> = sqlx::query_as::<_, (String, String)>("SELECT wallet, role FROM wallet_roles")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .filter(|(_, role)| excluded_roles.contains(role))
+        .map(|(wallet, _)| wallet)
+        .collect();
+    let buys: Vec<Entry> = buys.into_iter().filter(|b| !excluded.contains(&b.wallet)).collect();
+
+    let graph = detect(&buys, max_lag_ms, min_follows);
+    write_graph(pool, &graph, since_ns).await?;
+
+    Ok(FollowStats {
+        entries: buys.len(),
+        edges: graph.edges.len(),
+        leaders_backfilled: graph.leaders.len(),
+    })
+}
+
+async fn write_graph(pool: &Pool, graph: &FollowGraph, since_ns: i64) -> Result<()> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM follow_edges").execute(&mut *tx).await?;
+    for edge in &graph.edges {
+        sqlx::query(
+            r#"
+            INSERT INTO follow_edges (leader, follower, lag_ms_p50, lag_ms_p90, follow_prob, avg_follower_size_sol)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#
+        )
+        .bind(&edge.leader)
+        .bind(&edge.follower)
+        .bind(edge.lag_ms_p50)
+        .bind(edge.lag_ms_p90)
+        .bind(edge.follow_prob)
+        .bind(edge.avg_follower_size_sol)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // Entries in the window are re-credited from scratch
+    sqlx::query("UPDATE raw_events SET leader_wallet = NULL WHERE action = 'BUY' AND ts_ns >= $1 AND leader_wallet IS NOT NULL")
+        .bind(since_ns)
+        .execute(&mut *tx)
+        .await?;
+    for (event_id, leader) in &graph.leaders {
+        sqlx::query("UPDATE raw_events SET leader_wallet = $2 WHERE id = $1")
+            .bind(event_id)
+            .bind(leader)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    // leader_score: how reliably others follow the wallet (sum of its edges'
+    // follow_prob); followership_score: its strongest edge as a follower
+    let mut scores: HashMap<&str, (f64, f64)> = HashMap::new();
+    for edge in &graph.edges {
+        scores.entry(edge.leader.as_str()).or_default().0 += edge.follow_prob;
+        let follower = scores.entry(edge.follower.as_str()).or_default();
+        follower.1 = follower.1.max(edge.follow_prob);
+    }
+    sqlx::query("UPDATE wallet_patterns SET leader_score = 0, followership_score = 0")
+        .execute(&mut *tx)
+        .await?;
+    for (wallet, (leader_score, followership_score)) in scores {
+        sqlx::query(
+            r#"
+            INSERT INTO wallet_patterns (wallet, leader_score, followership_score, updated_at)
+            VALUES ($1, $2, $3, CURRENT_TIMESTAMP)
+            ON CONFLICT (wallet) DO UPDATE SET
+                leader_score = EXCLUDED.leader_score,
+                followership_score = EXCLUDED.followership_score
+            "#
+        )
+        .bind(wallet)
+        .bind(leader_score)
+        .bind(followership_score)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: i64 = 1_000_000;
+
+    fn buy(event_id: i64, wallet: &str, mint: &str, slot: i64, ts_ms: i64, sol: f64) -> Entry {
+        Entry {
+            event_id,
+            wallet: wallet.to_string(),
+            mint: mint.to_string(),
+            slot,
+            ts_ns: ts_ms * MS,
+            sol,
+        }
+    }
+
+    #[test]
+    fn repeated_quick_follows_make_an_edge() {
+        let mut buys = Vec::new();
+        // L enters four mints; F follows on three of them, 400-1200ms later
+        for (i, lag) in [400, 800, 1200].into_iter().enumerate() {
+            let mint = format!("M{}", i);
+            let t = i as i64 * 100_000;
+            buys.push(buy(i as i64 * 10, "L", &mint, 100 + i as i64 * 10, t, 1.0));
+            buys.push(buy(i as i64 * 10 + 1, "F", &mint, 102 + i as i64 * 10, t + lag, 0.5 + i as f64 * 0.5));
+        }
+        buys.push(buy(99, "L", "M9", 500, 900_000, 1.0));
+
+        let graph = detect(&buys, 5_000, 3);
+        assert_eq!(graph.edges.len(), 1);
+        let edge = &graph.edges[0];
+        assert_eq!((edge.leader.as_str(), edge.follower.as_str(), edge.follows), ("L", "F", 3));
+        assert_eq!((edge.lag_ms_p50, edge.lag_ms_p90), (800, 1200));
+        assert_eq!(edge.follow_prob, 0.75);
+        assert_eq!(edge.avg_follower_size_sol, 1.0);
+        assert_eq!(
+            graph.leaders,
+            vec![(1, "L".to_string()), (11, "L".to_string()), (21, "L".to_string())]
+        );
+    }
+
+    #[test]
+    fn slow_same_slot_and_occasional_buys_are_not_follows() {
+        let buys = vec![
+            // Too slow
+            buy(1, "L", "M1", 100, 0, 1.0),
+            buy(2, "F", "M1", 200, 60_000, 1.0),
+            // Same slot: a bundle, not a reaction
+            buy(3, "L", "M2", 300, 0, 1.0),
+            buy(4, "F", "M2", 300, 10, 1.0),
+            // F entered first, so L follows F; F's re-buy after L doesn't count
+            buy(5, "F", "M3", 399, 0, 1.0),
+            buy(6, "L", "M3", 400, 100, 1.0),
+            buy(7, "F", "M3", 401, 500, 1.0),
+        ];
+        let graph = detect(&buys, 5_000, 1);
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!((graph.edges[0].leader.as_str(), graph.edges[0].follower.as_str()), ("F", "L"));
+        assert_eq!(graph.leaders, vec![(6, "F".to_string())]);
+    }
+
+    #[test]
+    fn entry_is_credited_to_the_most_reliable_leader() {
+        let mut buys = Vec::new();
+        // A leads F on 2 of its 2 mints, B on 2 of its 4; both precede F on M0/M1
+        for i in 0..2 {
+            let mint = format!("M{}", i);
+            buys.push(buy(i * 10, "B", &mint, i * 10, 0, 1.0));
+            buys.push(buy(i * 10 + 1, "A", &mint, i * 10 + 1, 100, 1.0));
+            buys.push(buy(i * 10 + 2, "F", &mint, i * 10 + 2, 200, 1.0));
+        }
+        buys.push(buy(50, "B", "M5", 50, 0, 1.0));
+        buys.push(buy(60, "B", "M6", 60, 0, 1.0));
+
+        let graph = detect(&buys, 5_000, 2);
+        let prob = |leader: &str, follower: &str| {
+            graph.edges.iter().find(|e| e.leader == leader && e.follower == follower).map(|e| e.follow_prob)
+        };
+        assert_eq!(prob("A", "F"), Some(1.0));
+        assert_eq!(prob("B", "F"), Some(0.5));
+        assert_eq!(graph.leaders, vec![(1, "B".to_string()), (2, "A".to_string()), (11, "B".to_string()), (12, "A".to_string())]);
+    }
+}
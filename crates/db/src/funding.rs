@@ -0,0 +1,231 @@
+//! Funding-graph tracing - is a discovered wallet a fresh alt of one we know?
+//!
+//! Inbound SOL transfers are walked back from the wallet, breadth first, up
+//! to `max_hops`. A wallet's funders come from `wallet_funding` if it was
+//! traced within `cache_for` (`funding_traces`); otherwise they are fetched
+//! (over RPC, by the caller) and cached there. The first tracked wallet or
+//! sybil cluster member (`db::clusters`) reached is the wallet's link;
+//! members of the wallet's own cluster are skipped.
+
+use crate::clusters::FundingEdge;
+use crate::Pool;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+
+#[derive(Debug, Clone)]
+pub struct TraceOptions {
+    pub max_hops: u32,
+    /// Largest funders (by lamports) followed from each wallet
+    pub max_funders: usize,
+    /// How long a wallet's fetched funders are reused
+    pub cache_for: Duration,
+    /// Never walked through, e.g. exchange hot wallets that fund everyone
+    pub ignore: HashSet<String>,
+}
+
+impl Default for TraceOptions {
+    fn default() -> Self {
+        Self {
+            max_hops: 3,
+            max_funders: 3,
+            cache_for: Duration::days(7),
+            ignore: HashSet::new(),
+        }
+    }
+}
+
+/// One row of `wallet_links`
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct WalletLink {
+    pub wallet: String,
+    /// The tracked wallet or cluster member reached
+    pub linked_to: String,
+    /// `tracked` or `cluster`
+    pub kind: String,
+    pub cluster_id: Option<String>,
+    pub hops: i32,
+    /// Funders walked, comma-separated, ending at `linked_to`
+    pub path: String,
+    pub found_at: DateTime<Utc>,
+}
+
+impl WalletLink {
+    /// "alt of <alias>" for the report; `aliases` maps tracked wallets to their alias
+    pub fn describe(&self, aliases: &HashMap<String, String>) -> String {
+        let hops = if self.hops == 1 { "1 hop".to_string() } else { format!("{} hops", self.hops) };
+        match self.cluster_id.as_deref() {
+            Some(cluster) if self.kind == "cluster" => format!("alt of cluster {} ({})", cluster, hops),
+            _ => format!(
+                "alt of {} ({})",
+                aliases.get(&self.linked_to).unwrap_or(&self.linked_to),
+                hops
+            ),
+        }
+    }
+}
+
+/// Walk `wallet`'s funders back to a wallet in `tracked` or in a cluster other
+/// than its own. `fetch` returns a wallet's inbound transfers when the cache can't.
+pub async fn trace<F, Fut>(
+    pool: &Pool,
+    tracked: &HashSet<String>,
+    wallet: &str,
+    options: &TraceOptions,
+    now: DateTime<Utc>,
+    mut fetch: F,
+) -> Result<Option<WalletLink>>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<Vec<FundingEdge>>>,
+{
+    // Its own cluster mates funding it is what made the cluster, not a link
+    let own_cluster = cluster_of(pool, wallet).await?;
+    let mut seen: HashSet<String> = HashSet::from([wallet.to_string()]);
+    // (wallet, funders walked to reach it)
+    let mut frontier: Vec<(String, Vec<String>)> = vec![(wallet.to_string(), Vec::new())];
+
+    for _ in 0..options.max_hops {
+        let mut next = Vec::new();
+        for (node, path) in frontier {
+            for funder in funders(pool, &node, options, now, &mut fetch).await? {
+                if options.ignore.contains(&funder) || !seen.insert(funder.clone()) {
+                    continue;
+                }
+                let mut path = path.clone();
+                path.push(funder.clone());
+
+                let (kind, cluster_id) = if tracked.contains(&funder) {
+                    ("tracked", None)
+                } else {
+                    let cluster_id = cluster_of(pool, &funder).await?;
+                    if cluster_id.is_none() {
+                        next.push((funder, path));
+                        continue;
+                    }
+                    if cluster_id == own_cluster {
+                        continue;
+                    }
+                    ("cluster", cluster_id)
+                };
+                return Ok(Some(WalletLink {
+                    wallet: wallet.to_string(),
+                    linked_to: funder,
+                    kind: kind.to_string(),
+                    cluster_id,
+                    hops: path.len() as i32,
+                    path: path.join(","),
+                    found_at: now,
+                }));
+            }
+        }
+        frontier = next;
+    }
+    Ok(None)
+}
+
+async fn cluster_of(pool: &Pool, wallet: &str) -> Result<Option<String>> {
+    Ok(sqlx::query_scalar("SELECT cluster_id FROM wallet_clusters WHERE wallet = $1")
+        .bind(wallet)
+        .fetch_optional(pool)
+        .await?)
+}
+
+/// `wallet`'s largest funders, fetched and cached unless traced recently
+async fn funders<F, Fut>(pool: &Pool, wallet: &str, options: &TraceOptions, now: DateTime<Utc>, fetch: &mut F) -> Result<Vec<String>>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<Vec<FundingEdge>>>,
+{
+    let traced_at: Option<DateTime<Utc>> = sqlx::query_scalar("SELECT traced_at FROM funding_traces WHERE wallet = $1")
+        .bind(wallet)
+        .fetch_optional(pool)
+        .await?;
+    let cached = matches!(traced_at, Some(at) if at >= now - options.cache_for);
+    if !cached {
+        let edges = fetch(wallet.to_string()).await?;
+        store(pool, wallet, &edges, now).await?;
+    }
+
+    Ok(sqlx::query_scalar("SELECT funder FROM wallet_funding WHERE wallet = $1 ORDER BY lamports DESC, funder LIMIT $2")
+        .bind(wallet)
+        .bind(options.max_funders as i64)
+        .fetch_all(pool)
+        .await?)
+}
+
+/// Cache fetched transfers (summed per funder; what the ingestor saw is kept)
+/// and mark the wallet traced
+async fn store(pool: &Pool, wallet: &str, edges: &[FundingEdge], now: DateTime<Utc>) -> Result<()> {
+    let mut by_funder: BTreeMap<&str, (u64, DateTime<Utc>)> = BTreeMap::new();
+    for edge in edges.iter().filter(|e| e.wallet == wallet) {
+        let entry = by_funder.entry(edge.funder.as_str()).or_insert((0, edge.seen_at));
+        entry.0 += edge.lamports;
+        entry.1 = entry.1.min(edge.seen_at);
+    }
+
+    let mut tx = pool.begin().await?;
+    for (funder, (lamports, first_seen)) in &by_funder {
+        sqlx::query(
+            r#"
+            INSERT INTO wallet_funding (wallet, funder, lamports, first_seen, source) VALUES ($1, $2, $3, $4, 'rpc')
+            ON CONFLICT (wallet, funder) DO NOTHING
+            "#,
+        )
+        .bind(wallet)
+        .bind(*funder)
+        .bind(*lamports as i64)
+        .bind(*first_seen)
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO funding_traces (wallet, traced_at, transfers) VALUES ($1, $2, $3)
+        ON CONFLICT (wallet) DO UPDATE SET traced_at = excluded.traced_at, transfers = excluded.transfers
+        "#,
+    )
+    .bind(wallet)
+    .bind(now)
+    .bind(edges.len() as i32)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn save_link(pool: &Pool, link: &WalletLink) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO wallet_links (wallet, linked_to, kind, cluster_id, hops, path, found_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (wallet) DO UPDATE SET
+            linked_to = excluded.linked_to,
+            kind = excluded.kind,
+            cluster_id = excluded.cluster_id,
+            hops = excluded.hops,
+            path = excluded.path,
+            found_at = excluded.found_at
+        "#,
+    )
+    .bind(&link.wallet)
+    .bind(&link.linked_to)
+    .bind(&link.kind)
+    .bind(&link.cluster_id)
+    .bind(link.hops)
+    .bind(&link.path)
+    .bind(link.found_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Every stored link, by wallet
+pub async fn links(pool: &Pool) -> Result<HashMap<String, WalletLink>> {
+    let rows: Vec<WalletLink> =
+        sqlx::query_as("SELECT wallet, linked_to, kind, cluster_id, hops, path, found_at FROM wallet_links")
+            .fetch_all(pool)
+            .await?;
+    Ok(rows.into_iter().map(|link| (link.wallet.clone(), link)).collect())
+}
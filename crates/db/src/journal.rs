@@ -0,0 +1,105 @@
+//! Append-only on-disk spill journal for raw_events
+//!
+//! When a batch can't be written to Postgres it is appended here as JSONL
+//! (one `RawEvent` per line) and re-inserted once the database is back.
+
+use crate::raw_events::{bulk_insert_raw_events, RawEvent};
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Events re-inserted per batch during replay
+const REPLAY_CHUNK: usize = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct SpillJournal {
+    path: PathBuf,
+}
+
+impl SpillJournal {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// File being replayed; new spills keep going to `path` meanwhile
+    fn replay_path(&self) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(".replaying");
+        PathBuf::from(name)
+    }
+
+    /// Append events and fsync so they survive a crash
+    pub fn append(&self, events: &[RawEvent]) -> Result<()> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).context("Failed to create journal directory")?;
+        }
+
+        let mut buf = Vec::new();
+        for event in events {
+            serde_json::to_writer(&mut buf, event)?;
+            buf.push(b'\n');
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .context("Failed to open spill journal")?;
+        file.write_all(&buf)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Whether there are spilled events waiting to be replayed
+    pub fn has_pending(&self) -> bool {
+        [self.path.clone(), self.replay_path()]
+            .iter()
+            .any(|p| fs::metadata(p).map(|m| m.len() > 0).unwrap_or(false))
+    }
+
+    /// Re-insert spilled events. Returns the number of events read back.
+    ///
+    /// Inserts are idempotent (`ON CONFLICT DO NOTHING`), so if replay fails
+    /// part-way the remaining file is simply retried from the start next time.
+    pub async fn replay(&self, pool: &crate::Pool) -> Result<usize> {
+        let replay_path = self.replay_path();
+        if !replay_path.exists() {
+            if !self.path.exists() {
+                return Ok(0);
+            }
+            fs::rename(&self.path, &replay_path).context("Failed to rotate spill journal")?;
+        }
+
+        let reader = BufReader::new(File::open(&replay_path)?);
+        let mut events = Vec::new();
+        for (line_no, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<RawEvent>(&line) {
+                Ok(event) => events.push(event),
+                // A torn final line from a crash mid-append
+                Err(e) => warn!("Skipping corrupt journal line {}: {}", line_no + 1, e),
+            }
+        }
+
+        for chunk in events.chunks(REPLAY_CHUNK) {
+            bulk_insert_raw_events(pool, chunk).await?;
+        }
+
+        fs::remove_file(&replay_path)?;
+        Ok(events.len())
+    }
+}
@@ -0,0 +1,129 @@
+//! Embedded, versioned schema migrations
+//!
+//! SQL lives in `crates/db/migrations/<backend>/<schema>/` and is compiled into
+//! the binary. Main-database versions are below 1000, discovery versions start
+//! at 1001, so both sets can share one `_sqlx_migrations` table (e.g. a single
+//! SQLite file for dev).
+
+use crate::Pool;
+use anyhow::{bail, Context, Result};
+use sqlx::migrate::{Migrate, Migrator};
+use std::collections::HashMap;
+use tracing::info;
+
+#[cfg(feature = "postgres")]
+static MAIN: Migrator = sqlx::migrate!("./migrations/postgres/main");
+#[cfg(feature = "postgres")]
+static DISCOVERY: Migrator = sqlx::migrate!("./migrations/postgres/discovery");
+#[cfg(feature = "sqlite")]
+static MAIN: Migrator = sqlx::migrate!("./migrations/sqlite/main");
+#[cfg(feature = "sqlite")]
+static DISCOVERY: Migrator = sqlx::migrate!("./migrations/sqlite/discovery");
+
+/// Which database a pool points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schema {
+    /// wallets, raw_events, trades, ...
+    Main,
+    /// wallet_stats, positions, ...
+    Discovery,
+}
+
+/// What `connect` does about the schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationMode {
+    /// Apply pending migrations, then verify
+    Apply,
+    /// Only verify; never write to the database
+    Check,
+}
+
+impl Schema {
+    fn name(&self) -> &'static str {
+        match self {
+            Schema::Main => "main",
+            Schema::Discovery => "discovery",
+        }
+    }
+
+    fn migrator(&self) -> &'static Migrator {
+        match self {
+            Schema::Main => &MAIN,
+            Schema::Discovery => &DISCOVERY,
+        }
+    }
+
+    fn owns(&self, version: i64) -> bool {
+        match self {
+            Schema::Main => version < 1000,
+            Schema::Discovery => version >= 1000,
+        }
+    }
+}
+
+/// Bring the schema up to date (or just check it) and refuse to continue unless
+/// the database is at exactly the version this binary was built with.
+pub async fn ensure_schema(pool: &Pool, schema: Schema, mode: MigrationMode) -> Result<()> {
+    let embedded = schema.migrator();
+
+    if mode == MigrationMode::Apply {
+        // The other schema's versions may share the table; they're checked on their own
+        let migrator = Migrator {
+            migrations: embedded.migrations.clone(),
+            ignore_missing: true,
+            locking: true,
+        };
+        migrator
+            .run(pool)
+            .await
+            .with_context(|| format!("Failed to migrate {} schema", schema.name()))?;
+    }
+
+    verify(pool, schema, embedded).await
+}
+
+async fn verify(pool: &Pool, schema: Schema, embedded: &Migrator) -> Result<()> {
+    let name = schema.name();
+    let mut conn = pool.acquire().await?;
+
+    if let Some(version) = conn
+        .dirty_version()
+        .await
+        .with_context(|| format!("Can't read {} schema version (never migrated?)", name))?
+    {
+        bail!("{} schema migration {} failed part-way; fix it by hand before starting", name, version);
+    }
+
+    let applied: HashMap<i64, Vec<u8>> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .filter(|m| schema.owns(m.version))
+        .map(|m| (m.version, m.checksum.into_owned()))
+        .collect();
+    let expected = embedded.iter().map(|m| m.version).max().unwrap_or(0);
+    let current = applied.keys().copied().max().unwrap_or(0);
+
+    if let Some(newer) = applied.keys().find(|v| !embedded.iter().any(|m| m.version == **v)) {
+        bail!(
+            "{} schema is at version {} but this binary only knows up to {} (found unknown migration {}); upgrade the binary",
+            name, current, expected, newer
+        );
+    }
+    for migration in embedded.iter() {
+        match applied.get(&migration.version) {
+            None => bail!(
+                "{} schema is at version {} but this binary needs {} (missing {} {}); start without check-only mode to migrate",
+                name, current, expected, migration.version, migration.description
+            ),
+            Some(checksum) if checksum.as_slice() != migration.checksum.as_ref() => bail!(
+                "{} schema migration {} {} was changed after it was applied",
+                name, migration.version, migration.description
+            ),
+            Some(_) => {}
+        }
+    }
+
+    info!("🗄️  {} schema at version {}", name, current);
+    Ok(())
+}
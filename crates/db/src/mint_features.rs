@@ -0,0 +1,354 @@
+//! Rug-risk and holder-concentration features per mint
+//!
+//! Mints are followed from their CREATE for `window`. Holder balances come
+//! from the token balance changes of the Pump.fun transactions the ingestor
+//! sees, so tokens moved elsewhere aren't reflected until the holder trades
+//! again. The bonding curve isn't a holder (see `MintActivity::balances`).
+//!
+//! `mint_features` keeps the features for good; `mint_holders` only while the
+//! mint is followed, so a restart picks up where it left off.
+
+use crate::mints::{MintActivity, PUMP_TOKEN_SUPPLY};
+use crate::Pool;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, HashSet};
+
+/// Holders counted in `top10_share`
+const TOP_HOLDERS: usize = 10;
+
+/// One row of `mint_features`
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct MintFeatures {
+    pub mint: String,
+    pub creator: String,
+    pub created_at: DateTime<Utc>,
+    pub created_slot: i64,
+    /// Raw token units bought in the creation slot
+    pub creation_slot_tokens: i64,
+    /// Share of the supply bought in the creation slot (bundled launch)
+    pub creation_slot_share: f64,
+    /// Share of the supply held by the 10 largest holders
+    pub top10_share: f64,
+    pub creator_share: f64,
+    pub unique_buyers: i64,
+    pub first_sell_at: Option<DateTime<Utc>>,
+    pub secs_to_first_sell: Option<f64>,
+}
+
+/// Latest features of `mint`, if the ingestor saw it launch
+pub async fn get(pool: &Pool, mint: &str) -> Result<Option<MintFeatures>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT mint, creator, created_at, created_slot, creation_slot_tokens, creation_slot_share,
+               top10_share, creator_share, unique_buyers, first_sell_at, secs_to_first_sell
+        FROM mint_features WHERE mint = $1
+        "#,
+    )
+    .bind(mint)
+    .fetch_optional(pool)
+    .await?)
+}
+
+#[derive(Debug, Clone, Default)]
+struct Holder {
+    balance: u64,
+    bought: bool,
+}
+
+#[derive(Debug, Clone)]
+struct MintState {
+    creator: String,
+    created_at: DateTime<Utc>,
+    created_slot: u64,
+    creation_slot_tokens: u64,
+    first_sell_at: Option<DateTime<Utc>>,
+    holders: HashMap<String, Holder>,
+    /// Holders changed since the last flush
+    dirty_holders: HashSet<String>,
+}
+
+impl MintState {
+    fn features(&self, mint: &str) -> MintFeatures {
+        let share = |tokens: u64| tokens as f64 / PUMP_TOKEN_SUPPLY as f64;
+        let mut balances: Vec<u64> = self.holders.values().map(|h| h.balance).collect();
+        balances.sort_unstable_by(|a, b| b.cmp(a));
+
+        MintFeatures {
+            mint: mint.to_string(),
+            creator: self.creator.clone(),
+            created_at: self.created_at,
+            created_slot: self.created_slot as i64,
+            creation_slot_tokens: self.creation_slot_tokens as i64,
+            creation_slot_share: share(self.creation_slot_tokens),
+            top10_share: share(balances.iter().take(TOP_HOLDERS).sum()),
+            creator_share: share(self.holders.get(&self.creator).map(|h| h.balance).unwrap_or(0)),
+            unique_buyers: self.holders.values().filter(|h| h.bought).count() as i64,
+            first_sell_at: self.first_sell_at,
+            secs_to_first_sell: self
+                .first_sell_at
+                .map(|at| (at - self.created_at).num_milliseconds() as f64 / 1000.0),
+        }
+    }
+}
+
+/// In-memory features of recently launched mints
+pub struct MintFeatureAggregator {
+    mints: HashMap<String, MintState>,
+    /// Changed since the last flush
+    dirty: HashSet<String>,
+    window: Duration,
+}
+
+impl MintFeatureAggregator {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            mints: HashMap::new(),
+            dirty: HashSet::new(),
+            window,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.mints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mints.is_empty()
+    }
+
+    /// Current features of a followed mint
+    pub fn features(&self, mint: &str) -> Option<MintFeatures> {
+        self.mints.get(mint).map(|state| state.features(mint))
+    }
+
+    /// Pick up mints created within the window, with their holders (after a restart)
+    pub async fn load(&mut self, pool: &Pool, now: DateTime<Utc>) -> Result<usize> {
+        let since = now - self.window;
+        let rows: Vec<MintFeatures> = sqlx::query_as(
+            r#"
+            SELECT mint, creator, created_at, created_slot, creation_slot_tokens, creation_slot_share,
+                   top10_share, creator_share, unique_buyers, first_sell_at, secs_to_first_sell
+            FROM mint_features WHERE created_at >= $1
+            "#,
+        )
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+        let loaded = rows.len();
+        for row in rows {
+            self.mints.insert(
+                row.mint,
+                MintState {
+                    creator: row.creator,
+                    created_at: row.created_at,
+                    created_slot: row.created_slot as u64,
+                    creation_slot_tokens: row.creation_slot_tokens as u64,
+                    first_sell_at: row.first_sell_at,
+                    holders: HashMap::new(),
+                    dirty_holders: HashSet::new(),
+                },
+            );
+        }
+
+        let holders: Vec<(String, String, i64, bool)> = sqlx::query_as(
+            r#"
+            SELECT h.mint, h.owner, h.balance, h.bought
+            FROM mint_holders h
+            JOIN mint_features f ON f.mint = h.mint
+            WHERE f.created_at >= $1
+            "#,
+        )
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+        for (mint, owner, balance, bought) in holders {
+            if let Some(state) = self.mints.get_mut(&mint) {
+                state.holders.insert(owner, Holder { balance: balance as u64, bought });
+            }
+        }
+        Ok(loaded)
+    }
+
+    /// Fold one transaction's activity into its mint (ignored until the CREATE is seen)
+    pub fn record(&mut self, activity: &MintActivity) {
+        if let Some(creator) = &activity.creator {
+            self.mints.entry(activity.mint.clone()).or_insert_with(|| MintState {
+                creator: creator.clone(),
+                created_at: activity.seen_at,
+                created_slot: activity.slot,
+                creation_slot_tokens: 0,
+                first_sell_at: None,
+                holders: HashMap::new(),
+                dirty_holders: HashSet::new(),
+            });
+        }
+        let Some(state) = self.mints.get_mut(&activity.mint) else {
+            return;
+        };
+
+        for fill in &activity.fills {
+            if fill.is_buy {
+                if activity.slot == state.created_slot {
+                    state.creation_slot_tokens += fill.tokens;
+                }
+                state.holders.entry(fill.wallet.clone()).or_default().bought = true;
+                state.dirty_holders.insert(fill.wallet.clone());
+            } else if state.first_sell_at.is_none() {
+                state.first_sell_at = Some(activity.seen_at);
+            }
+        }
+        for change in &activity.balances {
+            state.holders.entry(change.owner.clone()).or_default().balance = change.post;
+            state.dirty_holders.insert(change.owner.clone());
+        }
+        self.dirty.insert(activity.mint.clone());
+    }
+
+    /// Write changed mints and holders out, then forget the mints past the
+    /// window (their holders are deleted; the features stay). Returns mints written.
+    pub async fn flush(&mut self, pool: &Pool, now: DateTime<Utc>) -> Result<usize> {
+        let cutoff = now - self.window;
+        let expired: Vec<String> = self
+            .mints
+            .iter()
+            .filter(|(_, state)| state.created_at < cutoff)
+            .map(|(mint, _)| mint.clone())
+            .collect();
+        if self.dirty.is_empty() && expired.is_empty() {
+            return Ok(0);
+        }
+
+        let mut tx = pool.begin().await?;
+        for mint in &self.dirty {
+            let Some(state) = self.mints.get(mint) else {
+                continue;
+            };
+            let f = state.features(mint);
+            sqlx::query(
+                r#"
+                INSERT INTO mint_features
+                    (mint, creator, created_at, created_slot, creation_slot_tokens, creation_slot_share,
+                     top10_share, creator_share, unique_buyers, first_sell_at, secs_to_first_sell, updated_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                ON CONFLICT (mint) DO UPDATE SET
+                    creation_slot_tokens = excluded.creation_slot_tokens,
+                    creation_slot_share = excluded.creation_slot_share,
+                    top10_share = excluded.top10_share,
+                    creator_share = excluded.creator_share,
+                    unique_buyers = excluded.unique_buyers,
+                    first_sell_at = excluded.first_sell_at,
+                    secs_to_first_sell = excluded.secs_to_first_sell,
+                    updated_at = excluded.updated_at
+                "#,
+            )
+            .bind(&f.mint)
+            .bind(&f.creator)
+            .bind(f.created_at)
+            .bind(f.created_slot)
+            .bind(f.creation_slot_tokens)
+            .bind(f.creation_slot_share)
+            .bind(f.top10_share)
+            .bind(f.creator_share)
+            .bind(f.unique_buyers)
+            .bind(f.first_sell_at)
+            .bind(f.secs_to_first_sell)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+
+            for owner in &state.dirty_holders {
+                let holder = &state.holders[owner];
+                sqlx::query(
+                    r#"
+                    INSERT INTO mint_holders (mint, owner, balance, bought) VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (mint, owner) DO UPDATE SET balance = excluded.balance, bought = excluded.bought
+                    "#,
+                )
+                .bind(mint)
+                .bind(owner)
+                .bind(holder.balance as i64)
+                .bind(holder.bought)
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+        for mint in &expired {
+            sqlx::query("DELETE FROM mint_holders WHERE mint = $1")
+                .bind(mint)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        let written = self.dirty.len();
+        for mint in self.dirty.drain() {
+            if let Some(state) = self.mints.get_mut(&mint) {
+                state.dirty_holders.clear();
+            }
+        }
+        for mint in &expired {
+            self.mints.remove(mint);
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mints::{BalanceChange, MintFill};
+    use chrono::TimeZone;
+
+    const DEV: &str = "Dev1111111111111111111111111111111111111111";
+    const PERCENT: u64 = PUMP_TOKEN_SUPPLY / 100;
+
+    fn activity(slot: u64, secs: i64, creator: Option<&str>, fills: Vec<MintFill>, balances: Vec<BalanceChange>) -> MintActivity {
+        MintActivity {
+            mint: "Mint".to_string(),
+            sig: format!("sig{}", secs),
+            program: "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P".to_string(),
+            slot,
+            seen_at: Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap() + Duration::seconds(secs),
+            creator: creator.map(str::to_string),
+            fills,
+            balances,
+        }
+    }
+
+    fn buy(wallet: &str, tokens: u64) -> MintFill {
+        MintFill { wallet: wallet.to_string(), is_buy: true, tokens, sol: None }
+    }
+
+    fn balance(owner: &str, pre: u64, post: u64) -> BalanceChange {
+        BalanceChange { owner: owner.to_string(), pre, post }
+    }
+
+    #[test]
+    fn bundled_launch_and_concentration() {
+        let mut features = MintFeatureAggregator::new(Duration::hours(24));
+        // Trades before the CREATE is seen are ignored
+        features.record(&activity(99, 0, None, vec![buy("Early", PERCENT)], vec![]));
+        assert!(features.is_empty());
+
+        // Creator and a bundled wallet buy 10% + 5% in the creation slot
+        features.record(&activity(100, 0, Some(DEV), vec![buy(DEV, 10 * PERCENT)], vec![balance(DEV, 0, 10 * PERCENT)]));
+        features.record(&activity(100, 0, None, vec![buy("Bundle", 5 * PERCENT)], vec![balance("Bundle", 0, 5 * PERCENT)]));
+        // Twelve 1% buyers later
+        for i in 0..12 {
+            let wallet = format!("Buyer{}", i);
+            features.record(&activity(101, 5, None, vec![buy(&wallet, PERCENT)], vec![balance(&wallet, 0, PERCENT)]));
+        }
+        // The bundle dumps 30s in
+        let sell = MintFill { wallet: "Bundle".to_string(), is_buy: false, tokens: 5 * PERCENT, sol: None };
+        features.record(&activity(140, 30, None, vec![sell], vec![balance("Bundle", 5 * PERCENT, 0)]));
+
+        let f = features.features("Mint").unwrap();
+        assert!((f.creation_slot_share - 0.15).abs() < 1e-12);
+        // 10% creator + nine 1% buyers
+        assert!((f.top10_share - 0.19).abs() < 1e-12, "{}", f.top10_share);
+        assert!((f.creator_share - 0.10).abs() < 1e-12);
+        assert_eq!(f.unique_buyers, 14);
+        assert_eq!(f.secs_to_first_sell, Some(30.0));
+    }
+}
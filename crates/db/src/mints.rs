@@ -0,0 +1,52 @@
+//! What the ingestor saw happen to a mint in one transaction
+//!
+//! The decode stage builds one `MintActivity` per mint its Pump.fun
+//! instructions touched; the per-mint trackers (creators, features) consume them.
+
+use chrono::{DateTime, Utc};
+
+/// Every Pump.fun mint has 1B tokens with 6 decimals
+pub const PUMP_TOKEN_SUPPLY: u64 = 1_000_000_000_000_000;
+
+/// A BUY or SELL of the mint
+#[derive(Debug, Clone, PartialEq)]
+pub struct MintFill {
+    pub wallet: String,
+    pub is_buy: bool,
+    /// Raw token units
+    pub tokens: u64,
+    pub sol: Option<f64>,
+}
+
+/// An owner's token balance of the mint before and after the transaction (raw units)
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceChange {
+    pub owner: String,
+    pub pre: u64,
+    pub post: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MintActivity {
+    pub mint: String,
+    pub sig: String,
+    pub program: String,
+    pub slot: u64,
+    pub seen_at: DateTime<Utc>,
+    /// CREATE signer, when the mint was created in this transaction
+    pub creator: Option<String>,
+    pub fills: Vec<MintFill>,
+    /// Owners whose balance changed, the mint's bonding curve excluded
+    pub balances: Vec<BalanceChange>,
+}
+
+impl MintActivity {
+    /// Tokens `wallet` sold in this transaction
+    pub fn sold_by(&self, wallet: &str) -> u64 {
+        self.fills.iter().filter(|f| !f.is_buy && f.wallet == wallet).map(|f| f.tokens).sum()
+    }
+
+    pub fn balance_of(&self, owner: &str) -> Option<&BalanceChange> {
+        self.balances.iter().find(|b| b.owner == owner)
+    }
+}
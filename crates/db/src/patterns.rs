@@ -0,0 +1,379 @@
+//! Wallet pattern analyzer - learn each tracked wallet's trading habits
+//!
+//! Reads the wallet's stitched `trades` (and the SOL limits on its raw BUY/SELL
+//! events) over a trailing window and upserts one `wallet_patterns` row:
+//! hold times, exit multiples, exit style, entry size, active hours and
+//! slippage tolerance.
+
+use crate::Pool;
+use anyhow::Result;
+use chrono::{DateTime, Timelike, Utc};
+use sqlx::Row;
+use std::collections::HashSet;
+
+const NS_PER_DAY: i64 = 86_400 * 1_000_000_000;
+
+/// Width of `preferred_time_window`, in hours
+const TIME_WINDOW_HOURS: usize = 4;
+
+/// One stitched trade (lot) of the wallet
+#[derive(Debug, Clone, Default)]
+pub struct TradeSample {
+    pub open_ts_ns: i64,
+    pub open_cost_sol: f64,
+    /// SELLs that took tokens from the lot
+    pub closes: i64,
+    /// Set once the lot is fully sold
+    pub hold_ms: Option<i64>,
+    pub pnl_x: Option<f64>,
+    pub was_win: Option<bool>,
+}
+
+impl TradeSample {
+    fn is_closed(&self) -> bool {
+        self.was_win.is_some()
+    }
+}
+
+/// One `wallet_patterns` row
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalletPattern {
+    pub wallet: String,
+    /// UTC days with at least one entry
+    pub sample_days: i32,
+    pub sample_trades: i32,
+    pub win_rate: Option<f64>,
+    pub avg_daily_trades: f64,
+    pub avg_entry_sol: f64,
+    pub p50_hold_ms: Option<i64>,
+    pub p90_hold_ms: Option<i64>,
+    /// Exit multiple (proceeds / cost) of fully sold lots
+    pub p50_target_x: Option<f64>,
+    pub p90_target_x: Option<f64>,
+    pub typical_exit_style: Option<String>,
+    pub slippage_pctl_95: Option<f64>,
+    /// e.g. "14:00-18:00 UTC"
+    pub preferred_time_window: Option<String>,
+}
+
+/// Nearest-rank percentile of an ascending slice (`p` in 0..=1)
+pub fn percentile<T: Copy>(sorted: &[T], p: f64) -> Option<T> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
+
+/// How the wallet usually gets out:
+/// - `holder`: most lots are still (partly) unsold
+/// - `scale_out`: most sold lots took several SELLs
+/// - `single_exit`: most sold lots went in one SELL
+pub fn exit_style(trades: &[TradeSample]) -> Option<&'static str> {
+    if trades.is_empty() {
+        return None;
+    }
+    let closed: Vec<&TradeSample> = trades.iter().filter(|t| t.is_closed()).collect();
+    if closed.len() * 2 < trades.len() {
+        return Some("holder");
+    }
+    let scaled = closed.iter().filter(|t| t.closes > 1).count();
+    Some(if scaled * 2 > closed.len() { "scale_out" } else { "single_exit" })
+}
+
+/// The `TIME_WINDOW_HOURS`-hour UTC window (wrapping midnight) with the most
+/// entries; ties go to the earliest start
+pub fn preferred_time_window(entries_ns: &[i64]) -> Option<String> {
+    if entries_ns.is_empty() {
+        return None;
+    }
+    let mut by_hour = [0usize; 24];
+    for ts in entries_ns {
+        let hour = DateTime::<Utc>::from_timestamp_nanos(*ts).hour() as usize;
+        by_hour[hour] += 1;
+    }
+    let start = (0..24)
+        .max_by_key(|&h| {
+            let count: usize = (0..TIME_WINDOW_HOURS).map(|i| by_hour[(h + i) % 24]).sum();
+            (count, std::cmp::Reverse(h))
+        })
+        .unwrap_or(0);
+    Some(format!("{:02}:00-{:02}:00 UTC", start, (start + TIME_WINDOW_HOURS) % 24))
+}
+
+/// Slippage the wallet allowed on one fill: how far its SOL limit (BUY
+/// `max_sol_cost`, SELL `min_sol_output`) was from what it actually paid or got
+pub fn slippage_tolerance(is_buy: bool, sol: f64, limit_sol: f64) -> Option<f64> {
+    if sol <= 0.0 || limit_sol < 0.0 {
+        return None;
+    }
+    let tolerance = if is_buy { limit_sol / sol - 1.0 } else { 1.0 - limit_sol / sol };
+    Some(tolerance.max(0.0))
+}
+
+/// Compute a wallet's pattern from its trades in the window and the slippage
+/// tolerances of its fills. None when there's nothing to learn from.
+pub fn analyze(wallet: &str, trades: &[TradeSample], slippages: &[f64]) -> Option<WalletPattern> {
+    if trades.is_empty() {
+        return None;
+    }
+
+    let days: HashSet<i64> = trades.iter().map(|t| t.open_ts_ns.div_euclid(NS_PER_DAY)).collect();
+    let sample_days = days.len() as i32;
+    let sample_trades = trades.len() as i32;
+
+    let closed: Vec<&TradeSample> = trades.iter().filter(|t| t.is_closed()).collect();
+    let wins = closed.iter().filter(|t| t.was_win == Some(true)).count();
+    let win_rate = (!closed.is_empty()).then(|| wins as f64 / closed.len() as f64);
+
+    let mut holds: Vec<i64> = closed.iter().filter_map(|t| t.hold_ms).collect();
+    holds.sort_unstable();
+    let mut targets: Vec<f64> = closed.iter().filter_map(|t| t.pnl_x).filter(|x| x.is_finite()).collect();
+    targets.sort_by(f64::total_cmp);
+    let mut slippages: Vec<f64> = slippages.iter().copied().filter(|s| s.is_finite()).collect();
+    slippages.sort_by(f64::total_cmp);
+
+    let entries: Vec<i64> = trades.iter().map(|t| t.open_ts_ns).collect();
+
+    Some(WalletPattern {
+        wallet: wallet.to_string(),
+        sample_days,
+        sample_trades,
+        win_rate,
+        avg_daily_trades: sample_trades as f64 / sample_days as f64,
+        avg_entry_sol: trades.iter().map(|t| t.open_cost_sol).sum::<f64>() / trades.len() as f64,
+        p50_hold_ms: percentile(&holds, 0.5),
+        p90_hold_ms: percentile(&holds, 0.9),
+        p50_target_x: percentile(&targets, 0.5),
+        p90_target_x: percentile(&targets, 0.9),
+        typical_exit_style: exit_style(trades).map(str::to_string),
+        slippage_pctl_95: percentile(&slippages, 0.95),
+        preferred_time_window: preferred_time_window(&entries),
+    })
+}
+
+/// Result of an analyzer run
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AnalyzeStats {
+    pub wallets: usize,
+    pub patterns: usize,
+    pub trades: usize,
+}
+
+/// Recompute `wallet_patterns` for every tracked wallet over the last `window_days`
+pub async fn analyze_tracked_wallets(pool: &Pool, window_days: u32) -> Result<AnalyzeStats> {
+    let since_ns = Utc::now().timestamp_nanos_opt().unwrap_or(i64::MAX) - window_days as i64 * NS_PER_DAY;
+    let wallets: Vec<String> = sqlx::query_scalar("SELECT wallet FROM wallets WHERE is_tracked ORDER BY wallet")
+        .fetch_all(pool)
+        .await?;
+
+    let mut stats = AnalyzeStats {
+        wallets: wallets.len(),
+        ..AnalyzeStats::default()
+    };
+    for wallet in &wallets {
+        let trades = load_trades(pool, wallet, since_ns).await?;
+        let slippages = load_slippages(pool, wallet, since_ns).await?;
+        let Some(pattern) = analyze(wallet, &trades, &slippages) else {
+            continue;
+        };
+        upsert_pattern(pool, &pattern).await?;
+        stats.patterns += 1;
+        stats.trades += trades.len();
+    }
+
+    Ok(stats)
+}
+
+/// The wallet's trades opened since `since_ns`
+async fn load_trades(pool: &Pool, wallet: &str, since_ns: i64) -> Result<Vec<TradeSample>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT open_ts_ns, open_cost_sol, hold_ms, pnl_x, was_win, meta_json FROM trades
+        WHERE wallet = $1 AND open_ts_ns >= $2
+        "#
+    )
+    .bind(wallet)
+    .bind(since_ns)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let meta: Option<serde_json::Value> = row.get("meta_json");
+            TradeSample {
+                open_ts_ns: row.get("open_ts_ns"),
+                open_cost_sol: row.get("open_cost_sol"),
+                closes: meta.and_then(|m| m["closes"].as_i64()).unwrap_or(0),
+                hold_ms: row.get("hold_ms"),
+                pnl_x: row.get("pnl_x"),
+                was_win: row.get("was_win"),
+            }
+        })
+        .collect())
+}
+
+/// Slippage tolerance of each of the wallet's own fills since `since_ns` that
+/// recorded its SOL limit (`meta_json.sol_limit_lamports`)
+async fn load_slippages(pool: &Pool, wallet: &str, since_ns: i64) -> Result<Vec<f64>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT action, amount_out, meta_json FROM raw_events
+        WHERE wallet = $1 AND ts_ns >= $2 AND action IN ('BUY', 'SELL')
+          AND (role IS NULL OR role = 'signer')
+        "#
+    )
+    .bind(wallet)
+    .bind(since_ns)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let sol: f64 = row.get::<Option<f64>, _>("amount_out")?;
+            let meta: serde_json::Value = row.get::<Option<serde_json::Value>, _>("meta_json")?;
+            let limit = meta["sol_limit_lamports"].as_u64()? as f64 / 1_000_000_000.0;
+            slippage_tolerance(row.get::<String, _>("action") == "BUY", sol, limit)
+        })
+        .collect())
+}
+
+/// Leaves `followership_score`/`leader_score` to the follow-edge job
+async fn upsert_pattern(pool: &Pool, p: &WalletPattern) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO wallet_patterns (
+            wallet, sample_days, sample_trades, win_rate, avg_daily_trades, avg_entry_sol,
+            p50_hold_ms, p90_hold_ms, p50_target_x, p90_target_x, typical_exit_style,
+            slippage_pctl_95, preferred_time_window, updated_at
+        ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, CURRENT_TIMESTAMP)
+        ON CONFLICT (wallet) DO UPDATE SET
+            sample_days = EXCLUDED.sample_days,
+            sample_trades = EXCLUDED.sample_trades,
+            win_rate = EXCLUDED.win_rate,
+            avg_daily_trades = EXCLUDED.avg_daily_trades,
+            avg_entry_sol = EXCLUDED.avg_entry_sol,
+            p50_hold_ms = EXCLUDED.p50_hold_ms,
+            p90_hold_ms = EXCLUDED.p90_hold_ms,
+            p50_target_x = EXCLUDED.p50_target_x,
+            p90_target_x = EXCLUDED.p90_target_x,
+            typical_exit_style = EXCLUDED.typical_exit_style,
+            slippage_pctl_95 = EXCLUDED.slippage_pctl_95,
+            preferred_time_window = EXCLUDED.preferred_time_window,
+            updated_at = EXCLUDED.updated_at
+        "#
+    )
+    .bind(&p.wallet)
+    .bind(p.sample_days)
+    .bind(p.sample_trades)
+    .bind(p.win_rate)
+    .bind(p.avg_daily_trades)
+    .bind(p.avg_entry_sol)
+    .bind(p.p50_hold_ms)
+    .bind(p.p90_hold_ms)
+    .bind(p.p50_target_x)
+    .bind(p.p90_target_x)
+    .bind(&p.typical_exit_style)
+    .bind(p.slippage_pctl_95)
+    .bind(&p.preferred_time_window)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR_NS: i64 = 3_600 * 1_000_000_000;
+
+    fn closed(open_ts_ns: i64, cost: f64, hold_ms: i64, pnl_x: f64, closes: i64) -> TradeSample {
+        TradeSample {
+            open_ts_ns,
+            open_cost_sol: cost,
+            closes,
+            hold_ms: Some(hold_ms),
+            pnl_x: Some(pnl_x),
+            was_win: Some(pnl_x > 1.0),
+        }
+    }
+
+    fn open(open_ts_ns: i64, cost: f64) -> TradeSample {
+        TradeSample { open_ts_ns, open_cost_sol: cost, ..TradeSample::default() }
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let values: Vec<i64> = (1..=10).collect();
+        assert_eq!(percentile(&values, 0.5), Some(5));
+        assert_eq!(percentile(&values, 0.9), Some(9));
+        assert_eq!(percentile(&values, 0.95), Some(10));
+        assert_eq!(percentile(&[7], 0.0), Some(7));
+        assert_eq!(percentile::<i64>(&[], 0.5), None);
+    }
+
+    #[test]
+    fn scalper_history() {
+        // Ten quick single-exit flips over two days, mostly 14:00-16:00 UTC
+        let mut trades = Vec::new();
+        for i in 0..10 {
+            let day = (i / 5) as i64 * 24 * HOUR_NS;
+            let x = if i < 7 { 1.2 + i as f64 * 0.1 } else { 0.8 };
+            trades.push(closed(day + 14 * HOUR_NS + i as i64 * 600_000_000_000, 0.5, 20_000 + i as i64 * 1_000, x, 1));
+        }
+        let slippages: Vec<f64> = (0..20).map(|i| 0.01 * (i + 1) as f64).collect();
+
+        let p = analyze("W", &trades, &slippages).unwrap();
+        assert_eq!((p.sample_days, p.sample_trades), (2, 10));
+        assert_eq!(p.avg_daily_trades, 5.0);
+        assert_eq!(p.win_rate, Some(0.7));
+        assert_eq!(p.avg_entry_sol, 0.5);
+        assert_eq!((p.p50_hold_ms, p.p90_hold_ms), (Some(24_000), Some(28_000)));
+        assert!((p.p50_target_x.unwrap() - 1.3).abs() < 1e-12);
+        assert!((p.p90_target_x.unwrap() - 1.7).abs() < 1e-12);
+        assert_eq!(p.typical_exit_style.as_deref(), Some("single_exit"));
+        assert!((p.slippage_pctl_95.unwrap() - 0.19).abs() < 1e-12);
+        assert_eq!(p.preferred_time_window.as_deref(), Some("12:00-16:00 UTC"));
+    }
+
+    #[test]
+    fn scale_out_and_holder_styles() {
+        let scaled = [
+            closed(0, 1.0, 60_000, 2.0, 3),
+            closed(HOUR_NS, 1.0, 60_000, 1.5, 2),
+            closed(2 * HOUR_NS, 1.0, 60_000, 0.5, 1),
+        ];
+        assert_eq!(exit_style(&scaled), Some("scale_out"));
+
+        // Two of three lots never fully sold
+        let holder = [closed(0, 1.0, 60_000, 2.0, 3), open(HOUR_NS, 2.0), open(2 * HOUR_NS, 3.0)];
+        let p = analyze("W", &holder, &[]).unwrap();
+        assert_eq!(p.typical_exit_style.as_deref(), Some("holder"));
+        assert_eq!(p.win_rate, Some(1.0));
+        assert_eq!(p.avg_entry_sol, 2.0);
+        assert_eq!(p.slippage_pctl_95, None);
+    }
+
+    #[test]
+    fn time_window_wraps_midnight() {
+        let entries = [23 * HOUR_NS, 24 * HOUR_NS, 25 * HOUR_NS, 12 * HOUR_NS];
+        assert_eq!(preferred_time_window(&entries).as_deref(), Some("22:00-02:00 UTC"));
+    }
+
+    #[test]
+    fn slippage_from_sol_limits() {
+        // Allowed 10% over on a buy, 25% under on a sell
+        assert!((slippage_tolerance(true, 1.0, 1.1).unwrap() - 0.1).abs() < 1e-12);
+        assert!((slippage_tolerance(false, 2.0, 1.5).unwrap() - 0.25).abs() < 1e-12);
+        // Limit tighter than the fill (fees): no negative tolerance
+        assert_eq!(slippage_tolerance(true, 1.0, 0.99), Some(0.0));
+        assert_eq!(slippage_tolerance(true, 0.0, 1.0), None);
+    }
+
+    #[test]
+    fn no_trades_no_pattern() {
+        assert_eq!(analyze("W", &[], &[0.1]), None);
+    }
+}
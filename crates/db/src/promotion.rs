@@ -0,0 +1,291 @@
+//! Promote discovered wallets into tracking, and demote them again
+//!
+//! Candidates come from `wallet_stats` in the discovery database; promoted
+//! wallets are inserted into the main database's `wallets` (which the ingestor
+//! reloads live) with `auto_promoted` set, and every change is written to
+//! `wallet_promotions`. Only auto-promoted wallets are ever demoted. A wallet
+//! whose sybil cluster (`db::clusters`) already has a tracked member, or a
+//! better-scoring one, isn't promoted.
+
+use crate::classify::{self, WalletClass};
+use crate::clusters;
+use crate::{Connection, Pool};
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// When a wallet is promoted, and when a promoted one is dropped again
+#[derive(Debug, Clone)]
+pub struct PromotionRules {
+    pub min_profit_score: f64,
+    pub min_trades: i32,
+    /// Only wallets that traded within this many days are promoted
+    pub max_idle_days: i64,
+    /// Classes that may be promoted; a tracked wallet reclassified out of them is demoted
+    pub classes: Vec<WalletClass>,
+    /// Demote below this score (keep it under `min_profit_score` so wallets
+    /// near the line don't flap)
+    pub demote_below_score: f64,
+    /// Demote after this many days without a trade
+    pub demote_idle_days: i64,
+    /// Promotions per run, best scores first
+    pub max_per_run: i64,
+}
+
+impl Default for PromotionRules {
+    fn default() -> Self {
+        Self {
+            min_profit_score: 0.5,
+            min_trades: 20,
+            max_idle_days: 3,
+            classes: WalletClass::COPYABLE.to_vec(),
+            demote_below_score: 0.0,
+            demote_idle_days: 14,
+            max_per_run: 10,
+        }
+    }
+}
+
+/// A wallet's discovery stats as the rules see them
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Candidate {
+    pub wallet: String,
+    pub profit_score: f64,
+    pub total_trades: i32,
+    pub last_seen: DateTime<Utc>,
+    pub wallet_class: String,
+}
+
+impl PromotionRules {
+    fn allows(&self, class: &str) -> bool {
+        // A class this build doesn't know is never copyable
+        WalletClass::from_str(class).is_ok_and(|c| self.classes.is_empty() || self.classes.contains(&c))
+    }
+
+    pub fn should_promote(&self, c: &Candidate, now: DateTime<Utc>) -> bool {
+        c.profit_score >= self.min_profit_score
+            && c.total_trades >= self.min_trades
+            && now - c.last_seen <= Duration::days(self.max_idle_days)
+            && self.allows(&c.wallet_class)
+    }
+
+    /// Why a tracked wallet should be dropped, if it should
+    pub fn demotion_reason(&self, c: &Candidate, now: DateTime<Utc>) -> Option<String> {
+        if !self.allows(&c.wallet_class) {
+            return Some(format!("classified {}", c.wallet_class));
+        }
+        if c.profit_score < self.demote_below_score {
+            return Some(format!("score {:.3} below {:.3}", c.profit_score, self.demote_below_score));
+        }
+        let idle = (now - c.last_seen).num_days();
+        if idle >= self.demote_idle_days {
+            return Some(format!("idle for {} days", idle));
+        }
+        None
+    }
+}
+
+/// Alias for a promoted wallet, e.g. `auto_7xKXtg2C`
+pub fn generated_alias(wallet: &str) -> String {
+    format!("auto_{}", wallet.get(..8).unwrap_or(wallet))
+}
+
+/// What one run changed
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PromotionStats {
+    pub promoted: usize,
+    pub demoted: usize,
+}
+
+const CANDIDATE_COLUMNS: &str = "wallet, profit_score, total_trades, last_seen, wallet_class";
+
+/// One promotion pass: demote tracked wallets that no longer pass, then
+/// promote the best untracked ones that do. `discovery` and `main` may be the
+/// same pool (single-file SQLite).
+pub async fn run(discovery: &Pool, main: &Pool, rules: &PromotionRules, now: DateTime<Utc>) -> Result<PromotionStats> {
+    let mut stats = PromotionStats::default();
+    // Wallets added by hand (tracked or deliberately not) are never touched
+    let hand_added: HashSet<String> = sqlx::query_scalar("SELECT wallet FROM wallets WHERE NOT auto_promoted")
+        .fetch_all(main)
+        .await?
+        .into_iter()
+        .collect();
+
+    let tracked: Vec<Candidate> = sqlx::query_as(&format!(
+        "SELECT {} FROM wallet_stats WHERE is_tracked",
+        CANDIDATE_COLUMNS
+    ))
+    .fetch_all(discovery)
+    .await?;
+    for wallet in tracked.iter().filter(|c| !hand_added.contains(&c.wallet)) {
+        let Some(reason) = rules.demotion_reason(wallet, now) else {
+            continue;
+        };
+        if demote(main, wallet, &reason).await? {
+            stats.demoted += 1;
+        }
+        set_tracked(discovery, &wallet.wallet, None).await?;
+    }
+
+    let candidates: Vec<Candidate> = sqlx::query_as(&format!(
+        r#"
+        SELECT {}
+        FROM wallet_stats
+        WHERE NOT is_tracked AND profit_score >= $1 AND total_trades >= $2 AND last_seen >= $3 {} {} {}
+        ORDER BY profit_score DESC
+        LIMIT $4
+        "#,
+        CANDIDATE_COLUMNS,
+        classify::class_filter(&rules.classes),
+        clusters::ONE_PER_CLUSTER,
+        clusters::NO_TRACKED_PEER
+    ))
+    .bind(rules.min_profit_score)
+    .bind(rules.min_trades)
+    .bind(now - Duration::days(rules.max_idle_days))
+    .bind(rules.max_per_run + hand_added.len() as i64)
+    .fetch_all(discovery)
+    .await?;
+    let promotable = candidates
+        .iter()
+        .filter(|c| !hand_added.contains(&c.wallet) && rules.should_promote(c, now))
+        .take(rules.max_per_run.max(0) as usize);
+    for wallet in promotable {
+        if promote(main, wallet).await? {
+            stats.promoted += 1;
+        }
+        set_tracked(discovery, &wallet.wallet, Some(now)).await?;
+    }
+
+    Ok(stats)
+}
+
+/// Insert (or re-track a previously demoted) wallet; false if it already was tracked
+async fn promote(main: &Pool, c: &Candidate) -> Result<bool> {
+    let mut tx = main.begin().await?;
+    let changed = sqlx::query(
+        r#"
+        INSERT INTO wallets (wallet, alias, is_tracked, notes, auto_promoted)
+        VALUES ($1, $2, TRUE, $3, TRUE)
+        ON CONFLICT (wallet) DO UPDATE SET is_tracked = TRUE
+        WHERE wallets.auto_promoted AND NOT wallets.is_tracked
+        "#,
+    )
+    .bind(&c.wallet)
+    .bind(generated_alias(&c.wallet))
+    .bind("promoted from discovery")
+    .execute(&mut *tx)
+    .await?
+    .rows_affected()
+        > 0;
+    if changed {
+        let reason = format!(
+            "score {:.3}, {} trades, {}",
+            c.profit_score, c.total_trades, c.wallet_class
+        );
+        audit(&mut tx, c, "promote", &reason).await?;
+    }
+    tx.commit().await?;
+    Ok(changed)
+}
+
+/// Stop tracking an auto-promoted wallet; false if there was nothing to stop
+async fn demote(main: &Pool, c: &Candidate, reason: &str) -> Result<bool> {
+    let mut tx = main.begin().await?;
+    let changed = sqlx::query("UPDATE wallets SET is_tracked = FALSE WHERE wallet = $1 AND auto_promoted AND is_tracked")
+        .bind(&c.wallet)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+        > 0;
+    if changed {
+        audit(&mut tx, c, "demote", reason).await?;
+    }
+    tx.commit().await?;
+    Ok(changed)
+}
+
+async fn audit(conn: &mut Connection, c: &Candidate, action: &str, reason: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO wallet_promotions (wallet, action, reason, profit_score, total_trades, wallet_class)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(&c.wallet)
+    .bind(action)
+    .bind(reason)
+    .bind(c.profit_score)
+    .bind(c.total_trades)
+    .bind(&c.wallet_class)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// `is_tracked` / `added_to_tracking_at` in the discovery database
+async fn set_tracked(discovery: &Pool, wallet: &str, since: Option<DateTime<Utc>>) -> Result<()> {
+    sqlx::query("UPDATE wallet_stats SET is_tracked = $2, added_to_tracking_at = $3 WHERE wallet = $1")
+        .bind(wallet)
+        .bind(since.is_some())
+        .bind(since)
+        .execute(discovery)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap()
+    }
+
+    fn candidate(profit_score: f64, total_trades: i32, idle_days: i64, class: &str) -> Candidate {
+        Candidate {
+            wallet: "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU".to_string(),
+            profit_score,
+            total_trades,
+            last_seen: now() - Duration::days(idle_days),
+            wallet_class: class.to_string(),
+        }
+    }
+
+    #[test]
+    fn promotes_only_active_copyable_wallets_above_the_bar() {
+        let rules = PromotionRules::default();
+        assert!(rules.should_promote(&candidate(1.0, 50, 1, "manual"), now()));
+        assert!(!rules.should_promote(&candidate(0.4, 50, 1, "manual"), now()));
+        assert!(!rules.should_promote(&candidate(1.0, 5, 1, "manual"), now()));
+        assert!(!rules.should_promote(&candidate(1.0, 50, 10, "manual"), now()));
+        assert!(!rules.should_promote(&candidate(1.0, 50, 1, "sniper"), now()));
+    }
+
+    #[test]
+    fn demotes_on_decay_but_not_inside_the_hysteresis_band() {
+        let rules = PromotionRules::default();
+        // Below the promotion bar but above the demotion one
+        assert_eq!(rules.demotion_reason(&candidate(0.2, 50, 1, "manual"), now()), None);
+        assert_eq!(
+            rules.demotion_reason(&candidate(-0.5, 50, 1, "manual"), now()).as_deref(),
+            Some("score -0.500 below 0.000")
+        );
+        assert_eq!(
+            rules.demotion_reason(&candidate(1.0, 50, 20, "manual"), now()).as_deref(),
+            Some("idle for 20 days")
+        );
+        assert_eq!(
+            rules.demotion_reason(&candidate(1.0, 50, 1, "hf_bot"), now()).as_deref(),
+            Some("classified hf_bot")
+        );
+    }
+
+    #[test]
+    fn alias_is_the_wallet_prefix() {
+        assert_eq!(generated_alias("7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU"), "auto_7xKXtg2C");
+        assert_eq!(generated_alias("short"), "auto_short");
+    }
+}
@@ -2,10 +2,14 @@
 
 use anyhow::Result;
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use crate::Pool;
+#[cfg(feature = "postgres")]
 use sqlx::PgPool;
+use tracing::warn;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RawEvent {
     pub ts_ns: i64,
     pub slot: Option<i64>,
@@ -34,9 +38,14 @@ pub struct RawEvent {
     pub pre_balance_sol: Option<f64>,  // Wallet SOL balance before transaction
     pub post_balance_sol: Option<f64>, // Wallet SOL balance after transaction
     pub balance_change_sol: Option<f64>, // Net SOL balance change (post - pre)
+    // How the wallet took part: signer | fee_payer | counterparty | referenced
+    pub role: Option<String>,
+    // The wallet's wallet_roles, comma separated (e.g. "liquidity,admin")
+    #[serde(default)]
+    pub wallet_roles: Option<String>,
 }
 
-pub async fn insert_raw_events_batch(pool: &PgPool, events: &[RawEvent]) -> Result<()> {
+pub async fn insert_raw_events_batch(pool: &Pool, events: &[RawEvent]) -> Result<()> {
     if events.is_empty() {
         return Ok(());
     }
@@ -50,8 +59,8 @@ pub async fn insert_raw_events_batch(pool: &PgPool, events: &[RawEvent]) -> Resu
                 mint, base_mint, quote_mint, amount_in, amount_out,
                 price_est, fee_sol, ix_accounts_json, meta_json, leader_wallet,
                 block_time, recv_time_ns, ix_index, decode_ok, decode_err,
-                pre_balance_sol, post_balance_sol, balance_change_sol
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25)
+                pre_balance_sol, post_balance_sol, balance_change_sol, role, wallet_roles
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27)
             ON CONFLICT (sig, wallet, action) DO NOTHING
             "#
         )
@@ -80,6 +89,8 @@ pub async fn insert_raw_events_batch(pool: &PgPool, events: &[RawEvent]) -> Resu
         .bind(event.pre_balance_sol)
         .bind(event.post_balance_sol)
         .bind(event.balance_change_sol)
+        .bind(&event.role)
+        .bind(&event.wallet_roles)
         .execute(&mut *tx)
         .await?;
     }
@@ -87,18 +98,20 @@ pub async fn insert_raw_events_batch(pool: &PgPool, events: &[RawEvent]) -> Resu
     Ok(())
 }
 
-pub async fn batch_insert_raw_events(pool: &PgPool, events: &[RawEvent]) -> Result<usize> {
+/// Insert events one by one, skipping rows the database rejects.
+/// Connection-level failures abort with an error so the caller can spill the batch.
+pub async fn batch_insert_raw_events(pool: &Pool, events: &[RawEvent]) -> Result<usize> {
     let mut count = 0;
     for event in events {
-        sqlx::query(
+        let result = sqlx::query(
             r#"
             INSERT INTO raw_events (
                 ts_ns, slot, sig, wallet, alias, program, action,
                 mint, base_mint, quote_mint, amount_in, amount_out,
                 price_est, fee_sol, ix_accounts_json, meta_json, leader_wallet,
                 block_time, recv_time_ns, ix_index, decode_ok, decode_err,
-                pre_balance_sol, post_balance_sol, balance_change_sol
-            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25)
+                pre_balance_sol, post_balance_sol, balance_change_sol, role, wallet_roles
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27)
             ON CONFLICT (sig, wallet, action) DO NOTHING
             "#
         )
@@ -127,17 +140,223 @@ pub async fn batch_insert_raw_events(pool: &PgPool, events: &[RawEvent]) -> Resu
         .bind(event.pre_balance_sol)
         .bind(event.post_balance_sol)
         .bind(event.balance_change_sol)
+        .bind(&event.role)
+        .bind(&event.wallet_roles)
         .execute(pool)
-        .await
-        .map(|_| {
-            count += 1;
-        })
-        .map_err(|e| {
-            eprintln!("❌ Database insert error: {} (sig: {:?}, wallet: {}, action: {})", 
-                e, event.sig, event.wallet, event.action);
-            e
-        })
-        .ok();
+        .await;
+
+        match result {
+            Ok(_) => count += 1,
+            Err(sqlx::Error::Database(e)) => {
+                eprintln!("❌ Database insert error: {} (sig: {:?}, wallet: {}, action: {})", 
+                    e, event.sig, event.wallet, event.action);
+            }
+            Err(e) => return Err(e.into()),
+        }
     }
     Ok(count)
 }
+
+/// Bulk insert for the selected backend: `COPY` on Postgres, one transaction
+/// of row inserts on SQLite. Returns rows inserted.
+pub async fn bulk_insert_raw_events(pool: &Pool, events: &[RawEvent]) -> Result<usize> {
+    #[cfg(feature = "postgres")]
+    {
+        copy_insert_raw_events(pool, events).await
+    }
+    #[cfg(feature = "sqlite")]
+    {
+        sqlite_insert_raw_events(pool, events).await
+    }
+}
+
+/// SQLite has no COPY, but a single transaction avoids a commit (fsync) per row.
+/// A rejected row only rolls back its own statement, so it's logged and skipped.
+#[cfg(feature = "sqlite")]
+async fn sqlite_insert_raw_events(pool: &Pool, events: &[RawEvent]) -> Result<usize> {
+    if events.is_empty() {
+        return Ok(0);
+    }
+
+    let mut tx = pool.begin().await?;
+    let mut count = 0;
+    for event in events {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO raw_events (
+                ts_ns, slot, sig, wallet, alias, program, action,
+                mint, base_mint, quote_mint, amount_in, amount_out,
+                price_est, fee_sol, ix_accounts_json, meta_json, leader_wallet,
+                block_time, recv_time_ns, ix_index, decode_ok, decode_err,
+                pre_balance_sol, post_balance_sol, balance_change_sol, role, wallet_roles
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27)
+            ON CONFLICT (sig, wallet, action) DO NOTHING
+            "#
+        )
+        .bind(event.ts_ns)
+        .bind(event.slot)
+        .bind(&event.sig)
+        .bind(&event.wallet)
+        .bind(&event.alias)
+        .bind(&event.program)
+        .bind(&event.action)
+        .bind(&event.mint)
+        .bind(&event.base_mint)
+        .bind(&event.quote_mint)
+        .bind(event.amount_in)
+        .bind(event.amount_out)
+        .bind(event.price_est)
+        .bind(event.fee_sol)
+        .bind(&event.ix_accounts_json)
+        .bind(&event.meta_json)
+        .bind(&event.leader_wallet)
+        .bind(event.block_time)
+        .bind(event.recv_time_ns)
+        .bind(event.ix_index)
+        .bind(event.decode_ok)
+        .bind(&event.decode_err)
+        .bind(event.pre_balance_sol)
+        .bind(event.post_balance_sol)
+        .bind(event.balance_change_sol)
+        .bind(&event.role)
+        .bind(&event.wallet_roles)
+        .execute(&mut *tx)
+        .await;
+
+        match result {
+            Ok(done) => count += done.rows_affected() as usize,
+            Err(sqlx::Error::Database(e)) => {
+                warn!("Skipping raw_event rejected by SQLite: {} (sig: {:?}, wallet: {})", e, event.sig, event.wallet);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    tx.commit().await?;
+    Ok(count)
+}
+
+/// Columns written by the bulk COPY path, in CSV order
+#[cfg(feature = "postgres")]
+const COPY_COLUMNS: &str = "ts_ns, slot, sig, wallet, alias, program, action, \
+    mint, base_mint, quote_mint, amount_in, amount_out, \
+    price_est, fee_sol, ix_accounts_json, meta_json, leader_wallet, \
+    block_time, recv_time_ns, ix_index, decode_ok, decode_err, \
+    pre_balance_sol, post_balance_sol, balance_change_sol, role, wallet_roles";
+
+/// Bulk insert via `COPY ... FROM STDIN` into a staging table, then
+/// `INSERT ... ON CONFLICT DO NOTHING` into raw_events, all in one transaction.
+///
+/// If Postgres rejects the batch itself (bad value), falls back to the
+/// row-by-row path so one bad event can't block the rest. Returns rows inserted.
+#[cfg(feature = "postgres")]
+pub async fn copy_insert_raw_events(pool: &PgPool, events: &[RawEvent]) -> Result<usize> {
+    if events.is_empty() {
+        return Ok(0);
+    }
+
+    match copy_insert(pool, events).await {
+        Ok(count) => Ok(count),
+        Err(sqlx::Error::Database(e)) => {
+            warn!("COPY insert of {} events rejected ({}), falling back to row inserts", events.len(), e);
+            batch_insert_raw_events(pool, events).await
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(feature = "postgres")]
+async fn copy_insert(pool: &PgPool, events: &[RawEvent]) -> Result<usize, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(&format!(
+        "CREATE TEMP TABLE raw_events_staging ON COMMIT DROP AS \
+         SELECT {} FROM raw_events WITH NO DATA",
+        COPY_COLUMNS
+    ))
+    .execute(&mut *tx)
+    .await?;
+
+    let mut csv = String::with_capacity(events.len() * 512);
+    for event in events {
+        write_csv_row(&mut csv, event);
+    }
+
+    let mut copy = tx
+        .copy_in_raw(&format!(
+            "COPY raw_events_staging ({}) FROM STDIN WITH (FORMAT csv)",
+            COPY_COLUMNS
+        ))
+        .await?;
+    copy.send(csv.as_bytes()).await?;
+    copy.finish().await?;
+
+    let inserted = sqlx::query(&format!(
+        "INSERT INTO raw_events ({cols}) \
+         SELECT {cols} FROM raw_events_staging \
+         ON CONFLICT (sig, wallet, action) DO NOTHING",
+        cols = COPY_COLUMNS
+    ))
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    tx.commit().await?;
+    Ok(inserted as usize)
+}
+
+#[cfg(feature = "postgres")]
+fn write_csv_row(out: &mut String, e: &RawEvent) {
+    let fields: [Option<String>; 27] = [
+        Some(e.ts_ns.to_string()),
+        e.slot.map(|v| v.to_string()),
+        e.sig.clone(),
+        Some(e.wallet.clone()),
+        e.alias.clone(),
+        Some(e.program.clone()),
+        Some(e.action.clone()),
+        e.mint.clone(),
+        e.base_mint.clone(),
+        e.quote_mint.clone(),
+        e.amount_in.map(csv_f64),
+        e.amount_out.map(csv_f64),
+        e.price_est.map(csv_f64),
+        e.fee_sol.map(csv_f64),
+        e.ix_accounts_json.as_ref().map(|v| v.to_string()),
+        e.meta_json.as_ref().map(|v| v.to_string()),
+        e.leader_wallet.clone(),
+        e.block_time.map(|t| t.to_rfc3339()),
+        e.recv_time_ns.map(|v| v.to_string()),
+        e.ix_index.map(|v| v.to_string()),
+        Some(if e.decode_ok { "t" } else { "f" }.to_string()),
+        e.decode_err.clone(),
+        e.pre_balance_sol.map(csv_f64),
+        e.post_balance_sol.map(csv_f64),
+        e.balance_change_sol.map(csv_f64),
+        e.role.clone(),
+        e.wallet_roles.clone(),
+    ];
+
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        // Unquoted empty = NULL; every value is quoted so "" stays an empty string
+        if let Some(value) = field {
+            out.push('"');
+            out.push_str(&value.replace('"', "\"\""));
+            out.push('"');
+        }
+    }
+    out.push('\n');
+}
+
+#[cfg(feature = "postgres")]
+fn csv_f64(v: f64) -> String {
+    if v.is_nan() {
+        "NaN".to_string()
+    } else if v.is_infinite() {
+        if v > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else {
+        v.to_string()
+    }
+}
@@ -0,0 +1,400 @@
+//! Discovery wallet scoring - rank wallets by risk-adjusted realized performance
+//!
+//! Components come from each wallet's closed lots in `positions` (per-lot
+//! return = `realized_pnl / sol_spent`, in sell order) and its P&L days in
+//! `wallet_daily_stats`, and are stored on `wallet_stats` next to the
+//! `profit_score` a `ScoringModel` derives from them.
+
+use crate::patterns::percentile;
+use crate::{Connection, Db, Pool};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+
+/// Wallets rescored per transaction by `rescore_all`
+const RESCORE_BATCH: i64 = 5_000;
+
+/// Risk-adjusted performance of a wallet's closed lots
+#[derive(Debug, Clone, Copy, Default, PartialEq, sqlx::FromRow)]
+pub struct ScoreComponents {
+    /// Fully sold lots the components are computed from
+    pub closed_trades: i32,
+    /// Mean / standard deviation of per-lot returns
+    pub sharpe: f64,
+    /// Deepest fall of the cumulative per-lot return from its running peak
+    pub max_drawdown: f64,
+    pub median_roi: f64,
+    /// Share of P&L days that were profitable
+    pub consistency: f64,
+}
+
+impl ScoreComponents {
+    /// `returns` in sell order, `daily_pnl` one entry per day with realized P&L
+    pub fn compute(returns: &[f64], daily_pnl: &[f64]) -> Self {
+        let n = returns.len();
+        let mean = if n > 0 { returns.iter().sum::<f64>() / n as f64 } else { 0.0 };
+        let sharpe = if n >= 2 {
+            let var = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+            let std = var.sqrt();
+            if std > 1e-12 { mean / std } else { 0.0 }
+        } else {
+            0.0
+        };
+
+        let (mut cumulative, mut peak, mut max_drawdown) = (0.0f64, 0.0f64, 0.0f64);
+        for r in returns {
+            cumulative += r;
+            peak = peak.max(cumulative);
+            max_drawdown = max_drawdown.max(peak - cumulative);
+        }
+
+        let mut sorted = returns.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let median_roi = percentile(&sorted, 0.5).unwrap_or(0.0);
+
+        let consistency = if daily_pnl.is_empty() {
+            0.0
+        } else {
+            daily_pnl.iter().filter(|p| **p > 0.0).count() as f64 / daily_pnl.len() as f64
+        };
+
+        Self {
+            closed_trades: n as i32,
+            sharpe,
+            max_drawdown,
+            median_roi,
+            consistency,
+        }
+    }
+}
+
+/// What a model scores a wallet from
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScoreInputs {
+    pub components: ScoreComponents,
+    pub total_trades: i32,
+    pub net_pnl_sol: f64,
+    pub win_rate: f64,
+    /// Days since the wallet's last trade
+    pub days_idle: f64,
+}
+
+/// Turns a wallet's inputs into its `profit_score` (higher ranks first)
+pub trait ScoringModel: Debug + Send + Sync {
+    fn score(&self, inputs: &ScoreInputs) -> f64;
+}
+
+/// Weighted Sharpe-like ratio, median ROI, consistency and drawdown, shrunk
+/// towards zero for wallets with few closed lots and decayed while idle
+#[derive(Debug, Clone)]
+pub struct RiskAdjusted {
+    pub sharpe_weight: f64,
+    pub median_roi_weight: f64,
+    pub consistency_weight: f64,
+    pub drawdown_weight: f64,
+    /// Idle days after which the score is halved (0 disables the decay)
+    pub recency_half_life_days: f64,
+    /// Closed lots at which the score counts half (`n / (n + k)`)
+    pub confidence_trades: f64,
+}
+
+impl Default for RiskAdjusted {
+    fn default() -> Self {
+        Self {
+            sharpe_weight: 1.0,
+            median_roi_weight: 1.0,
+            consistency_weight: 1.0,
+            drawdown_weight: 0.5,
+            recency_half_life_days: 14.0,
+            confidence_trades: 10.0,
+        }
+    }
+}
+
+impl ScoringModel for RiskAdjusted {
+    fn score(&self, inputs: &ScoreInputs) -> f64 {
+        let c = &inputs.components;
+        if c.closed_trades == 0 {
+            return 0.0;
+        }
+        // Clamped so one 100x lot or a near-zero deviation can't dominate
+        let quality = self.sharpe_weight * c.sharpe.clamp(-5.0, 5.0)
+            + self.median_roi_weight * c.median_roi.clamp(-1.0, 5.0)
+            + self.consistency_weight * (2.0 * c.consistency - 1.0)
+            - self.drawdown_weight * c.max_drawdown.min(10.0);
+
+        let n = c.closed_trades as f64;
+        let confidence = n / (n + self.confidence_trades.max(0.0));
+        let recency = if self.recency_half_life_days > 0.0 {
+            0.5f64.powf(inputs.days_idle.max(0.0) / self.recency_half_life_days)
+        } else {
+            1.0
+        };
+        quality * confidence * recency
+    }
+}
+
+/// The original `(net_pnl_sol * win_rate * total_trades) / 100` ranking, zero
+/// below 10 trades
+#[derive(Debug, Clone, Default)]
+pub struct Legacy;
+
+impl ScoringModel for Legacy {
+    fn score(&self, inputs: &ScoreInputs) -> f64 {
+        if inputs.total_trades < 10 {
+            return 0.0;
+        }
+        inputs.net_pnl_sol * inputs.win_rate * inputs.total_trades as f64 / 100.0
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct StoredScore {
+    wallet: String,
+    total_trades: i32,
+    net_pnl_sol: f64,
+    win_rate: f64,
+    last_seen: DateTime<Utc>,
+    #[sqlx(flatten)]
+    components: ScoreComponents,
+}
+
+#[derive(sqlx::FromRow)]
+struct LotReturn {
+    wallet: String,
+    roi: f64,
+}
+
+#[derive(sqlx::FromRow)]
+struct DayPnl {
+    wallet: String,
+    daily_pnl: f64,
+}
+
+/// Run a query with a `{wallets}` filter for each of `wallets`
+pub(crate) async fn fetch_for_wallets<T>(conn: &mut Connection, sql: &str, wallets: &[String]) -> Result<Vec<T>>
+where
+    T: for<'r> sqlx::FromRow<'r, <Db as sqlx::Database>::Row> + Send + Unpin,
+{
+    #[cfg(feature = "postgres")]
+    return Ok(sqlx::query_as::<_, T>(&sql.replace("{wallets}", "wallet = ANY($1)"))
+        .bind(wallets)
+        .fetch_all(conn)
+        .await?);
+
+    // No array binds in SQLite
+    #[cfg(feature = "sqlite")]
+    {
+        let sql = sql.replace("{wallets}", "wallet = $1");
+        let mut rows = Vec::new();
+        for wallet in wallets {
+            rows.extend(sqlx::query_as::<_, T>(&sql).bind(wallet).fetch_all(&mut *conn).await?);
+        }
+        Ok(rows)
+    }
+}
+
+/// Rescore `wallets`: components are recomputed from positions for the ones in
+/// `recompute` (their realized P&L changed), the rest keep their stored ones
+pub(crate) async fn rescore(
+    conn: &mut Connection,
+    model: &dyn ScoringModel,
+    wallets: &[String],
+    recompute: &HashSet<&str>,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    if wallets.is_empty() {
+        return Ok(());
+    }
+    let stored: Vec<StoredScore> = fetch_for_wallets(
+        conn,
+        r#"SELECT wallet, total_trades, net_pnl_sol, win_rate, last_seen,
+                  closed_trades, sharpe, max_drawdown, median_roi, consistency
+           FROM wallet_stats WHERE {wallets}"#,
+        wallets,
+    )
+    .await?;
+
+    let changed: Vec<String> = wallets.iter().filter(|w| recompute.contains(w.as_str())).cloned().collect();
+    let mut returns: HashMap<String, Vec<f64>> = HashMap::new();
+    let mut days: HashMap<String, Vec<f64>> = HashMap::new();
+    if !changed.is_empty() {
+        let lots: Vec<LotReturn> = fetch_for_wallets(
+            conn,
+            r#"SELECT wallet, realized_pnl / sol_spent AS roi FROM positions
+               WHERE {wallets} AND is_closed AND sol_spent > 0
+               ORDER BY wallet, sold_at, bought_at"#,
+            &changed,
+        )
+        .await?;
+        for lot in lots {
+            returns.entry(lot.wallet).or_default().push(lot.roi);
+        }
+        let pnl_days: Vec<DayPnl> = fetch_for_wallets(
+            conn,
+            "SELECT wallet, daily_pnl FROM wallet_daily_stats WHERE {wallets} AND daily_pnl <> 0",
+            &changed,
+        )
+        .await?;
+        for day in pnl_days {
+            days.entry(day.wallet).or_default().push(day.daily_pnl);
+        }
+    }
+
+    let mut scored = Vec::with_capacity(stored.len());
+    for row in stored {
+        let components = if recompute.contains(row.wallet.as_str()) {
+            ScoreComponents::compute(
+                returns.get(&row.wallet).map(Vec::as_slice).unwrap_or_default(),
+                days.get(&row.wallet).map(Vec::as_slice).unwrap_or_default(),
+            )
+        } else {
+            row.components
+        };
+        let inputs = ScoreInputs {
+            components,
+            total_trades: row.total_trades,
+            net_pnl_sol: row.net_pnl_sol,
+            win_rate: row.win_rate,
+            days_idle: (now - row.last_seen).num_seconds() as f64 / 86_400.0,
+        };
+        scored.push((row.wallet, components, model.score(&inputs)));
+    }
+    write_scores(conn, &scored).await
+}
+
+#[cfg(feature = "postgres")]
+async fn write_scores(conn: &mut Connection, scored: &[(String, ScoreComponents, f64)]) -> Result<()> {
+    let wallets: Vec<&str> = scored.iter().map(|(w, _, _)| w.as_str()).collect();
+    let closed: Vec<i32> = scored.iter().map(|(_, c, _)| c.closed_trades).collect();
+    let sharpe: Vec<f64> = scored.iter().map(|(_, c, _)| c.sharpe).collect();
+    let drawdown: Vec<f64> = scored.iter().map(|(_, c, _)| c.max_drawdown).collect();
+    let median: Vec<f64> = scored.iter().map(|(_, c, _)| c.median_roi).collect();
+    let consistency: Vec<f64> = scored.iter().map(|(_, c, _)| c.consistency).collect();
+    let score: Vec<f64> = scored.iter().map(|(_, _, s)| *s).collect();
+
+    sqlx::query(
+        r#"
+        UPDATE wallet_stats w SET
+            closed_trades = s.closed_trades,
+            sharpe = s.sharpe,
+            max_drawdown = s.max_drawdown,
+            median_roi = s.median_roi,
+            consistency = s.consistency,
+            profit_score = s.profit_score
+        FROM UNNEST($1::text[], $2::int4[], $3::float8[], $4::float8[], $5::float8[], $6::float8[], $7::float8[])
+            AS s(wallet, closed_trades, sharpe, max_drawdown, median_roi, consistency, profit_score)
+        WHERE w.wallet = s.wallet
+        "#,
+    )
+    .bind(&wallets)
+    .bind(&closed)
+    .bind(&sharpe)
+    .bind(&drawdown)
+    .bind(&median)
+    .bind(&consistency)
+    .bind(&score)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+async fn write_scores(conn: &mut Connection, scored: &[(String, ScoreComponents, f64)]) -> Result<()> {
+    for (wallet, c, score) in scored {
+        sqlx::query(
+            r#"
+            UPDATE wallet_stats SET
+                closed_trades = $2, sharpe = $3, max_drawdown = $4,
+                median_roi = $5, consistency = $6, profit_score = $7
+            WHERE wallet = $1
+            "#,
+        )
+        .bind(wallet)
+        .bind(c.closed_trades)
+        .bind(c.sharpe)
+        .bind(c.max_drawdown)
+        .bind(c.median_roi)
+        .bind(c.consistency)
+        .bind(score)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Recompute components and scores of every wallet, e.g. after changing the
+/// model or its weights, or to let recency decay reach idle wallets
+pub async fn rescore_all(pool: &Pool, model: &dyn ScoringModel) -> Result<usize> {
+    let now = Utc::now();
+    let mut after = String::new();
+    let mut total = 0;
+    loop {
+        let wallets: Vec<String> = sqlx::query_scalar(
+            "SELECT wallet FROM wallet_stats WHERE wallet > $1 ORDER BY wallet LIMIT $2",
+        )
+        .bind(&after)
+        .bind(RESCORE_BATCH)
+        .fetch_all(pool)
+        .await?;
+        let Some(last) = wallets.last() else { break };
+        after = last.clone();
+
+        let all: HashSet<&str> = wallets.iter().map(String::as_str).collect();
+        let mut tx = pool.begin().await?;
+        rescore(&mut tx, model, &wallets, &all, now).await?;
+        tx.commit().await?;
+        total += wallets.len();
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(returns: &[f64], daily_pnl: &[f64], days_idle: f64) -> ScoreInputs {
+        ScoreInputs {
+            components: ScoreComponents::compute(returns, daily_pnl),
+            days_idle,
+            ..ScoreInputs::default()
+        }
+    }
+
+    #[test]
+    fn components_follow_the_sell_order() {
+        let c = ScoreComponents::compute(&[0.5, -1.0, -0.5, 2.0], &[1.0, -2.0, 0.5]);
+        assert_eq!(c.closed_trades, 4);
+        // Mean 0.25, sample variance 5.25 / 3
+        assert!((c.sharpe - 0.25 / 1.75f64.sqrt()).abs() < 1e-9);
+        // Peak 0.5 after the first lot, trough -1.0 after the third
+        assert!((c.max_drawdown - 1.5).abs() < 1e-9);
+        assert_eq!(c.median_roi, -0.5);
+        assert!((c.consistency - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn steady_wallet_outranks_a_lucky_one() {
+        let model = RiskAdjusted::default();
+        let steady = inputs(&[0.3, 0.2, 0.4, 0.25, 0.3, 0.35], &[0.5, 0.4, 0.6], 0.0);
+        let lucky = inputs(&[-1.0, -1.0, 9.0, -1.0, -1.0, -0.9], &[-2.0, 8.0, -1.9], 0.0);
+        assert!(model.score(&steady) > 0.0);
+        assert!(model.score(&steady) > model.score(&lucky));
+        // The legacy formula can't tell them apart without 10 trades
+        assert_eq!(Legacy.score(&steady), 0.0);
+    }
+
+    #[test]
+    fn score_grows_with_trades_and_halves_per_idle_half_life() {
+        let model = RiskAdjusted::default();
+        let few = inputs(&[0.3, 0.2], &[0.5], 0.0);
+        let many = inputs(&[0.3, 0.2].repeat(10), &[0.5], 0.0);
+        assert!(model.score(&many) > model.score(&few));
+        assert!(model.score(&few) > 0.0);
+
+        let idle = inputs(&[0.3, 0.2].repeat(10), &[0.5], 14.0);
+        assert!((model.score(&idle) - model.score(&many) / 2.0).abs() < 1e-9);
+        assert_eq!(model.score(&inputs(&[], &[], 0.0)), 0.0);
+    }
+}
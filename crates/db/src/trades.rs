@@ -0,0 +1,413 @@
+//! Trade stitcher - pair tracked wallets' BUYs and SELLs into FIFO lots
+//!
+//! Every BUY in `raw_events` opens a lot (one `trades` row, keyed by
+//! `(open_sig, wallet)`); SELLs consume the oldest open lots first, splitting
+//! across as many lots as it takes. A (wallet, mint) is always recomputed from
+//! its full event history, so re-running is idempotent. Traders recorded only
+//! because their mint is watched (not in `wallets`) aren't stitched.
+
+use crate::{watermarks, Pool};
+use anyhow::Result;
+use serde_json::json;
+use sqlx::Row;
+use std::collections::{HashSet, VecDeque};
+
+/// `job_watermarks` key for incremental runs
+const JOB: &str = "trade_stitcher";
+
+/// A lot with less than this fraction of its size left counts as fully sold
+const DUST: f64 = 1e-9;
+
+/// One BUY or SELL by the wallet itself
+#[derive(Debug, Clone)]
+pub struct Fill {
+    pub sig: String,
+    pub ts_ns: i64,
+    pub is_buy: bool,
+    /// Token amount (raw units, as decoded)
+    pub qty: f64,
+    /// SOL spent (BUY) or received (SELL)
+    pub sol: f64,
+}
+
+/// One BUY lot and whatever part of it has been sold so far
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub open_sig: String,
+    pub open_ts_ns: i64,
+    pub open_qty: f64,
+    pub open_cost_sol: f64,
+    /// Latest sell that took tokens from this lot
+    pub close_sig: Option<String>,
+    pub close_ts_ns: Option<i64>,
+    pub close_qty: f64,
+    pub close_proceeds_sol: f64,
+    /// Number of sells that took tokens from this lot
+    pub closes: u32,
+}
+
+impl Trade {
+    pub fn remaining_qty(&self) -> f64 {
+        (self.open_qty - self.close_qty).max(0.0)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.remaining_qty() <= self.open_qty * DUST
+    }
+
+    /// Cost basis of the part sold so far
+    pub fn closed_cost_sol(&self) -> f64 {
+        if self.open_qty > 0.0 {
+            self.open_cost_sol * self.close_qty / self.open_qty
+        } else {
+            0.0
+        }
+    }
+
+    /// Realized P&L on the part sold so far
+    pub fn pnl_sol(&self) -> Option<f64> {
+        (self.closes > 0).then(|| self.close_proceeds_sol - self.closed_cost_sol())
+    }
+
+    pub fn pnl_x(&self) -> Option<f64> {
+        let cost = self.closed_cost_sol();
+        (self.closes > 0 && cost > 0.0).then(|| self.close_proceeds_sol / cost)
+    }
+
+    /// Open to the latest (partial) close
+    pub fn hold_ms(&self) -> Option<i64> {
+        self.close_ts_ns.map(|close| (close - self.open_ts_ns) / 1_000_000)
+    }
+
+    /// Only decided once the whole lot is sold
+    pub fn was_win(&self) -> Option<bool> {
+        if self.is_closed() {
+            self.pnl_sol().map(|pnl| pnl > 0.0)
+        } else {
+            None
+        }
+    }
+
+    fn status(&self) -> &'static str {
+        match (self.closes, self.is_closed()) {
+            (0, _) => "open",
+            (_, true) => "closed",
+            _ => "partial",
+        }
+    }
+}
+
+/// Lots for one (wallet, mint)
+#[derive(Debug, Default, Clone)]
+pub struct Stitched {
+    pub trades: Vec<Trade>,
+    /// SELLs (or parts of them) with no open lot, e.g. tokens bought before tracking started
+    pub unmatched_sells: usize,
+    pub unmatched_qty: f64,
+}
+
+/// FIFO-match fills (already in chain order) into lots
+pub fn stitch(fills: &[Fill]) -> Stitched {
+    let mut out = Stitched::default();
+    // Lots with tokens left, oldest first (indices into out.trades)
+    let mut open: VecDeque<usize> = VecDeque::new();
+
+    for fill in fills.iter().filter(|f| f.qty > 0.0) {
+        if fill.is_buy {
+            open.push_back(out.trades.len());
+            out.trades.push(Trade {
+                open_sig: fill.sig.clone(),
+                open_ts_ns: fill.ts_ns,
+                open_qty: fill.qty,
+                open_cost_sol: fill.sol,
+                close_sig: None,
+                close_ts_ns: None,
+                close_qty: 0.0,
+                close_proceeds_sol: 0.0,
+                closes: 0,
+            });
+            continue;
+        }
+
+        let mut left = fill.qty;
+        while left > fill.qty * DUST {
+            let Some(&idx) = open.front() else {
+                break;
+            };
+            let lot = &mut out.trades[idx];
+            let take = left.min(lot.remaining_qty());
+            // Proceeds are split pro rata by tokens
+            lot.close_qty += take;
+            lot.close_proceeds_sol += fill.sol * take / fill.qty;
+            lot.close_sig = Some(fill.sig.clone());
+            lot.close_ts_ns = Some(fill.ts_ns);
+            lot.closes += 1;
+            left -= take;
+            if lot.is_closed() {
+                open.pop_front();
+            }
+        }
+        if left > fill.qty * DUST {
+            out.unmatched_sells += 1;
+            out.unmatched_qty += left;
+        }
+    }
+
+    out
+}
+
+/// Result of a stitcher run
+#[derive(Debug, Default, Clone, Copy)]
+pub struct StitchStats {
+    pub pairs: usize,
+    pub trades: usize,
+    pub closed: usize,
+    pub unmatched_sells: usize,
+    /// raw_events.id the run covered up to
+    pub last_event_id: i64,
+}
+
+/// Re-stitch every (wallet, mint) with events newer than the last run
+pub async fn stitch_new_events(pool: &Pool) -> Result<StitchStats> {
+    let since = watermarks::get(pool, JOB).await?;
+    let upto = watermarks::settled_event_id(pool, since).await?;
+    if upto <= since {
+        return Ok(StitchStats {
+            last_event_id: since,
+            ..StitchStats::default()
+        });
+    }
+
+    let pairs: Vec<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT DISTINCT wallet, mint FROM raw_events
+        WHERE id > $1 AND id <= $2 AND mint IS NOT NULL AND action IN ('BUY', 'SELL')
+          AND wallet IN (SELECT wallet FROM wallets)
+        "#
+    )
+    .bind(since)
+    .bind(upto)
+    .fetch_all(pool)
+    .await?;
+
+    let mut stats = stitch_pairs(pool, &pairs).await?;
+    watermarks::set(pool, JOB, upto).await?;
+    stats.last_event_id = upto;
+    Ok(stats)
+}
+
+/// Recompute the whole trades table from raw_events
+pub async fn rebuild(pool: &Pool) -> Result<StitchStats> {
+    let upto = watermarks::settled_event_id(pool, 0).await?;
+
+    // Lots whose events are gone entirely
+    sqlx::query(
+        r#"
+        DELETE FROM trades WHERE NOT EXISTS (
+            SELECT 1 FROM raw_events e
+            WHERE e.wallet = trades.wallet AND e.mint = trades.mint AND e.action IN ('BUY', 'SELL')
+        )
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    let pairs: Vec<(String, String)> = sqlx::query_as(
+        r#"
+        SELECT DISTINCT wallet, mint FROM raw_events
+        WHERE mint IS NOT NULL AND action IN ('BUY', 'SELL') AND wallet IN (SELECT wallet FROM wallets)
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut stats = stitch_pairs(pool, &pairs).await?;
+    watermarks::set(pool, JOB, upto).await?;
+    stats.last_event_id = upto;
+    Ok(stats)
+}
+
+async fn stitch_pairs(pool: &Pool, pairs: &[(String, String)]) -> Result<StitchStats> {
+    let mut stats = StitchStats {
+        pairs: pairs.len(),
+        ..StitchStats::default()
+    };
+
+    for (wallet, mint) in pairs {
+        let fills = load_fills(pool, wallet, mint).await?;
+        let stitched = stitch(&fills);
+        write_trades(pool, wallet, mint, &stitched).await?;
+
+        stats.trades += stitched.trades.len();
+        stats.closed += stitched.trades.iter().filter(|t| t.is_closed()).count();
+        stats.unmatched_sells += stitched.unmatched_sells;
+    }
+
+    Ok(stats)
+}
+
+/// The wallet's own BUY/SELL fills for a mint in chain order. Rows recorded
+/// before roles existed (role IS NULL) are trusted as the wallet's own.
+async fn load_fills(pool: &Pool, wallet: &str, mint: &str) -> Result<Vec<Fill>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT sig, ts_ns, action, amount_in, amount_out FROM raw_events
+        WHERE wallet = $1 AND mint = $2 AND action IN ('BUY', 'SELL') AND sig IS NOT NULL
+          AND (role IS NULL OR role = 'signer')
+        ORDER BY slot, ts_ns, id
+        "#
+    )
+    .bind(wallet)
+    .bind(mint)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Fill {
+            sig: row.get("sig"),
+            ts_ns: row.get("ts_ns"),
+            is_buy: row.get::<String, _>("action") == "BUY",
+            qty: row.get::<Option<f64>, _>("amount_in").unwrap_or(0.0),
+            sol: row.get::<Option<f64>, _>("amount_out").unwrap_or(0.0),
+        })
+        .collect())
+}
+
+/// Upsert the lots for one (wallet, mint) and drop rows for BUYs that no longer exist
+async fn write_trades(pool: &Pool, wallet: &str, mint: &str, stitched: &Stitched) -> Result<()> {
+    let mut tx = pool.begin().await?;
+
+    let existing: Vec<String> = sqlx::query_scalar("SELECT open_sig FROM trades WHERE wallet = $1 AND mint = $2")
+        .bind(wallet)
+        .bind(mint)
+        .fetch_all(&mut *tx)
+        .await?;
+    let keep: HashSet<&str> = stitched.trades.iter().map(|t| t.open_sig.as_str()).collect();
+    for open_sig in existing.iter().filter(|s| !keep.contains(s.as_str())) {
+        sqlx::query("DELETE FROM trades WHERE open_sig = $1 AND wallet = $2")
+            .bind(open_sig)
+            .bind(wallet)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    for trade in &stitched.trades {
+        let sold = trade.closes > 0;
+        sqlx::query(
+            r#"
+            INSERT INTO trades (
+                wallet, mint, open_sig, open_ts_ns, open_qty, open_cost_sol,
+                close_sig, close_ts_ns, close_qty, close_proceeds_sol,
+                hold_ms, pnl_sol, pnl_x, was_win, meta_json
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+            ON CONFLICT (open_sig, wallet) DO UPDATE SET
+                mint = EXCLUDED.mint,
+                open_ts_ns = EXCLUDED.open_ts_ns,
+                open_qty = EXCLUDED.open_qty,
+                open_cost_sol = EXCLUDED.open_cost_sol,
+                close_sig = EXCLUDED.close_sig,
+                close_ts_ns = EXCLUDED.close_ts_ns,
+                close_qty = EXCLUDED.close_qty,
+                close_proceeds_sol = EXCLUDED.close_proceeds_sol,
+                hold_ms = EXCLUDED.hold_ms,
+                pnl_sol = EXCLUDED.pnl_sol,
+                pnl_x = EXCLUDED.pnl_x,
+                was_win = EXCLUDED.was_win,
+                meta_json = EXCLUDED.meta_json
+            "#
+        )
+        .bind(wallet)
+        .bind(mint)
+        .bind(&trade.open_sig)
+        .bind(trade.open_ts_ns)
+        .bind(trade.open_qty)
+        .bind(trade.open_cost_sol)
+        .bind(&trade.close_sig)
+        .bind(trade.close_ts_ns)
+        .bind(sold.then_some(trade.close_qty))
+        .bind(sold.then_some(trade.close_proceeds_sol))
+        .bind(trade.hold_ms())
+        .bind(trade.pnl_sol())
+        .bind(trade.pnl_x())
+        .bind(trade.was_win())
+        .bind(json!({
+            "status": trade.status(),
+            "remaining_qty": trade.remaining_qty(),
+            "closes": trade.closes,
+        }))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buy(sig: &str, ts_ns: i64, qty: f64, sol: f64) -> Fill {
+        Fill { sig: sig.to_string(), ts_ns, is_buy: true, qty, sol }
+    }
+
+    fn sell(sig: &str, ts_ns: i64, qty: f64, sol: f64) -> Fill {
+        Fill { sig: sig.to_string(), ts_ns, is_buy: false, qty, sol }
+    }
+
+    #[test]
+    fn scaling_out_closes_one_lot_over_several_sells() {
+        let stitched = stitch(&[
+            buy("b1", 0, 1000.0, 1.0),
+            sell("s1", 1_000_000_000, 400.0, 0.8),
+            sell("s2", 3_000_000_000, 600.0, 0.3),
+        ]);
+
+        let lot = &stitched.trades[0];
+        assert!(lot.is_closed());
+        assert_eq!(lot.closes, 2);
+        assert_eq!(lot.close_sig.as_deref(), Some("s2"));
+        assert!((lot.pnl_sol().unwrap() - 0.1).abs() < 1e-12);
+        assert!((lot.pnl_x().unwrap() - 1.1).abs() < 1e-12);
+        assert_eq!(lot.hold_ms(), Some(3000));
+        assert_eq!(lot.was_win(), Some(true));
+        assert_eq!(stitched.unmatched_sells, 0);
+    }
+
+    #[test]
+    fn one_sell_spans_lots_oldest_first() {
+        let stitched = stitch(&[
+            buy("b1", 0, 100.0, 1.0),
+            buy("b2", 1, 100.0, 2.0),
+            sell("s1", 2, 150.0, 3.0),
+        ]);
+
+        let (first, second) = (&stitched.trades[0], &stitched.trades[1]);
+        assert!(first.is_closed());
+        assert!((first.close_proceeds_sol - 2.0).abs() < 1e-12);
+        assert_eq!(first.was_win(), Some(true));
+
+        // Half of the second lot sold at cost: realized 0, not decided yet
+        assert!(!second.is_closed());
+        assert!((second.remaining_qty() - 50.0).abs() < 1e-12);
+        assert!(second.pnl_sol().unwrap().abs() < 1e-12);
+        assert_eq!(second.was_win(), None);
+    }
+
+    #[test]
+    fn sells_without_a_buy_are_reported_not_matched() {
+        let stitched = stitch(&[
+            sell("s0", 0, 500.0, 0.5),
+            buy("b1", 1, 100.0, 1.0),
+            sell("s1", 2, 300.0, 0.6),
+        ]);
+
+        assert_eq!(stitched.trades.len(), 1);
+        assert!(stitched.trades[0].is_closed());
+        // s1 paid 0.6 for 300 tokens; the lot only had 100 of them
+        assert!((stitched.trades[0].close_proceeds_sol - 0.2).abs() < 1e-12);
+        assert_eq!(stitched.unmatched_sells, 2);
+        assert!((stitched.unmatched_qty - 700.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,295 @@
+//! Managing the `wallets` and `wallet_roles` tables
+//!
+//! Every write validates the address first, so a typo can't end up as a
+//! tracked "wallet" the stream never matches.
+
+use crate::Pool;
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+
+/// Check that `wallet` is a base58 Solana public key (32 bytes)
+pub fn validate_pubkey(wallet: &str) -> Result<()> {
+    let bytes = bs58::decode(wallet)
+        .into_vec()
+        .with_context(|| format!("'{}' is not base58", wallet))?;
+    if bytes.len() != 32 {
+        bail!("'{}' is {} bytes, not a 32-byte public key", wallet, bytes.len());
+    }
+    Ok(())
+}
+
+/// One row of `wallets` with its roles
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalletEntry {
+    pub wallet: String,
+    pub alias: Option<String>,
+    pub is_tracked: bool,
+    pub auto_promoted: bool,
+    pub notes: Option<String>,
+    pub roles: Vec<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct WalletRow {
+    wallet: String,
+    alias: Option<String>,
+    is_tracked: bool,
+    auto_promoted: bool,
+    notes: Option<String>,
+}
+
+/// Add or update a wallet and start tracking it. Alias and notes are only
+/// changed when given. The wallet counts as hand-added from now on, so the
+/// promotion job leaves it alone.
+pub async fn add(pool: &Pool, wallet: &str, alias: Option<&str>, notes: Option<&str>) -> Result<()> {
+    validate_pubkey(wallet)?;
+    sqlx::query(
+        r#"
+        INSERT INTO wallets (wallet, alias, is_tracked, notes) VALUES ($1, $2, TRUE, $3)
+        ON CONFLICT (wallet) DO UPDATE SET
+            alias = COALESCE(excluded.alias, wallets.alias),
+            notes = COALESCE(excluded.notes, wallets.notes),
+            is_tracked = TRUE,
+            auto_promoted = FALSE
+        "#,
+    )
+    .bind(wallet)
+    .bind(alias)
+    .bind(notes)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Delete a wallet and its roles. Fails for wallets with recorded history
+/// (events, trades, ...); untrack those instead.
+pub async fn remove(pool: &Pool, wallet: &str) -> Result<bool> {
+    // raw_events has no foreign key (watchlist traders aren't in `wallets`)
+    let has_events: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM raw_events WHERE wallet = $1)")
+        .bind(wallet)
+        .fetch_one(pool)
+        .await?;
+    if has_events {
+        bail!("{} has recorded history; untrack it instead", wallet);
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM wallet_roles WHERE wallet = $1")
+        .bind(wallet)
+        .execute(&mut *tx)
+        .await?;
+    let removed = sqlx::query("DELETE FROM wallets WHERE wallet = $1")
+        .bind(wallet)
+        .execute(&mut *tx)
+        .await
+        .with_context(|| format!("{} has recorded history; untrack it instead", wallet))?
+        .rows_affected();
+    tx.commit().await?;
+    Ok(removed > 0)
+}
+
+/// Returns false if the wallet doesn't exist
+pub async fn set_alias(pool: &Pool, wallet: &str, alias: Option<&str>) -> Result<bool> {
+    let updated = sqlx::query("UPDATE wallets SET alias = $2 WHERE wallet = $1")
+        .bind(wallet)
+        .bind(alias)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    Ok(updated > 0)
+}
+
+/// Returns false if the wallet doesn't exist
+pub async fn set_tracked(pool: &Pool, wallet: &str, tracked: bool) -> Result<bool> {
+    let updated = sqlx::query("UPDATE wallets SET is_tracked = $2 WHERE wallet = $1")
+        .bind(wallet)
+        .bind(tracked)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    Ok(updated > 0)
+}
+
+pub async fn add_roles(pool: &Pool, wallet: &str, roles: &[String]) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    for role in roles {
+        sqlx::query("INSERT INTO wallet_roles (wallet, role) VALUES ($1, $2) ON CONFLICT (wallet, role) DO NOTHING")
+            .bind(wallet)
+            .bind(role)
+            .execute(&mut *tx)
+            .await
+            .with_context(|| format!("{} is not in wallets", wallet))?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn remove_roles(pool: &Pool, wallet: &str, roles: &[String]) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    for role in roles {
+        sqlx::query("DELETE FROM wallet_roles WHERE wallet = $1 AND role = $2")
+            .bind(wallet)
+            .bind(role)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Wallets ordered by alias, then address; only tracked ones unless `all`
+pub async fn list(pool: &Pool, all: bool) -> Result<Vec<WalletEntry>> {
+    let rows: Vec<WalletRow> = sqlx::query_as(
+        r#"
+        SELECT wallet, alias, is_tracked, auto_promoted, notes FROM wallets
+        WHERE is_tracked OR $1
+        ORDER BY alias IS NULL, alias, wallet
+        "#,
+    )
+    .bind(all)
+    .fetch_all(pool)
+    .await?;
+    let role_rows: Vec<(String, String)> = sqlx::query_as("SELECT wallet, role FROM wallet_roles ORDER BY role")
+        .fetch_all(pool)
+        .await?;
+    let mut roles: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (wallet, role) in role_rows {
+        roles.entry(wallet).or_default().push(role);
+    }
+
+    Ok(rows
+        .into_iter()
+        .map(|row| WalletEntry {
+            roles: roles.remove(&row.wallet).unwrap_or_default(),
+            wallet: row.wallet,
+            alias: row.alias,
+            is_tracked: row.is_tracked,
+            auto_promoted: row.auto_promoted,
+            notes: row.notes,
+        })
+        .collect())
+}
+
+/// A wallet to import, with the roles to give it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    pub wallet: String,
+    pub alias: Option<String>,
+    pub roles: Vec<String>,
+}
+
+/// Result of an import
+#[derive(Debug, Default, Clone)]
+pub struct ImportStats {
+    pub wallets: usize,
+    pub roles: usize,
+    /// Rows skipped for an invalid address, with the reason
+    pub rejected: Vec<String>,
+}
+
+/// Upsert `rows` as tracked wallets (like `add`) plus their roles, in one
+/// transaction. Rows with an invalid address are skipped and reported.
+pub async fn import(pool: &Pool, rows: &[ImportRow]) -> Result<ImportStats> {
+    let mut stats = ImportStats::default();
+    let mut tx = pool.begin().await?;
+    for row in rows {
+        if let Err(e) = validate_pubkey(&row.wallet) {
+            stats.rejected.push(e.to_string());
+            continue;
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO wallets (wallet, alias, is_tracked) VALUES ($1, $2, TRUE)
+            ON CONFLICT (wallet) DO UPDATE SET
+                alias = COALESCE(excluded.alias, wallets.alias),
+                is_tracked = TRUE,
+                auto_promoted = FALSE
+            "#,
+        )
+        .bind(&row.wallet)
+        .bind(&row.alias)
+        .execute(&mut *tx)
+        .await?;
+        stats.wallets += 1;
+
+        for role in &row.roles {
+            stats.roles += sqlx::query(
+                "INSERT INTO wallet_roles (wallet, role) VALUES ($1, $2) ON CONFLICT (wallet, role) DO NOTHING",
+            )
+            .bind(&row.wallet)
+            .bind(role)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() as usize;
+        }
+    }
+    tx.commit().await?;
+    Ok(stats)
+}
+
+/// Parse `wallet[,alias[,role;role...]]` lines. Blank lines, `#` comments and
+/// a `wallet,...` header are skipped; fields are not quoted.
+pub fn parse_csv(text: &str) -> Vec<ImportRow> {
+    let mut rows = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split(',').map(str::trim);
+        let wallet = fields.next().unwrap_or_default();
+        if wallet.eq_ignore_ascii_case("wallet") {
+            continue;
+        }
+        let alias = fields.next().filter(|a| !a.is_empty()).map(str::to_string);
+        let roles = fields
+            .next()
+            .map(|r| r.split(';').map(str::trim).filter(|r| !r.is_empty()).map(str::to_string).collect())
+            .unwrap_or_default();
+        rows.push(ImportRow {
+            wallet: wallet.to_string(),
+            alias,
+            roles,
+        });
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pubkeys_must_be_32_base58_bytes() {
+        assert!(validate_pubkey("6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P").is_ok());
+        assert!(validate_pubkey("11111111111111111111111111111111").is_ok());
+        // 0, O, I and l aren't in the alphabet
+        assert!(validate_pubkey("0EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P").is_err());
+        assert!(validate_pubkey("6EF8rrecthR5Dkzon8Nwu78").is_err());
+        assert!(validate_pubkey("").is_err());
+    }
+
+    #[test]
+    fn csv_rows_with_optional_alias_and_roles() {
+        let rows = parse_csv(
+            "wallet,alias,roles\n\
+             # liquidity wallets\n\
+             AAA, lk ,liquidity; admin\n\
+             \n\
+             BBB,,\n\
+             CCC\n",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                ImportRow {
+                    wallet: "AAA".into(),
+                    alias: Some("lk".into()),
+                    roles: vec!["liquidity".into(), "admin".into()],
+                },
+                ImportRow { wallet: "BBB".into(), alias: None, roles: vec![] },
+                ImportRow { wallet: "CCC".into(), alias: None, roles: vec![] },
+            ]
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! Managing the `watched_mints` table
+//!
+//! The ingestor records every trade on a watched mint, tracked trader or not.
+//! Rows come from the CLI (`source = 'cli'`) or are synced from the config
+//! (`source = 'config'`) at ingestor startup.
+
+use crate::wallets::validate_pubkey;
+use crate::Pool;
+use anyhow::Result;
+
+/// One row of `watched_mints`
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct WatchedMint {
+    pub mint: String,
+    pub note: Option<String>,
+    pub source: String,
+}
+
+/// Watch a mint; the note is only changed when given
+pub async fn add(pool: &Pool, mint: &str, note: Option<&str>) -> Result<()> {
+    validate_pubkey(mint)?;
+    sqlx::query(
+        r#"
+        INSERT INTO watched_mints (mint, note, source) VALUES ($1, $2, 'cli')
+        ON CONFLICT (mint) DO UPDATE SET note = COALESCE(excluded.note, watched_mints.note)
+        "#,
+    )
+    .bind(mint)
+    .bind(note)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Returns false if the mint wasn't watched
+pub async fn remove(pool: &Pool, mint: &str) -> Result<bool> {
+    let removed = sqlx::query("DELETE FROM watched_mints WHERE mint = $1")
+        .bind(mint)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    Ok(removed > 0)
+}
+
+pub async fn list(pool: &Pool) -> Result<Vec<WatchedMint>> {
+    Ok(sqlx::query_as("SELECT mint, note, source FROM watched_mints ORDER BY added_at, mint")
+        .fetch_all(pool)
+        .await?)
+}
+
+/// Make the config-sourced rows exactly `mints`: add the new ones, drop the
+/// ones no longer configured. Mints added from the CLI are left alone.
+/// Returns (added, removed).
+pub async fn sync_config(pool: &Pool, mints: &[String]) -> Result<(usize, usize)> {
+    for mint in mints {
+        validate_pubkey(mint)?;
+    }
+
+    let mut tx = pool.begin().await?;
+    let configured: Vec<String> = sqlx::query_scalar("SELECT mint FROM watched_mints WHERE source = 'config'")
+        .fetch_all(&mut *tx)
+        .await?;
+
+    let mut removed = 0;
+    for mint in configured.iter().filter(|m| !mints.contains(m)) {
+        removed += sqlx::query("DELETE FROM watched_mints WHERE mint = $1")
+            .bind(mint)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected() as usize;
+    }
+    let mut added = 0;
+    for mint in mints {
+        added += sqlx::query(
+            "INSERT INTO watched_mints (mint, source) VALUES ($1, 'config') ON CONFLICT (mint) DO NOTHING",
+        )
+        .bind(mint)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected() as usize;
+    }
+    tx.commit().await?;
+    Ok((added, removed))
+}
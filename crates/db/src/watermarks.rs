@@ -0,0 +1,66 @@
+//! Per-job progress through raw_events (`job_watermarks`)
+
+use crate::Pool;
+use anyhow::Result;
+
+/// Last raw_events.id the job has processed (0 if it never ran)
+pub async fn get(pool: &Pool, job: &str) -> Result<i64> {
+    let id: Option<i64> = sqlx::query_scalar("SELECT last_event_id FROM job_watermarks WHERE job = $1")
+        .bind(job)
+        .fetch_optional(pool)
+        .await?;
+    Ok(id.unwrap_or(0))
+}
+
+pub async fn set(pool: &Pool, job: &str, last_event_id: i64) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO job_watermarks (job, last_event_id) VALUES ($1, $2)
+        ON CONFLICT (job) DO UPDATE SET
+            last_event_id = EXCLUDED.last_event_id,
+            updated_at = CURRENT_TIMESTAMP
+        "#
+    )
+    .bind(job)
+    .bind(last_event_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Highest raw_events.id a job can move its watermark past `since` to.
+///
+/// Ids come from a sequence, so a batch still being inserted can commit
+/// after a later one: the rows after the first gap above `since` are only
+/// read once the gap is a minute old. An older gap is a rolled-back batch
+/// or ids burnt by `ON CONFLICT DO NOTHING`, and is passed over.
+#[cfg(feature = "postgres")]
+pub async fn settled_event_id(pool: &Pool, since: i64) -> Result<i64> {
+    let gap: Option<i64> = sqlx::query_scalar(
+        r#"
+        SELECT MIN(e.id) FROM raw_events e
+        WHERE e.id > $1 + 1 AND e.created_at > NOW() - INTERVAL '1 minute'
+          AND NOT EXISTS (SELECT 1 FROM raw_events p WHERE p.id = e.id - 1)
+        "#
+    )
+    .bind(since)
+    .fetch_one(pool)
+    .await?;
+
+    let id: Option<i64> = sqlx::query_scalar("SELECT MAX(id) FROM raw_events WHERE id > $1 AND id < $2")
+        .bind(since)
+        .bind(gap.unwrap_or(i64::MAX))
+        .fetch_one(pool)
+        .await?;
+    Ok(id.unwrap_or(since))
+}
+
+/// Highest raw_events.id a job can move its watermark past `since` to. SQLite
+/// has one writer at a time, so ids become visible in order.
+#[cfg(feature = "sqlite")]
+pub async fn settled_event_id(pool: &Pool, since: i64) -> Result<i64> {
+    let id: Option<i64> = sqlx::query_scalar("SELECT MAX(id) FROM raw_events")
+        .fetch_one(pool)
+        .await?;
+    Ok(id.unwrap_or(since))
+}
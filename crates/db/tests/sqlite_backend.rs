@@ -0,0 +1,782 @@
+//! End-to-end checks of the SQLite backend against the embedded migrations
+//!
+//!     cargo test -p db --features sqlite
+
+#![cfg(feature = "sqlite")]
+
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use db::classify::WalletClass;
+use db::clusters::FundingEdge;
+use db::creators::CreatorTracker;
+use db::discovery::{get_top_wallets, rebuild_daily_stats, DiscoveryAggregator};
+use db::funding::{self, TraceOptions};
+use db::migrations::{ensure_schema, MigrationMode, Schema};
+use db::mint_features::{self, MintFeatureAggregator};
+use db::mints::{BalanceChange, MintActivity, MintFill, PUMP_TOKEN_SUPPLY};
+use db::raw_events::{bulk_insert_raw_events, RawEvent};
+use db::scoring::{self, Legacy};
+use db::promotion::{self, PromotionRules};
+use db::wallets::{self, ImportRow};
+use db::watchlist;
+use db::{follow, patterns, trades};
+use sqlx::Row;
+use std::collections::{HashMap, HashSet};
+
+const WALLET: &str = "Trader1111111111111111111111111111111111111";
+const MINT: &str = "Mint11111111111111111111111111111111111111";
+/// A valid address (WALLET isn't one), for code that validates
+const WALLET_PUBKEY: &str = "7xKXtg2CW87d97TXJSDpbD5jBkheTqA83TZRuJosgAsU";
+
+async fn empty_db() -> db::Pool {
+    // One connection so every query sees the same in-memory database
+    sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap()
+}
+
+async fn memory_db() -> db::Pool {
+    let pool = empty_db().await;
+    ensure_schema(&pool, Schema::Main, MigrationMode::Apply).await.unwrap();
+    ensure_schema(&pool, Schema::Discovery, MigrationMode::Apply).await.unwrap();
+    pool
+}
+
+fn event(sig: &str, action: &str) -> RawEvent {
+    RawEvent {
+        ts_ns: 1,
+        slot: Some(1),
+        sig: Some(sig.to_string()),
+        wallet: WALLET.to_string(),
+        alias: Some("trader".to_string()),
+        program: "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P".to_string(),
+        action: action.to_string(),
+        mint: Some(MINT.to_string()),
+        base_mint: None,
+        quote_mint: None,
+        amount_in: Some(1000.0),
+        amount_out: Some(0.5),
+        price_est: None,
+        fee_sol: Some(0.000005),
+        ix_accounts_json: Some(serde_json::json!({ "wallet": WALLET })),
+        meta_json: None,
+        leader_wallet: None,
+        block_time: Some(Utc::now()),
+        recv_time_ns: Some(1),
+        ix_index: None,
+        decode_ok: true,
+        decode_err: None,
+        pre_balance_sol: Some(10.0),
+        post_balance_sol: Some(9.5),
+        balance_change_sol: Some(-0.5),
+        role: Some("signer".to_string()),
+        wallet_roles: None,
+    }
+}
+
+#[tokio::test]
+async fn check_mode_refuses_unmigrated_schema() {
+    let pool = empty_db().await;
+    assert!(ensure_schema(&pool, Schema::Main, MigrationMode::Check).await.is_err());
+
+    ensure_schema(&pool, Schema::Main, MigrationMode::Apply).await.unwrap();
+    ensure_schema(&pool, Schema::Main, MigrationMode::Check).await.unwrap();
+    // Sharing the file doesn't make the discovery schema look migrated
+    assert!(ensure_schema(&pool, Schema::Discovery, MigrationMode::Check).await.is_err());
+}
+
+#[tokio::test]
+async fn check_mode_refuses_newer_schema() {
+    let pool = memory_db().await;
+    sqlx::query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time) \
+         VALUES (999, 'from the future', TRUE, X'00', 0)",
+    )
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    let err = ensure_schema(&pool, Schema::Main, MigrationMode::Check).await.unwrap_err();
+    assert!(err.to_string().contains("upgrade the binary"), "{}", err);
+}
+
+#[tokio::test]
+async fn raw_events_insert_is_idempotent() {
+    let pool = memory_db().await;
+    sqlx::query("INSERT INTO wallets (wallet, alias) VALUES ($1, 'trader')")
+        .bind(WALLET)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let batch = vec![event("sig1", "BUY"), event("sig2", "SELL")];
+    assert_eq!(bulk_insert_raw_events(&pool, &batch).await.unwrap(), 2);
+    assert_eq!(bulk_insert_raw_events(&pool, &batch).await.unwrap(), 0);
+
+    let row = sqlx::query("SELECT ix_accounts_json, role FROM raw_events WHERE sig = 'sig1'")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let json: serde_json::Value = row.get("ix_accounts_json");
+    assert_eq!(json["wallet"], WALLET);
+    assert_eq!(row.get::<String, _>("role"), "signer");
+}
+
+#[tokio::test]
+async fn discovery_flush_closes_positions_fifo() {
+    let pool = memory_db().await;
+    let t0 = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+
+    let mut aggregator = DiscoveryAggregator::new();
+    aggregator.record(WALLET, "BUY", Some(1.0), Some(1000.0), Some(MINT), t0);
+    aggregator.record(WALLET, "BUY", Some(2.0), Some(1000.0), Some(MINT), t0 + Duration::seconds(1));
+    let stats = aggregator.flush(&pool).await.unwrap();
+    assert_eq!((stats.new_wallets, stats.positions_opened), (1, 2));
+
+    // 1500 tokens: closes the 1 SOL lot (+2.0) and half of the 2 SOL lot (+0.5)
+    aggregator.record(WALLET, "SELL", Some(4.5), Some(1500.0), Some(MINT), t0 + Duration::seconds(2));
+    let stats = aggregator.flush(&pool).await.unwrap();
+    assert_eq!((stats.new_wallets, stats.positions_closed), (0, 1));
+    assert!(aggregator.is_empty());
+
+    let wallet_row = |pool| {
+        sqlx::query(
+            "SELECT total_trades, total_sol_in, total_sol_out, net_pnl_sol, realized_wins, \
+             realized_losses, win_rate, unmatched_sells, unmatched_sol_out \
+             FROM wallet_stats WHERE wallet = $1",
+        )
+        .bind(WALLET)
+        .fetch_one(pool)
+    };
+    let row = wallet_row(&pool).await.unwrap();
+    assert_eq!(row.get::<i32, _>("total_trades"), 3);
+    assert_eq!(row.get::<f64, _>("total_sol_in"), 3.0);
+    assert_eq!(row.get::<f64, _>("total_sol_out"), 4.5);
+    assert_eq!(row.get::<f64, _>("net_pnl_sol"), 2.5);
+    assert_eq!(row.get::<i32, _>("realized_wins"), 1);
+    assert_eq!(row.get::<f64, _>("win_rate"), 1.0);
+
+    let (remaining, avg_price): (f64, f64) =
+        sqlx::query_as("SELECT tokens_remaining, avg_buy_price FROM positions WHERE NOT is_closed")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!((remaining, avg_price), (500.0, 0.002));
+
+    // Sells the last 500 tokens at a loss that wipes the lot's gain, plus 500
+    // the wallet held before it was seen
+    aggregator.record(WALLET, "SELL", Some(1.0), Some(1000.0), Some(MINT), t0 + Duration::seconds(3));
+    let stats = aggregator.flush(&pool).await.unwrap();
+    assert_eq!((stats.positions_closed, stats.unmatched_sells), (1, 1));
+
+    let row = wallet_row(&pool).await.unwrap();
+    assert_eq!(row.get::<f64, _>("net_pnl_sol"), 2.0);
+    assert_eq!(row.get::<i32, _>("realized_losses"), 1);
+    assert_eq!(row.get::<f64, _>("win_rate"), 0.5);
+    assert_eq!(row.get::<i32, _>("unmatched_sells"), 1);
+    assert_eq!(row.get::<f64, _>("unmatched_sol_out"), 0.5);
+
+    // Below the 10-trade threshold, so not ranked yet
+    assert!(get_top_wallets(&pool, 10, None).await.unwrap().is_empty());
+
+    // Lot returns in sell order: +200%, then 0% once the second lot is gone
+    let (closed, sharpe, drawdown, median, consistency, score): (i32, f64, f64, f64, f64, f64) =
+        sqlx::query_as(
+            "SELECT closed_trades, sharpe, max_drawdown, median_roi, consistency, profit_score \
+             FROM wallet_stats WHERE wallet = $1",
+        )
+        .bind(WALLET)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!((closed, drawdown, median, consistency), (2, 0.0, 0.0, 1.0));
+    assert!((sharpe - 0.5f64.sqrt()).abs() < 1e-9);
+    assert!(score > 0.0);
+
+    assert_eq!(scoring::rescore_all(&pool, &Legacy).await.unwrap(), 1);
+    let score: f64 = sqlx::query_scalar("SELECT profit_score FROM wallet_stats WHERE wallet = $1")
+        .bind(WALLET)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(score, 0.0);
+
+    let daily = |pool| {
+        sqlx::query_as::<_, (NaiveDate, i32, i32, i32, f64, f64, f64)>(
+            "SELECT date, trades, buys, sells, sol_in, sol_out, daily_pnl FROM wallet_daily_stats WHERE wallet = $1",
+        )
+        .bind(WALLET)
+        .fetch_all(pool)
+    };
+    let day = t0.date_naive();
+    assert_eq!(daily(&pool).await.unwrap(), vec![(day, 4, 2, 2, 3.0, 5.5, 2.0)]);
+
+    // Rebuilt from positions: two lots bought, both counted as sold once (the
+    // unmatched 0.5 SOL has no lot)
+    let stats = rebuild_daily_stats(&pool, None).await.unwrap();
+    assert_eq!(stats.days, 1);
+    assert_eq!(daily(&pool).await.unwrap(), vec![(day, 4, 2, 2, 3.0, 5.0, 2.0)]);
+}
+
+#[tokio::test]
+async fn snipers_are_classified_and_left_out_of_copyable_rankings() {
+    let pool = memory_db().await;
+    let t0 = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+    let mut aggregator = DiscoveryAggregator::new();
+    let mut trade = |wallet: &str, action: &str, mint: &str, slot: u64, tx_index: u64| {
+        let at = t0 + Duration::seconds(slot as i64);
+        aggregator.record(wallet, action, Some(1.0), Some(1000.0), Some(mint), at);
+        aggregator.record_slot(wallet, action, mint, slot, tx_index);
+    };
+
+    // The sniper buys every mint in its CREATE slot, the trader a few slots later
+    for i in 0..5u64 {
+        let mint = format!("Mint{}", i);
+        let slot = 100 + i * 10;
+        trade("dev", "CREATE", &mint, slot, 0);
+        trade("sniper", "BUY", &mint, slot, 1);
+        trade("trader", "BUY", &mint, slot + 3, 0);
+        trade("sniper", "SELL", &mint, slot + 5, 0);
+        trade("trader", "SELL", &mint, slot + 6, 0);
+    }
+    aggregator.settle_slots();
+    let stats = aggregator.flush(&pool).await.unwrap();
+    assert_eq!(stats.signalled_wallets, 1);
+
+    let (class, snipes): (String, i32) =
+        sqlx::query_as("SELECT wallet_class, sniper_buys FROM wallet_stats WHERE wallet = 'sniper'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!((class.as_str(), snipes), ("sniper", 5));
+
+    let ranked = |classes| get_top_wallets(&pool, 10, classes);
+    assert_eq!(ranked(None).await.unwrap().len(), 2);
+    let copyable = ranked(Some(WalletClass::COPYABLE)).await.unwrap();
+    assert_eq!(copyable.len(), 1);
+    assert_eq!((copyable[0].wallet.as_str(), copyable[0].wallet_class.as_str()), ("trader", "manual"));
+}
+
+#[tokio::test]
+async fn co_funded_bundle_buyers_rank_as_one_wallet() {
+    let pool = memory_db().await;
+    let t0 = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+    let mut aggregator = DiscoveryAggregator::new();
+
+    // Three sybils and an unrelated wallet buy together every time
+    for i in 0..5u64 {
+        let mint = format!("Mint{}", i);
+        let slot = 100 + i * 10;
+        for wallet in ["sybil1", "sybil2", "sybil3", "organic"] {
+            aggregator.record(wallet, "BUY", Some(1.0), Some(1000.0), Some(&mint), t0 + Duration::seconds(slot as i64));
+            aggregator.record_slot(wallet, "BUY", &mint, slot, 0);
+            aggregator.record(wallet, "SELL", Some(2.0), Some(1000.0), Some(&mint), t0 + Duration::seconds(slot as i64 + 5));
+            aggregator.record_slot(wallet, "SELL", &mint, slot + 5, 0);
+        }
+    }
+    // One funder behind two of them, the third funded by one of those
+    aggregator.record_funding("sybil1", "Boss", 1_000_000_000, t0);
+    aggregator.record_funding("sybil2", "Boss", 1_000_000_000, t0);
+    aggregator.record_funding("sybil3", "sybil2", 500_000_000, t0);
+    aggregator.settle_slots();
+    assert_eq!(aggregator.flush(&pool).await.unwrap().clustered_wallets, 3);
+
+    let clusters: Vec<(String, String, Option<String>)> =
+        sqlx::query_as("SELECT wallet, cluster_id, funder FROM wallet_clusters ORDER BY wallet")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(clusters.len(), 3);
+    assert!(clusters.iter().all(|(_, id, funder)| id == "sybil1" && funder.as_deref() == Some("Boss")));
+
+    let ranked: Vec<String> = get_top_wallets(&pool, 10, None).await.unwrap().into_iter().map(|w| w.wallet).collect();
+    assert_eq!(ranked.len(), 2);
+    assert!(ranked.contains(&"organic".to_string()));
+}
+
+#[tokio::test]
+async fn settled_bundles_flush_without_pending_trades() {
+    let pool = memory_db().await;
+    let t0 = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+    let mut aggregator = DiscoveryAggregator::new();
+    aggregator.record_slot("sybil1", "BUY", MINT, 100, 0);
+    aggregator.record_slot("sybil2", "BUY", MINT, 100, 1);
+    aggregator.record_funding("sybil1", "Boss", 1_000_000_000, t0);
+    aggregator.record_funding("sybil2", "Boss", 1_000_000_000, t0);
+
+    // As at shutdown: the last slots settle with no fills waiting
+    aggregator.settle_slots();
+    assert!(aggregator.is_empty() && aggregator.has_pending());
+    assert_eq!(aggregator.flush(&pool).await.unwrap().clustered_wallets, 2);
+    assert!(!aggregator.has_pending());
+}
+
+#[tokio::test]
+async fn funding_trace_links_alts_and_caches_what_it_fetched() {
+    let pool = memory_db().await;
+    let now = Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap();
+    let tracked: HashSet<String> = HashSet::from(["Main".to_string()]);
+    let options = TraceOptions::default();
+    // wallet -> (funder, lamports) as the RPC would report them
+    let transfers: HashMap<&str, Vec<(&str, u64)>> = HashMap::from([
+        ("Fresh", vec![("Hop", 2_000_000_000), ("Noise", 1_000)]),
+        ("Hop", vec![("Main", 5_000_000_000)]),
+        ("Alt", vec![("Sybil", 1_000_000_000)]),
+        ("Mate", vec![("Sybil", 1_000_000_000)]),
+    ]);
+    let mut fetched = Vec::new();
+    let mut fetch = |wallet: String| {
+        fetched.push(wallet.clone());
+        let edges: Vec<FundingEdge> = transfers
+            .get(wallet.as_str())
+            .into_iter()
+            .flatten()
+            .map(|(funder, lamports)| FundingEdge {
+                wallet: wallet.clone(),
+                funder: funder.to_string(),
+                lamports: *lamports,
+                seen_at: now,
+            })
+            .collect();
+        async move { Ok::<_, anyhow::Error>(edges) }
+    };
+
+    let link = funding::trace(&pool, &tracked, "Fresh", &options, now, &mut fetch).await.unwrap().unwrap();
+    assert_eq!((link.linked_to.as_str(), link.kind.as_str(), link.hops), ("Main", "tracked", 2));
+    assert_eq!(link.path, "Hop,Main");
+    // Cached, so the second trace asks the RPC for nothing
+    let again = funding::trace(&pool, &tracked, "Fresh", &options, now, &mut fetch).await.unwrap();
+    assert_eq!(again.as_ref(), Some(&link));
+    // An ignored funder isn't walked through
+    let ignoring = TraceOptions { ignore: HashSet::from(["Hop".to_string()]), ..TraceOptions::default() };
+    assert_eq!(funding::trace(&pool, &tracked, "Fresh", &ignoring, now, &mut fetch).await.unwrap(), None);
+
+    // A cluster member counts too, unless it's in the wallet's own cluster
+    sqlx::query(
+        r#"
+        INSERT INTO wallet_clusters (wallet, cluster_id, funder, mint, slot, linked_at) VALUES
+            ('Sybil', 'Sybil', NULL, $1, 1, $2),
+            ('Mate', 'Sybil', 'Sybil', $1, 1, $2)
+        "#,
+    )
+    .bind(MINT)
+    .bind(now)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let alt = funding::trace(&pool, &tracked, "Alt", &options, now, &mut fetch).await.unwrap().unwrap();
+    assert_eq!(funding::trace(&pool, &tracked, "Mate", &options, now, &mut fetch).await.unwrap(), None);
+    assert_eq!(fetched, vec!["Fresh", "Hop", "Noise", "Alt", "Mate"]);
+
+    funding::save_link(&pool, &link).await.unwrap();
+    funding::save_link(&pool, &alt).await.unwrap();
+    let links = funding::links(&pool).await.unwrap();
+    let aliases = HashMap::from([("Main".to_string(), "whale".to_string())]);
+    assert_eq!(links["Fresh"].describe(&aliases), "alt of whale (2 hops)");
+    assert_eq!(links["Alt"].describe(&aliases), "alt of cluster Sybil (1 hop)");
+
+    let sources: Vec<String> = sqlx::query_scalar("SELECT DISTINCT source FROM wallet_funding").fetch_all(&pool).await.unwrap();
+    assert_eq!(sources, vec!["rpc".to_string()]);
+}
+
+#[tokio::test]
+async fn promotion_tracks_good_wallets_and_demotes_decayed_ones() {
+    let pool = memory_db().await;
+    let now = Utc::now();
+    let discovered = |wallet: &'static str, score: f64, class: &'static str| {
+        sqlx::query(
+            "INSERT INTO wallet_stats (wallet, first_seen, last_seen, total_trades, profit_score, wallet_class) \
+             VALUES ($1, $2, $2, 30, $3, $4)",
+        )
+        .bind(wallet)
+        .bind(now - Duration::days(1))
+        .bind(score)
+        .bind(class)
+    };
+    discovered("GoodTrader1111111111", 1.0, "manual").execute(&pool).await.unwrap();
+    discovered("SniperBot11111111111", 2.0, "sniper").execute(&pool).await.unwrap();
+    discovered("HandPicked1111111111", 3.0, "manual").execute(&pool).await.unwrap();
+    // Deliberately untracked by hand; promotion must not bring it back
+    sqlx::query("INSERT INTO wallets (wallet, is_tracked) VALUES ('HandPicked1111111111', FALSE)")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let rules = PromotionRules::default();
+    let stats = promotion::run(&pool, &pool, &rules, now).await.unwrap();
+    assert_eq!((stats.promoted, stats.demoted), (1, 0));
+    let (alias, tracked, auto): (String, bool, bool) =
+        sqlx::query_as("SELECT alias, is_tracked, auto_promoted FROM wallets WHERE wallet = 'GoodTrader1111111111'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!((alias.as_str(), tracked, auto), ("auto_GoodTrad", true, true));
+    let tracked_in_discovery: Vec<String> =
+        sqlx::query_scalar("SELECT wallet FROM wallet_stats WHERE is_tracked AND added_to_tracking_at IS NOT NULL")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(tracked_in_discovery, vec!["GoodTrader1111111111".to_string()]);
+
+    // Nothing changes on a second pass
+    let stats = promotion::run(&pool, &pool, &rules, now).await.unwrap();
+    assert_eq!((stats.promoted, stats.demoted), (0, 0));
+
+    // Its score decays below the demotion bar, then recovers
+    let set_score = |score: f64| {
+        sqlx::query("UPDATE wallet_stats SET profit_score = $1 WHERE wallet = 'GoodTrader1111111111'").bind(score)
+    };
+    set_score(-0.2).execute(&pool).await.unwrap();
+    let stats = promotion::run(&pool, &pool, &rules, now).await.unwrap();
+    assert_eq!((stats.promoted, stats.demoted), (0, 1));
+    let tracked: bool = sqlx::query_scalar("SELECT is_tracked FROM wallets WHERE wallet = 'GoodTrader1111111111'")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert!(!tracked);
+
+    set_score(0.8).execute(&pool).await.unwrap();
+    let stats = promotion::run(&pool, &pool, &rules, now).await.unwrap();
+    assert_eq!((stats.promoted, stats.demoted), (1, 0));
+
+    let audit: Vec<(String, String)> =
+        sqlx::query_as("SELECT wallet, action FROM wallet_promotions ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    let good = "GoodTrader1111111111".to_string();
+    assert_eq!(
+        audit,
+        vec![
+            (good.clone(), "promote".to_string()),
+            (good.clone(), "demote".to_string()),
+            (good, "promote".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn wallet_management_validates_and_keeps_history() {
+    let pool = memory_db().await;
+    const LK: &str = "11111111111111111111111111111112";
+
+    assert!(wallets::add(&pool, "not-a-wallet", None, None).await.is_err());
+    wallets::add(&pool, WALLET_PUBKEY, Some("trader"), None).await.unwrap();
+    wallets::add_roles(&pool, WALLET_PUBKEY, &["admin".to_string()]).await.unwrap();
+    assert!(wallets::set_alias(&pool, WALLET_PUBKEY, Some("renamed")).await.unwrap());
+    assert!(!wallets::set_tracked(&pool, LK, false).await.unwrap());
+
+    let stats = wallets::import(
+        &pool,
+        &[
+            ImportRow { wallet: LK.to_string(), alias: Some("lk".to_string()), roles: vec!["liquidity".to_string()] },
+            // Re-importing keeps the alias when the source has none
+            ImportRow { wallet: WALLET_PUBKEY.to_string(), alias: None, roles: vec!["admin".to_string()] },
+            ImportRow { wallet: "typo".to_string(), alias: None, roles: vec![] },
+        ],
+    )
+    .await
+    .unwrap();
+    assert_eq!((stats.wallets, stats.roles, stats.rejected.len()), (2, 1, 1));
+
+    assert!(wallets::set_tracked(&pool, LK, false).await.unwrap());
+    let tracked = wallets::list(&pool, false).await.unwrap();
+    assert_eq!(tracked.len(), 1);
+    assert_eq!(tracked[0].alias.as_deref(), Some("renamed"));
+    assert_eq!(tracked[0].roles, vec!["admin".to_string()]);
+    let all = wallets::list(&pool, true).await.unwrap();
+    assert_eq!((all[0].wallet.as_str(), all[0].roles.clone()), (LK, vec!["liquidity".to_string()]));
+
+    // A wallet with recorded events can only be untracked
+    bulk_insert_raw_events(&pool, &[RawEvent { wallet: WALLET_PUBKEY.to_string(), ..event("sig1", "BUY") }])
+        .await
+        .unwrap();
+    assert!(wallets::remove(&pool, WALLET_PUBKEY).await.is_err());
+    assert_eq!(wallets::list(&pool, true).await.unwrap().len(), 2);
+    assert!(wallets::remove(&pool, LK).await.unwrap());
+    assert_eq!(wallets::list(&pool, true).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn stitcher_writes_fifo_trades_idempotently() {
+    let pool = memory_db().await;
+    sqlx::query("INSERT INTO wallets (wallet) VALUES ($1)")
+        .bind(WALLET)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let fill = |sig: &str, slot: i64, action: &str, tokens: f64, sol: f64| RawEvent {
+        slot: Some(slot),
+        ts_ns: slot * 400_000_000,
+        amount_in: Some(tokens),
+        amount_out: Some(sol),
+        ..event(sig, action)
+    };
+    let mut referenced = fill("b2", 2, "BUY", 500.0, 9.0);
+    referenced.role = Some("referenced".to_string());
+    bulk_insert_raw_events(&pool, &[fill("b1", 1, "BUY", 1000.0, 1.0), referenced, fill("s1", 3, "SELL", 400.0, 0.8)])
+        .await
+        .unwrap();
+
+    let stats = trades::stitch_new_events(&pool).await.unwrap();
+    assert_eq!((stats.pairs, stats.trades, stats.closed), (1, 1, 0));
+
+    // New events only re-stitch what changed; the lot is updated in place
+    bulk_insert_raw_events(&pool, &[fill("s2", 4, "SELL", 600.0, 0.3)]).await.unwrap();
+    let stats = trades::stitch_new_events(&pool).await.unwrap();
+    assert_eq!((stats.pairs, stats.closed), (1, 1));
+    assert_eq!(trades::stitch_new_events(&pool).await.unwrap().pairs, 0);
+    trades::rebuild(&pool).await.unwrap();
+
+    let rows = sqlx::query("SELECT open_sig, close_sig, close_qty, pnl_sol, hold_ms, was_win FROM trades")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].get::<String, _>("open_sig"), "b1");
+    assert_eq!(rows[0].get::<String, _>("close_sig"), "s2");
+    assert_eq!(rows[0].get::<f64, _>("close_qty"), 1000.0);
+    assert!((rows[0].get::<f64, _>("pnl_sol") - 0.1).abs() < 1e-9);
+    assert_eq!(rows[0].get::<i64, _>("hold_ms"), 1200);
+    assert!(rows[0].get::<bool, _>("was_win"));
+}
+
+#[tokio::test]
+async fn analyzer_upserts_wallet_patterns_from_trades() {
+    let pool = memory_db().await;
+    sqlx::query("INSERT INTO wallets (wallet) VALUES ($1)")
+        .bind(WALLET)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let now_ns = Utc::now().timestamp_nanos_opt().unwrap();
+    let fill = |sig: &str, secs_ago: i64, action: &str, sol: f64, limit_lamports: u64| RawEvent {
+        ts_ns: now_ns - secs_ago * 1_000_000_000,
+        slot: Some(now_ns / 400_000_000 - secs_ago),
+        amount_out: Some(sol),
+        meta_json: Some(serde_json::json!({ "sol_limit_lamports": limit_lamports })),
+        ..event(sig, action)
+    };
+    // Bought 1000 tokens for 1 SOL (10% slippage allowed), sold them for 2 SOL a minute later
+    bulk_insert_raw_events(&pool, &[fill("b1", 120, "BUY", 1.0, 1_100_000_000), fill("s1", 60, "SELL", 2.0, 1_800_000_000)])
+        .await
+        .unwrap();
+    trades::stitch_new_events(&pool).await.unwrap();
+
+    let stats = patterns::analyze_tracked_wallets(&pool, 14).await.unwrap();
+    assert_eq!((stats.wallets, stats.patterns, stats.trades), (1, 1, 1));
+    patterns::analyze_tracked_wallets(&pool, 14).await.unwrap();
+
+    let row = sqlx::query(
+        "SELECT sample_trades, win_rate, p50_hold_ms, p50_target_x, typical_exit_style, slippage_pctl_95 \
+         FROM wallet_patterns WHERE wallet = $1",
+    )
+    .bind(WALLET)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(row.get::<i32, _>("sample_trades"), 1);
+    assert_eq!(row.get::<f64, _>("win_rate"), 1.0);
+    assert_eq!(row.get::<i64, _>("p50_hold_ms"), 60_000);
+    assert_eq!(row.get::<f64, _>("p50_target_x"), 2.0);
+    assert_eq!(row.get::<String, _>("typical_exit_style"), "single_exit");
+    assert!((row.get::<f64, _>("slippage_pctl_95") - 0.1).abs() < 1e-9);
+}
+
+#[tokio::test]
+async fn follow_edges_backfill_leader_wallet() {
+    let pool = memory_db().await;
+    const LEADER: &str = "Leader111111111111111111111111111111111111";
+    for wallet in [WALLET, LEADER] {
+        sqlx::query("INSERT INTO wallets (wallet) VALUES ($1)")
+            .bind(wallet)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+
+    // The trader buys two mints a slot and 500ms after the leader
+    let now_ns = Utc::now().timestamp_nanos_opt().unwrap();
+    let mut buys = Vec::new();
+    for (i, mint) in ["MintA", "MintB"].into_iter().enumerate() {
+        let slot = 1_000 + i as i64 * 100;
+        let ts_ns = now_ns - 60_000_000_000 + i as i64 * 10_000_000_000;
+        let base = RawEvent { mint: Some(mint.to_string()), amount_out: Some(2.0), ..event("", "BUY") };
+        buys.push(RawEvent {
+            sig: Some(format!("lead{}", i)),
+            wallet: LEADER.to_string(),
+            slot: Some(slot),
+            ts_ns,
+            ..base.clone()
+        });
+        buys.push(RawEvent {
+            sig: Some(format!("follow{}", i)),
+            slot: Some(slot + 1),
+            ts_ns: ts_ns + 500_000_000,
+            ..base
+        });
+    }
+    bulk_insert_raw_events(&pool, &buys).await.unwrap();
+
+    let stats = follow::detect_follow_edges(&pool, 14, 10_000, 2, &[]).await.unwrap();
+    assert_eq!((stats.entries, stats.edges, stats.leaders_backfilled), (4, 1, 2));
+    // Idempotent
+    follow::detect_follow_edges(&pool, 14, 10_000, 2, &[]).await.unwrap();
+
+    let (leader, follower, lag, prob, size): (String, String, i64, f64, f64) = sqlx::query_as(
+        "SELECT leader, follower, lag_ms_p50, follow_prob, avg_follower_size_sol FROM follow_edges",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!((leader.as_str(), follower.as_str()), (LEADER, WALLET));
+    assert_eq!((lag, prob, size), (500, 1.0, 2.0));
+
+    let credited: Vec<Option<String>> =
+        sqlx::query_scalar("SELECT leader_wallet FROM raw_events ORDER BY sig")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    let leader = Some(LEADER.to_string());
+    assert_eq!(credited, vec![leader.clone(), leader, None, None]);
+
+    let scores: (f64, f64) = sqlx::query_as(
+        "SELECT followership_score, leader_score FROM wallet_patterns WHERE wallet = $1",
+    )
+    .bind(WALLET)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(scores, (1.0, 0.0));
+    // A liquidity wallet leads nobody once its role is excluded
+    wallets::add_roles(&pool, LEADER, &["liquidity".to_string()]).await.unwrap();
+    let stats = follow::detect_follow_edges(&pool, 14, 10_000, 2, &["liquidity".to_string()]).await.unwrap();
+    assert_eq!((stats.entries, stats.edges, stats.leaders_backfilled), (2, 0, 0));
+}
+
+#[tokio::test]
+async fn events_keep_the_wallet_roles_they_were_recorded_with() {
+    let pool = memory_db().await;
+    sqlx::query("INSERT INTO wallets (wallet) VALUES ($1)")
+        .bind(WALLET)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let labelled = RawEvent { wallet_roles: Some("admin,liquidity".to_string()), ..event("sig1", "BUY") };
+    bulk_insert_raw_events(&pool, &[labelled, event("sig2", "SELL")]).await.unwrap();
+
+    let roles: Vec<Option<String>> = sqlx::query_scalar("SELECT wallet_roles FROM raw_events ORDER BY sig")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(roles, vec![Some("admin,liquidity".to_string()), None]);
+}
+
+#[tokio::test]
+async fn watchlist_sync_keeps_cli_mints_and_records_untracked_traders() {
+    let pool = memory_db().await;
+    const CONFIG_MINT: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
+
+    watchlist::add(&pool, WALLET_PUBKEY, Some("holding")).await.unwrap();
+    assert!(watchlist::add(&pool, "not-a-mint", None).await.is_err());
+    assert_eq!(watchlist::sync_config(&pool, &[CONFIG_MINT.to_string()]).await.unwrap(), (1, 0));
+    assert_eq!(watchlist::sync_config(&pool, &[CONFIG_MINT.to_string()]).await.unwrap(), (0, 0));
+    let sources: Vec<(String, String)> =
+        watchlist::list(&pool).await.unwrap().into_iter().map(|w| (w.mint, w.source)).collect();
+    assert_eq!(sources.len(), 2);
+
+    // Dropped from the config: only the config row goes
+    assert_eq!(watchlist::sync_config(&pool, &[]).await.unwrap(), (0, 1));
+    let left = watchlist::list(&pool).await.unwrap();
+    assert_eq!(left.len(), 1);
+    assert_eq!((left[0].mint.as_str(), left[0].note.as_deref()), (WALLET_PUBKEY, Some("holding")));
+
+    // A watched mint's traders aren't in `wallets`, and aren't stitched
+    assert_eq!(bulk_insert_raw_events(&pool, &[event("sig1", "BUY")]).await.unwrap(), 1);
+    assert_eq!(trades::rebuild(&pool).await.unwrap().pairs, 0);
+}
+
+#[tokio::test]
+async fn mint_creators_survive_a_restart_within_the_window() {
+    let pool = memory_db().await;
+    let created = Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap();
+    let activity = |sig: &str, creator: Option<&str>, sold: u64, hours: i64| MintActivity {
+        mint: MINT.to_string(),
+        sig: sig.to_string(),
+        program: "pumpfun".to_string(),
+        slot: 1,
+        seen_at: created + Duration::hours(hours),
+        creator: creator.map(str::to_string),
+        fills: vec![MintFill { wallet: WALLET.to_string(), is_buy: false, tokens: sold, sol: None }],
+        balances: vec![],
+    };
+
+    let mut tracker = CreatorTracker::new(0.05, Duration::hours(24));
+    assert_eq!(tracker.record(&activity("create", Some(WALLET), 0, 0)), None);
+    assert_eq!(tracker.record(&activity("sell1", None, PUMP_TOKEN_SUPPLY / 50, 1)), None);
+    assert_eq!(tracker.flush(&pool, created + Duration::hours(1)).await.unwrap(), 1);
+
+    // Restarted: the earlier 2% counts toward the threshold
+    let mut restarted = CreatorTracker::new(0.05, Duration::hours(24));
+    assert_eq!(restarted.load(&pool, created + Duration::hours(2)).await.unwrap(), 1);
+    let dump = restarted.record(&activity("sell2", None, PUMP_TOKEN_SUPPLY / 25, 2)).unwrap();
+    assert!((dump.supply_fraction - 0.06).abs() < 1e-12);
+    restarted.flush(&pool, created + Duration::hours(2)).await.unwrap();
+
+    let (sold, dump_sig): (i64, Option<String>) =
+        sqlx::query_as("SELECT tokens_sold, dump_sig FROM mint_creators WHERE mint = $1")
+            .bind(MINT)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!((sold, dump_sig.as_deref()), ((PUMP_TOKEN_SUPPLY / 50 * 3) as i64, Some("sell2")));
+
+    // Past the window it's neither loaded nor kept in memory
+    assert_eq!(CreatorTracker::new(0.05, Duration::hours(24)).load(&pool, created + Duration::hours(25)).await.unwrap(), 0);
+    restarted.flush(&pool, created + Duration::hours(25)).await.unwrap();
+    assert!(restarted.is_empty());
+}
+
+#[tokio::test]
+async fn mint_features_pick_up_holders_after_a_restart() {
+    let pool = memory_db().await;
+    let created = Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap();
+    let percent = PUMP_TOKEN_SUPPLY / 100;
+    let buy = |wallet: &str, slot: u64, creator: Option<&str>| MintActivity {
+        mint: MINT.to_string(),
+        sig: format!("{}-{}", wallet, slot),
+        program: "pumpfun".to_string(),
+        slot,
+        seen_at: created + Duration::seconds(slot as i64 - 100),
+        creator: creator.map(str::to_string),
+        fills: vec![MintFill { wallet: wallet.to_string(), is_buy: true, tokens: percent, sol: None }],
+        balances: vec![BalanceChange { owner: wallet.to_string(), pre: 0, post: percent }],
+    };
+
+    let mut features = MintFeatureAggregator::new(Duration::hours(24));
+    features.record(&buy(WALLET, 100, Some(WALLET)));
+    assert_eq!(features.flush(&pool, created).await.unwrap(), 1);
+
+    // Restarted: the creator's holding still counts
+    let mut restarted = MintFeatureAggregator::new(Duration::hours(24));
+    assert_eq!(restarted.load(&pool, created + Duration::hours(1)).await.unwrap(), 1);
+    restarted.record(&buy("Buyer2", 110, None));
+    restarted.flush(&pool, created + Duration::hours(1)).await.unwrap();
+
+    let stored = mint_features::get(&pool, MINT).await.unwrap().unwrap();
+    assert_eq!(stored.unique_buyers, 2);
+    assert!((stored.top10_share - 0.02).abs() < 1e-12);
+    assert!((stored.creator_share - 0.01).abs() < 1e-12);
+    assert!((stored.creation_slot_share - 0.01).abs() < 1e-12);
+    assert_eq!(stored.secs_to_first_sell, None);
+
+    // Past the window the holders go, the features stay
+    restarted.flush(&pool, created + Duration::hours(25)).await.unwrap();
+    assert!(restarted.is_empty());
+    let holders: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM mint_holders").fetch_one(&pool).await.unwrap();
+    assert_eq!(holders, 0);
+    assert!(mint_features::get(&pool, MINT).await.unwrap().is_some());
+}
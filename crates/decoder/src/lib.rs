@@ -31,6 +31,7 @@ impl Action {
 pub struct DecodedInstruction {
     pub action: Action,
     pub mint: Option<String>,
+    pub user: Option<String>,      // Signer the instruction acts for (the trader)
     pub token_amount: Option<u64>,
     pub max_sol_cost: Option<u64>, // For BUY: max SOL to spend; For SELL: min SOL to receive
     pub decode_ok: bool,
@@ -47,13 +48,71 @@ const DISCRIMINATOR_BUY: [u8; 8] = [0x66, 0x06, 0x3d, 0x12, 0x01, 0xda, 0xeb, 0x
 const DISCRIMINATOR_SELL: [u8; 8] = [0x33, 0xe6, 0x85, 0xa4, 0x01, 0x7f, 0x83, 0xad];
 const DISCRIMINATOR_WITHDRAW: [u8; 8] = [0xb7, 0x12, 0x46, 0x9c, 0x94, 0x6d, 0xa1, 0x22];
 
+/// Anchor `emit_cpi!` events are self-invoked inner instructions whose data
+/// starts with this tag, then the event discriminator
+const EVENT_IX_TAG: [u8; 8] = [0xe4, 0x45, 0xa5, 0x2e, 0x51, 0xcb, 0x9a, 0x1d];
+/// SHA256("event:TradeEvent")[0..8]
+const DISCRIMINATOR_TRADE_EVENT: [u8; 8] = [0xbd, 0xdb, 0x7f, 0xd3, 0x4e, 0xe6, 0x61, 0xee];
+
+/// The TradeEvent Pump.fun emits after every BUY/SELL, with the bonding
+/// curve's reserves after the trade
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TradeEvent {
+    pub mint: String,
+    pub sol_amount: u64,
+    pub token_amount: u64,
+    pub is_buy: bool,
+    pub user: String,
+    pub timestamp: i64,
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+}
+
+/// Decode a TradeEvent from an inner instruction's data; `None` for anything else.
+/// Newer program versions append fields, which are ignored.
+pub fn decode_trade_event(data: &[u8]) -> Option<TradeEvent> {
+    if data.len() < 121 || data[0..8] != EVENT_IX_TAG || data[8..16] != DISCRIMINATOR_TRADE_EVENT {
+        return None;
+    }
+    let body = &data[16..];
+    let u64_at = |at: usize| u64::from_le_bytes(body[at..at + 8].try_into().unwrap_or([0u8; 8]));
+
+    Some(TradeEvent {
+        mint: bs58::encode(&body[0..32]).into_string(),
+        sol_amount: u64_at(32),
+        token_amount: u64_at(40),
+        is_buy: body[48] != 0,
+        user: bs58::encode(&body[49..81]).into_string(),
+        timestamp: u64_at(81) as i64,
+        virtual_sol_reserves: u64_at(89),
+        virtual_token_reserves: u64_at(97),
+    })
+}
+
+pub const SYSTEM_PROGRAM_ID: &str = "11111111111111111111111111111111";
+/// System Program instruction index of `Transfer` (u32 LE, then lamports as u64 LE)
+const SYSTEM_IX_TRANSFER: u32 = 2;
+
+/// Lamports moved by a System Program `Transfer` (accounts: from, to); `None`
+/// for any other System instruction
+pub fn decode_system_transfer(data: &[u8]) -> Option<u64> {
+    if data.len() < 12 || data[0..4] != SYSTEM_IX_TRANSFER.to_le_bytes() {
+        return None;
+    }
+    Some(u64::from_le_bytes(data[4..12].try_into().ok()?))
+}
+
 /// Decode a Pump.fun instruction by discriminator
+///
+/// `accounts` are the instruction's own accounts (resolved from its account
+/// indices), in IDL order - not the transaction's full account key list.
 pub fn decode_instruction(data: &[u8], accounts: &[String]) -> Result<DecodedInstruction> {
     if data.len() < 8 {
         let err_msg = format!("Instruction data too short: {} bytes (expected at least 8)", data.len());
         return Ok(DecodedInstruction {
             action: Action::Unknown,
             mint: None,
+            user: None,
             token_amount: None,
             max_sol_cost: None,
             decode_ok: false,
@@ -96,11 +155,22 @@ pub fn decode_instruction(data: &[u8], accounts: &[String]) -> Result<DecodedIns
     // - INITIALIZE: no mint (global state creation)
     // - SET_PARAMS: no mint (global parameter update)
     let mint = match action {
-        Action::Create => accounts.get(0).cloned(),
+        Action::Create => accounts.first().cloned(),
         Action::Buy | Action::Sell | Action::Withdraw => accounts.get(2).cloned(),
         Action::Initialize | Action::SetParams | Action::Unknown => None,
     };
 
+    // Extract the `user` signer (the wallet actually trading):
+    // - CREATE: user is at index 7
+    // - BUY/SELL/WITHDRAW: user is at index 6
+    // - INITIALIZE/SET_PARAMS: user is at index 1
+    let user = match action {
+        Action::Create => accounts.get(7).cloned(),
+        Action::Buy | Action::Sell | Action::Withdraw => accounts.get(6).cloned(),
+        Action::Initialize | Action::SetParams => accounts.get(1).cloned(),
+        Action::Unknown => None,
+    };
+
     // Parse instruction data:
     // For BUY/SELL: [discriminator (8)] + [token_amount (8)] + [max_sol_cost (8)]
     let token_amount = if data.len() >= 16 {
@@ -134,9 +204,55 @@ pub fn decode_instruction(data: &[u8], accounts: &[String]) -> Result<DecodedIns
     Ok(DecodedInstruction {
         action,
         mint,
+        user,
         token_amount,
         max_sol_cost,
         decode_ok,
         decode_err,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trade_event_carries_reserves_after_the_trade() {
+        let mint = [1u8; 32];
+        let user = [2u8; 32];
+        let mut data = EVENT_IX_TAG.to_vec();
+        data.extend_from_slice(&DISCRIMINATOR_TRADE_EVENT);
+        data.extend_from_slice(&mint);
+        data.extend_from_slice(&500_000_000u64.to_le_bytes());
+        data.extend_from_slice(&17_000_000_000u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(&user);
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes());
+        data.extend_from_slice(&30_500_000_000u64.to_le_bytes());
+        data.extend_from_slice(&1_056_000_000_000_000u64.to_le_bytes());
+
+        let event = decode_trade_event(&data).unwrap();
+        assert_eq!(event.mint, bs58::encode(mint).into_string());
+        assert_eq!(event.user, bs58::encode(user).into_string());
+        assert_eq!((event.sol_amount, event.token_amount, event.is_buy), (500_000_000, 17_000_000_000, true));
+        assert_eq!(event.timestamp, 1_700_000_000);
+        assert_eq!((event.virtual_sol_reserves, event.virtual_token_reserves), (30_500_000_000, 1_056_000_000_000_000));
+
+        // A BUY instruction is not an event
+        assert_eq!(decode_trade_event(&DISCRIMINATOR_BUY), None);
+        assert_eq!(decode_trade_event(&data[..100]), None);
+    }
+
+    #[test]
+    fn system_transfer_lamports() {
+        let mut data = 2u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&1_500_000_000u64.to_le_bytes());
+        assert_eq!(decode_system_transfer(&data), Some(1_500_000_000));
+
+        // CreateAccount (0) and truncated data
+        let mut create = 0u32.to_le_bytes().to_vec();
+        create.extend_from_slice(&[0u8; 44]);
+        assert_eq!(decode_system_transfer(&create), None);
+        assert_eq!(decode_system_transfer(&data[..8]), None);
+    }
+}
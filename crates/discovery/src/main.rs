@@ -0,0 +1,196 @@
+//! Maintenance commands for the discovery database
+//!
+//!   cargo run -p discovery -- rebuild-daily-stats   # recompute wallet_daily_stats
+//!   cargo run -p discovery -- rescore               # recompute every profit_score
+//!   cargo run -p discovery -- reclassify            # recompute every wallet_class
+//!   cargo run -p discovery -- promote               # move wallets in and out of tracking
+//!   cargo run -p discovery -- trace-funding [limit] # link top wallets to tracked ones by funding (RPC)
+//!   cargo run -p discovery -- top [limit]           # top wallets, with their alt-of links
+
+use anyhow::{bail, Context, Result};
+use common::config::{ClassificationConfig, Config, FundingTraceConfig, PromotionConfig, ScoringConfig, ScoringModelKind};
+use common::logging;
+use common::rpc::RpcClient;
+use db::classify::{self, ClassThresholds};
+use db::clusters::FundingEdge;
+use db::discovery;
+use db::funding::{self, TraceOptions};
+use db::migrations::{MigrationMode, Schema};
+use db::promotion::{self, PromotionRules};
+use db::scoring::{self, Legacy, RiskAdjusted, ScoringModel};
+use db::wallets;
+use std::collections::{HashMap, HashSet};
+use tracing::{info, warn};
+
+const COMMANDS: &str = "rebuild-daily-stats, rescore, reclassify, promote, trace-funding or top";
+const DEFAULT_TOP_LIMIT: i32 = 50;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    logging::init();
+
+    let command = std::env::args().nth(1);
+    let config = Config::load("configs/config.example.toml")?;
+    let migration_mode = if config.database.migrate_check_only {
+        MigrationMode::Check
+    } else {
+        MigrationMode::Apply
+    };
+    let discovery_url = config
+        .database
+        .discovery_url
+        .as_deref()
+        .context("database.discovery_url is not set")?;
+    let pool = db::connect(Some(discovery_url), Schema::Discovery, migration_mode).await?;
+
+    match command.as_deref() {
+        Some("rebuild-daily-stats") => {
+            // Tracked wallets' exact counts come from the copytrader database
+            let main = db::connect(Some(&config.database.url), Schema::Main, migration_mode).await?;
+            info!("📅 Rebuilding wallet_daily_stats from positions and raw_events...");
+            let stats = discovery::rebuild_daily_stats(&pool, Some(&main)).await?;
+            info!(
+                "📅 Rebuilt {} wallet-days ({} with exact counts from raw_events)",
+                stats.days, stats.tracked_days
+            );
+        }
+        Some("rescore") => {
+            let model = scoring_model(&config.scoring);
+            info!("🏅 Rescoring wallet_stats with {:?}...", model);
+            let wallets = scoring::rescore_all(&pool, model.as_ref()).await?;
+            info!("🏅 Rescored {} wallets", wallets);
+        }
+        Some("reclassify") => {
+            let thresholds = class_thresholds(&config.classification);
+            info!("🤖 Reclassifying wallet_stats with {:?}...", thresholds);
+            let wallets = classify::reclassify_all(&pool, &thresholds).await?;
+            info!("🤖 Reclassified {} wallets", wallets);
+        }
+        Some("promote") => {
+            let rules = promotion_rules(&config.promotion)?;
+            let main = db::connect(Some(&config.database.url), Schema::Main, migration_mode).await?;
+            let stats = promotion::run(&pool, &main, &rules, chrono::Utc::now()).await?;
+            info!("⬆️  Promoted {} wallets, demoted {}", stats.promoted, stats.demoted);
+        }
+        Some("trace-funding") => {
+            let limit = limit_arg()?;
+            let main = db::connect(Some(&config.database.url), Schema::Main, migration_mode).await?;
+            let tracked: HashSet<String> = wallets::list(&main, false).await?.into_iter().map(|w| w.wallet).collect();
+            let linked = funding::links(&pool).await?;
+            let options = trace_options(&config.funding_trace);
+            let history = config.funding_trace.history_per_wallet;
+            let signatures = config.funding_trace.signatures_per_wallet;
+            let rpc = RpcClient::new(&config.solana.rpc_url)?;
+
+            let candidates: Vec<String> = discovery::get_top_wallets(&pool, limit, None)
+                .await?
+                .into_iter()
+                .filter(|w| !w.is_tracked && !tracked.contains(&w.wallet) && !linked.contains_key(&w.wallet))
+                .map(|w| w.wallet)
+                .collect();
+            info!("🔗 Tracing funding of {} wallets over {}...", candidates.len(), config.solana.rpc_url);
+
+            let mut found = 0;
+            for wallet in &candidates {
+                let now = chrono::Utc::now();
+                let rpc = &rpc;
+                let fetch = move |w: String| async move {
+                    let transfers = rpc.inbound_transfers(&w, history, signatures).await?;
+                    let edges: Vec<FundingEdge> = transfers
+                        .into_iter()
+                        .map(|t| FundingEdge {
+                            funder: t.funder,
+                            lamports: t.lamports,
+                            seen_at: t.block_time.and_then(|bt| chrono::DateTime::from_timestamp(bt, 0)).unwrap_or(now),
+                            wallet: w.clone(),
+                        })
+                        .collect();
+                    Ok::<_, anyhow::Error>(edges)
+                };
+                match funding::trace(&pool, &tracked, wallet, &options, now, fetch).await {
+                    Ok(Some(link)) => {
+                        info!("🔗 {} is an alt of {} ({} hops: {})", wallet, link.linked_to, link.hops, link.path);
+                        funding::save_link(&pool, &link).await?;
+                        found += 1;
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("⚠️  Funding trace of {} failed: {:#}", wallet, e),
+                }
+            }
+            info!("🔗 Linked {} of {} wallets", found, candidates.len());
+        }
+        Some("top") => {
+            let limit = limit_arg()?;
+            let main = db::connect(Some(&config.database.url), Schema::Main, migration_mode).await?;
+            let aliases: HashMap<String, String> = wallets::list(&main, true)
+                .await?
+                .into_iter()
+                .filter_map(|w| Some((w.wallet, w.alias?)))
+                .collect();
+            let links = funding::links(&pool).await?;
+            for w in discovery::get_top_wallets(&pool, limit, None).await? {
+                let link = links.get(&w.wallet).map(|l| l.describe(&aliases)).unwrap_or_default();
+                println!(
+                    "{:<44}  {:>8.3}  {:>10.3} SOL  {:<12}  {}",
+                    w.wallet, w.profit_score, w.net_pnl_sol, w.wallet_class, link
+                );
+            }
+        }
+        Some(other) => bail!("Unknown command '{}' (expected {})", other, COMMANDS),
+        None => bail!("Missing command (expected {})", COMMANDS),
+    }
+
+    Ok(())
+}
+
+/// Optional second argument, `DEFAULT_TOP_LIMIT` when absent
+fn limit_arg() -> Result<i32> {
+    match std::env::args().nth(2) {
+        Some(limit) => limit.parse().with_context(|| format!("Invalid limit '{}'", limit)),
+        None => Ok(DEFAULT_TOP_LIMIT),
+    }
+}
+
+fn trace_options(config: &FundingTraceConfig) -> TraceOptions {
+    TraceOptions {
+        max_hops: config.max_hops,
+        max_funders: config.max_funders_per_wallet,
+        cache_for: chrono::Duration::hours(config.cache_hours),
+        ignore: config.ignore_funders.iter().cloned().collect(),
+    }
+}
+
+fn promotion_rules(config: &PromotionConfig) -> Result<PromotionRules> {
+    Ok(PromotionRules {
+        min_profit_score: config.min_profit_score,
+        min_trades: config.min_trades,
+        max_idle_days: config.max_idle_days,
+        classes: config.classes.iter().map(|c| c.parse()).collect::<Result<_>>()?,
+        demote_below_score: config.demote_below_score,
+        demote_idle_days: config.demote_idle_days,
+        max_per_run: config.max_per_run,
+    })
+}
+
+fn class_thresholds(config: &ClassificationConfig) -> ClassThresholds {
+    ClassThresholds {
+        min_sandwiches: config.min_sandwiches,
+        min_sniper_buys: config.min_sniper_buys,
+        sniper_buy_share: config.sniper_buy_share,
+        hf_trades_per_day: config.hf_trades_per_day,
+    }
+}
+
+fn scoring_model(config: &ScoringConfig) -> Box<dyn ScoringModel> {
+    match config.model {
+        ScoringModelKind::RiskAdjusted => Box::new(RiskAdjusted {
+            sharpe_weight: config.sharpe_weight,
+            median_roi_weight: config.median_roi_weight,
+            consistency_weight: config.consistency_weight,
+            drawdown_weight: config.drawdown_weight,
+            recency_half_life_days: config.recency_half_life_days,
+            confidence_trades: config.confidence_trades,
+        }),
+        ScoringModelKind::Legacy => Box::new(Legacy),
+    }
+}
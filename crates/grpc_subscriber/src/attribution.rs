@@ -0,0 +1,474 @@
+//! Work out what part a wallet played in a transaction
+//!
+//! A wallet appearing in `account_keys` doesn't mean it traded: it can be the
+//! fee recipient, a transfer target, or just a referenced account. Only the
+//! instruction's `user` signer is credited with the trade.
+
+use crate::pipeline::{DiscoveryUpdate, Landing};
+use db::mints::{MintActivity, MintFill};
+use decoder::{Action, DecodedInstruction, TradeEvent};
+
+const LAMPORTS_PER_SOL: f64 = 1_000_000_000.0;
+
+/// Programs and sysvars that show up in Pump.fun transactions and must never be
+/// credited with a trade
+const NON_WALLET_ACCOUNTS: &[&str] = &[
+    "11111111111111111111111111111111",             // System Program
+    "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",  // Token Program
+    "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",  // Token-2022
+    "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL", // Associated Token Program
+    "ComputeBudget111111111111111111111111111111",  // Compute Budget
+    "SysvarRent111111111111111111111111111111111",  // Rent sysvar
+    "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s",  // Metaplex Token Metadata
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletRole {
+    /// The instruction's `user` signer - the wallet that actually traded
+    Signer,
+    /// Paid the transaction fee but isn't the instruction's user (relayer/bundler)
+    FeePayer,
+    /// Not the actor, but its SOL balance moved (fee recipient, transfer target)
+    Counterparty,
+    /// Merely present in the account list
+    Referenced,
+}
+
+impl WalletRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WalletRole::Signer => "signer",
+            WalletRole::FeePayer => "fee_payer",
+            WalletRole::Counterparty => "counterparty",
+            WalletRole::Referenced => "referenced",
+        }
+    }
+
+    /// Whether the trade should be attributed to this wallet
+    pub fn is_actor(&self) -> bool {
+        matches!(self, WalletRole::Signer)
+    }
+}
+
+/// A System Program transfer decoded from the transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolTransfer {
+    pub from: String,
+    pub to: String,
+    pub lamports: u64,
+}
+
+/// The transfer if `data`/`accounts` are a System Program `Transfer` (accounts:
+/// from, to) between two different accounts
+pub fn sol_transfer(data: &[u8], accounts: &[String]) -> Option<SolTransfer> {
+    let lamports = decoder::decode_system_transfer(data)?;
+    match accounts {
+        [from, to, ..] if from != to && lamports > 0 => Some(SolTransfer {
+            from: from.clone(),
+            to: to.clone(),
+            lamports,
+        }),
+        _ => None,
+    }
+}
+
+/// Classify the wallet at `wallet_idx` for one decoded instruction.
+///
+/// `num_required_signatures` comes from the message header: the first that
+/// many account keys are signers, and key 0 is the fee payer.
+pub fn classify_wallet(
+    account_keys: &[String],
+    wallet_idx: usize,
+    num_required_signatures: usize,
+    decoded: &DecodedInstruction,
+    pre_balances: &[u64],
+    post_balances: &[u64],
+) -> WalletRole {
+    let is_signer = wallet_idx < num_required_signatures;
+
+    match decoded.user.as_deref() {
+        Some(user) if account_keys.get(wallet_idx).map(String::as_str) == Some(user) => {
+            WalletRole::Signer
+        }
+        // No user decoded (unknown instruction) - fall back to the signature set
+        None if is_signer => WalletRole::Signer,
+        _ if wallet_idx == 0 && is_signer => WalletRole::FeePayer,
+        _ => {
+            let balance_moved = matches!(
+                (pre_balances.get(wallet_idx), post_balances.get(wallet_idx)),
+                (Some(pre), Some(post)) if pre != post
+            );
+            if balance_moved {
+                WalletRole::Counterparty
+            } else {
+                WalletRole::Referenced
+            }
+        }
+    }
+}
+
+/// Resolve an instruction's account indices into pubkeys (keeps IDL positions)
+pub fn instruction_accounts(indices: &[u8], account_keys: &[String]) -> Vec<String> {
+    indices
+        .iter()
+        .map(|&i| account_keys.get(i as usize).cloned().unwrap_or_default())
+        .collect()
+}
+
+/// The TradeEvent each decoded BUY/SELL emitted (same mint, user and side,
+/// first unused in order); `None` for other actions or when none matches
+pub fn pair_trade_events<'a>(
+    decoded_actions: &[DecodedInstruction],
+    trade_events: &'a [TradeEvent],
+) -> Vec<Option<&'a TradeEvent>> {
+    let mut used = vec![false; trade_events.len()];
+    decoded_actions
+        .iter()
+        .map(|decoded| {
+            let is_buy = match decoded.action {
+                Action::Buy => true,
+                Action::Sell => false,
+                _ => return None,
+            };
+            let i = (0..trade_events.len()).find(|&i| {
+                let e = &trade_events[i];
+                !used[i]
+                    && e.is_buy == is_buy
+                    && decoded.mint.as_ref() == Some(&e.mint)
+                    && decoded.user.as_ref() == Some(&e.user)
+            })?;
+            used[i] = true;
+            Some(&trade_events[i])
+        })
+        .collect()
+}
+
+/// Discovery stats updates for a transaction: one per decoded BUY/SELL/CREATE,
+/// credited to that instruction's `user` signer only. A BUY carries the SOL
+/// `transfers` sent its trader in the same transaction (bundle funding).
+///
+/// Programs, PDAs (bonding curves can't sign), the fee recipient and any other
+/// account that merely appears in `account_keys` are never credited.
+#[allow(clippy::too_many_arguments)]
+pub fn discovery_updates(
+    account_keys: &[String],
+    num_required_signatures: usize,
+    decoded_actions: &[DecodedInstruction],
+    pre_balances: &[u64],
+    post_balances: &[u64],
+    program_id: &str,
+    transfers: &[SolTransfer],
+    landing: Landing,
+) -> Vec<DiscoveryUpdate> {
+    let mut updates = Vec::new();
+
+    for decoded in decoded_actions {
+        if !matches!(decoded.action, Action::Buy | Action::Sell | Action::Create) {
+            continue;
+        }
+        let Some(user) = decoded.user.as_deref() else {
+            continue;
+        };
+        if user == program_id || NON_WALLET_ACCOUNTS.contains(&user) {
+            continue;
+        }
+        // Only signers can be the actor; this also rules out PDAs
+        let Some(idx) = account_keys.iter().position(|k| k == user) else {
+            continue;
+        };
+        if idx >= num_required_signatures {
+            continue;
+        }
+
+        let balance_change = match (pre_balances.get(idx), post_balances.get(idx)) {
+            (Some(&pre), Some(&post)) => Some((post as i64 - pre as i64) as f64 / LAMPORTS_PER_SOL),
+            _ => None,
+        };
+        let sol_amount = match decoded.action {
+            Action::Buy => balance_change.filter(|c| *c < 0.0).map(|c| -c),
+            Action::Sell => balance_change.filter(|c| *c >= 0.0),
+            _ => None,
+        };
+        let funders = match decoded.action {
+            Action::Buy => transfers
+                .iter()
+                .filter(|t| t.to == user && t.from != program_id && !NON_WALLET_ACCOUNTS.contains(&t.from.as_str()))
+                .map(|t| (t.from.clone(), t.lamports))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        updates.push(DiscoveryUpdate {
+            wallet: user.to_string(),
+            action: decoded.action.as_str().to_string(),
+            sol_amount,
+            token_amount: decoded.token_amount.map(|amt| amt as f64),
+            mint: decoded.mint.clone(),
+            seen_at: landing.seen_at,
+            slot: landing.slot,
+            tx_index: landing.tx_index,
+            funders,
+        });
+    }
+
+    updates
+}
+
+/// One `MintActivity` per mint the decoded instructions touched: the CREATE
+/// signer and each BUY/SELL fill. Balances are left for the caller to fill in.
+///
+/// Fills use the paired TradeEvent's amounts (what actually filled); without
+/// one, the instruction's token amount and no SOL.
+pub fn mint_activities(
+    decoded_actions: &[DecodedInstruction],
+    action_events: &[Option<&TradeEvent>],
+    sig: &str,
+    program_id: &str,
+    landing: Landing,
+) -> Vec<MintActivity> {
+    let mut activities: Vec<MintActivity> = Vec::new();
+
+    for (decoded, trade_event) in decoded_actions.iter().zip(action_events) {
+        let (Some(mint), Some(user)) = (&decoded.mint, &decoded.user) else {
+            continue;
+        };
+        let idx = match activities.iter().position(|a| &a.mint == mint) {
+            Some(idx) => idx,
+            None => {
+                activities.push(MintActivity {
+                    mint: mint.clone(),
+                    sig: sig.to_string(),
+                    program: program_id.to_string(),
+                    slot: landing.slot,
+                    seen_at: landing.seen_at,
+                    creator: None,
+                    fills: Vec::new(),
+                    balances: Vec::new(),
+                });
+                activities.len() - 1
+            }
+        };
+        let activity = &mut activities[idx];
+
+        match decoded.action {
+            Action::Create => activity.creator = Some(user.clone()),
+            Action::Buy | Action::Sell => {
+                let (tokens, sol) = match trade_event {
+                    Some(e) => (e.token_amount, Some(e.sol_amount as f64 / LAMPORTS_PER_SOL)),
+                    None => (decoded.token_amount.unwrap_or(0), None),
+                };
+                activity.fills.push(MintFill {
+                    wallet: user.clone(),
+                    is_buy: decoded.action == Action::Buy,
+                    tokens,
+                    sol,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    activities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    const PUMP_PROGRAM: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
+    const FEE_RECIPIENT: &str = "CebN5WGQ4jvEPvsVU4EoHEpgzq1VV7AbicfhtW4xC9iM";
+
+    /// Transaction fixture in the shape Yellowstone delivers it (instruction data base58)
+    #[derive(Deserialize)]
+    struct Fixture {
+        num_required_signatures: usize,
+        account_keys: Vec<String>,
+        instructions: Vec<FixtureIx>,
+        inner_instructions: Vec<FixtureInner>,
+        pre_balances: Vec<u64>,
+        post_balances: Vec<u64>,
+        expected: Vec<Expected>,
+    }
+
+    #[derive(Deserialize)]
+    struct FixtureIx {
+        program_id_index: usize,
+        accounts: Vec<u8>,
+        data: String,
+    }
+
+    #[derive(Deserialize)]
+    struct FixtureInner {
+        instructions: Vec<FixtureIx>,
+    }
+
+    #[derive(Deserialize)]
+    struct Expected {
+        wallet: String,
+        action: String,
+        mint: String,
+        sol_amount: Option<f64>,
+    }
+
+    fn load(json: &str) -> (Fixture, Vec<DecodedInstruction>) {
+        let fixture: Fixture = serde_json::from_str(json).unwrap();
+        let decoded = fixture
+            .instructions
+            .iter()
+            .chain(fixture.inner_instructions.iter().flat_map(|i| &i.instructions))
+            .filter(|ix| fixture.account_keys[ix.program_id_index] == PUMP_PROGRAM)
+            .map(|ix| {
+                let data = bs58::decode(&ix.data).into_vec().unwrap();
+                let accounts = instruction_accounts(&ix.accounts, &fixture.account_keys);
+                decoder::decode_instruction(&data, &accounts).unwrap()
+            })
+            .collect();
+        (fixture, decoded)
+    }
+
+    fn assert_credited_only_to_trader(json: &str) {
+        let (fixture, decoded) = load(json);
+        let updates = discovery_updates(
+            &fixture.account_keys,
+            fixture.num_required_signatures,
+            &decoded,
+            &fixture.pre_balances,
+            &fixture.post_balances,
+            PUMP_PROGRAM,
+            &[],
+            Landing { slot: 1, tx_index: 0, seen_at: chrono::Utc::now() },
+        );
+
+        assert_eq!(updates.len(), fixture.expected.len());
+        for (update, expected) in updates.iter().zip(&fixture.expected) {
+            assert_eq!(update.wallet, expected.wallet);
+            assert_eq!(update.action, expected.action);
+            assert_eq!(update.mint.as_deref(), Some(expected.mint.as_str()));
+            match (update.sol_amount, expected.sol_amount) {
+                (Some(got), Some(want)) => assert!((got - want).abs() < 1e-9, "{} != {}", got, want),
+                (got, want) => assert_eq!(got, want),
+            }
+        }
+    }
+
+    #[test]
+    fn direct_buy_credits_only_the_signer() {
+        assert_credited_only_to_trader(include_str!("../tests/fixtures/pump_buy_direct.json"));
+    }
+
+    #[test]
+    fn relayed_sell_credits_trader_not_fee_payer() {
+        assert_credited_only_to_trader(include_str!("../tests/fixtures/pump_sell_relayed.json"));
+    }
+
+    #[test]
+    fn create_and_buy_credits_creator_not_mint_signer() {
+        assert_credited_only_to_trader(include_str!("../tests/fixtures/pump_create_and_buy.json"));
+    }
+
+    #[test]
+    fn roles_for_accounts_present_in_a_relayed_sell() {
+        let (fixture, decoded) = load(include_str!("../tests/fixtures/pump_sell_relayed.json"));
+        let sell = &decoded[0];
+        let role_of = |wallet: &str| {
+            let idx = fixture.account_keys.iter().position(|k| k == wallet).unwrap();
+            classify_wallet(
+                &fixture.account_keys,
+                idx,
+                fixture.num_required_signatures,
+                sell,
+                &fixture.pre_balances,
+                &fixture.post_balances,
+            )
+        };
+
+        assert_eq!(role_of(sell.user.as_deref().unwrap()), WalletRole::Signer);
+        assert_eq!(role_of(&fixture.account_keys[0]), WalletRole::FeePayer);
+        assert_eq!(role_of(FEE_RECIPIENT), WalletRole::Counterparty);
+        assert_eq!(role_of(PUMP_PROGRAM), WalletRole::Referenced);
+    }
+
+    #[test]
+    fn trade_events_pair_with_their_own_instruction() {
+        let (_, decoded) = load(include_str!("../tests/fixtures/pump_sell_relayed.json"));
+        let sell = &decoded[0];
+        let event = |is_buy: bool, user: &str, virtual_sol_reserves: u64| TradeEvent {
+            mint: sell.mint.clone().unwrap(),
+            sol_amount: 0,
+            token_amount: 0,
+            is_buy,
+            user: user.to_string(),
+            timestamp: 0,
+            virtual_sol_reserves,
+            virtual_token_reserves: 0,
+        };
+        let trader = sell.user.as_deref().unwrap();
+        // Someone else's sell and a buy by the trader come first
+        let events = vec![event(false, FEE_RECIPIENT, 1), event(true, trader, 2), event(false, trader, 3)];
+
+        let paired = pair_trade_events(&decoded, &events);
+        assert_eq!(paired.len(), decoded.len());
+        assert_eq!(paired[0].map(|e| e.virtual_sol_reserves), Some(3));
+        assert_eq!(pair_trade_events(&decoded, &[])[0], None);
+    }
+
+    #[test]
+    fn buy_carries_sol_sent_to_its_trader() {
+        let (fixture, decoded) = load(include_str!("../tests/fixtures/pump_buy_direct.json"));
+        let trader = decoded[0].user.clone().unwrap();
+        let transfer = |from: &str, to: &str| {
+            let mut data = 2u32.to_le_bytes().to_vec();
+            data.extend_from_slice(&1_000_000_000u64.to_le_bytes());
+            sol_transfer(&data, &[from.to_string(), to.to_string()])
+        };
+        // Funded by a bundler; paying the fee recipient isn't funding
+        let transfers: Vec<SolTransfer> =
+            [transfer("Bundler", &trader), transfer(&trader, FEE_RECIPIENT), transfer(&trader, &trader)]
+                .into_iter()
+                .flatten()
+                .collect();
+        assert_eq!(transfers.len(), 2);
+
+        let updates = discovery_updates(
+            &fixture.account_keys,
+            fixture.num_required_signatures,
+            &decoded,
+            &fixture.pre_balances,
+            &fixture.post_balances,
+            PUMP_PROGRAM,
+            &transfers,
+            Landing { slot: 1, tx_index: 0, seen_at: chrono::Utc::now() },
+        );
+        assert_eq!(updates[0].funders, vec![("Bundler".to_string(), 1_000_000_000)]);
+    }
+
+    #[test]
+    fn mint_activity_uses_filled_amounts() {
+        let (_, decoded) = load(include_str!("../tests/fixtures/pump_sell_relayed.json"));
+        let sell = &decoded[0];
+        let filled = TradeEvent {
+            mint: sell.mint.clone().unwrap(),
+            sol_amount: 2_500_000_000,
+            token_amount: 42,
+            is_buy: false,
+            user: sell.user.clone().unwrap(),
+            timestamp: 0,
+            virtual_sol_reserves: 0,
+            virtual_token_reserves: 0,
+        };
+        let landing = || Landing { slot: 7, tx_index: 0, seen_at: chrono::Utc::now() };
+
+        let activities = mint_activities(&decoded, &pair_trade_events(&decoded, &[filled]), "sig", PUMP_PROGRAM, landing());
+        assert_eq!(activities.len(), 1);
+        assert_eq!(activities[0].creator, None);
+        assert_eq!(activities[0].slot, 7);
+        assert_eq!(activities[0].sold_by(sell.user.as_deref().unwrap()), 42);
+        assert_eq!(activities[0].fills[0].sol, Some(2.5));
+
+        // Unpaired: the instruction's amount, SOL unknown
+        let unpaired = mint_activities(&decoded, &pair_trade_events(&decoded, &[]), "sig", PUMP_PROGRAM, landing());
+        assert_eq!(unpaired[0].fills[0].tokens, sell.token_amount.unwrap_or(0));
+        assert_eq!(unpaired[0].fills[0].sol, None);
+    }
+}
@@ -1,22 +1,33 @@
+mod attribution;
+mod pipeline;
+mod wallets;
+
 use anyhow::{Context, Result};
 use common::{config::Config, logging, sol_price::SolPriceCache};
-use db::{self as database, raw_events::batch_insert_raw_events};
+use db::{self as database, creators::CreatorTracker, journal::SpillJournal, migrations::{MigrationMode, Schema}};
+use db::mint_features::MintFeatureAggregator;
+use db::mints::BalanceChange;
+use pipeline::{PipelineMetrics, StageSinks, TxMessage};
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use tokio::time::{Duration, interval};
+use tokio::sync::{mpsc, oneshot, watch, Mutex, Notify};
+use tokio::time::Duration;
 use tokio_stream::StreamExt;
 use tracing::{error, info, warn};
 use yellowstone_grpc_client::GeyserGrpcClient;
 use yellowstone_grpc_proto::prelude::{
     subscribe_update::UpdateOneof, CommitmentLevel, SubscribeRequest,
-    SubscribeRequestFilterTransactions, SubscribeUpdateTransaction,
+    SubscribeRequestFilterTransactions, SubscribeUpdateTransaction, TransactionStatusMeta,
 };
 use solana_sdk::pubkey::Pubkey;
+use attribution::WalletRole;
+use wallets::{RolePolicy, TrackedWallets, WalletRegistry};
 
 const BATCH_SIZE: usize = 100;
 const BATCH_INTERVAL_SECS: u64 = 5;
+const SHUTDOWN_DRAIN_TIMEOUT_SECS: u64 = 30;
 const LAMPORTS_PER_SOL: f64 = 1_000_000_000.0;
 
 #[tokio::main]
@@ -29,14 +40,19 @@ async fn main() -> Result<()> {
     let config = Config::load("configs/config.example.toml")?;
     info!("⚙️  Loaded config: grpc_url={}, db_url=***", config.solana.grpc_url);
 
-    // Connect to database
-    let pool = database::connect(Some(&config.database.url)).await?;
+    // Connect to database (migrates or checks the schema version)
+    let migration_mode = if config.database.migrate_check_only {
+        MigrationMode::Check
+    } else {
+        MigrationMode::Apply
+    };
+    let pool = database::connect(Some(&config.database.url), Schema::Main, migration_mode).await?;
     database::health_check(&pool).await?;
     info!("✅ Database connection healthy (copytrader)");
 
     // Connect to discovery database (optional)
     let discovery_pool = if let Some(discovery_url) = &config.database.discovery_url {
-        let pool = database::connect(Some(discovery_url)).await?;
+        let pool = database::connect(Some(discovery_url), Schema::Discovery, migration_mode).await?;
         database::health_check(&pool).await?;
         info!("✅ Discovery database connection healthy");
         Some(pool)
@@ -50,91 +66,224 @@ async fn main() -> Result<()> {
     sol_price_cache.clone().start_updater();
     info!("💰 SOL price updater started (fetching every 10 seconds)");
 
+    // Config watchlist into watched_mints (mints added from the CLI are kept)
+    let (added, removed) = database::watchlist::sync_config(&pool, &config.watchlist.mints).await?;
+    if added + removed > 0 {
+        info!("👁️  Watchlist synced from config: +{} / -{}", added, removed);
+    }
+
     // Load tracked wallets
-    let (tracked_wallets, wallet_aliases) = load_tracked_wallets(&pool).await?;
-    info!("👥 Loaded {} tracked wallets", tracked_wallets.len());
+    let tracked = wallets::load_tracked_wallets(&pool).await?;
+    info!("👥 Loaded {} tracked wallets ({} with roles)", tracked.len(), tracked.roles.len());
+    if !tracked.watched_mints.is_empty() {
+        info!("👁️  Watching every trade on {} mints", tracked.watched_mints.len());
+    }
+    let wallet_registry = WalletRegistry::new(tracked);
+    let role_policy = RolePolicy::from_config(&config);
 
     let program_id = Pubkey::from_str(&config.pumpfun.program_id)?;
     info!("🎯 Monitoring Pump.fun program: {}", program_id);
 
-    // Shared buffer for batching
+    // Shared buffer for batching, flushed on interval or when it reaches BATCH_SIZE
     let buffer = Arc::new(Mutex::new(Vec::new()));
-    let buffer_clone = buffer.clone();
-    let pool_clone = pool.clone();
+    let flush_wake = Arc::new(Notify::new());
+    let flush_drained = Arc::new(Notify::new());
 
-    // Spawn batch flusher
+    // Shutdown on SIGINT/SIGTERM
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
     tokio::spawn(async move {
-        let mut interval = interval(Duration::from_secs(BATCH_INTERVAL_SECS));
-        loop {
-            interval.tick().await;
-            let mut buf = buffer_clone.lock().await;
-            if !buf.is_empty() {
-                let events: Vec<_> = buf.drain(..).collect();
-                drop(buf);
-                if let Err(e) = batch_insert_raw_events(&pool_clone, &events).await {
-                    error!("Failed to flush events: {}", e);
-                } else if !events.is_empty() {
-                    info!("💾 Flushed {} events to database", events.len());
-                }
+        wait_for_shutdown_signal().await;
+        let _ = shutdown_tx.send(true);
+    });
+
+    // Build bounded stage pipeline (receive → decode → persist)
+    let pipeline::Pipeline {
+        tx_sender,
+        tx_receiver,
+        event_receiver,
+        discovery_receiver,
+        mint_receiver,
+        sinks,
+    } = pipeline::Pipeline::new(&config.pipeline, discovery_pool.is_some(), config.creators.enabled || config.mint_features.enabled);
+    info!(
+        "🧵 Pipeline queues: tx={}, events={}, discovery={}, mints={}, on_full={:?}",
+        config.pipeline.tx_queue_capacity,
+        config.pipeline.event_queue_capacity,
+        config.pipeline.discovery_queue_capacity,
+        config.pipeline.mint_queue_capacity,
+        config.pipeline.on_full
+    );
+
+    let event_stage = tokio::spawn(pipeline::run_event_stage(
+        event_receiver,
+        buffer.clone(),
+        flush_wake.clone(),
+        flush_drained.clone(),
+        BATCH_SIZE,
+    ));
+    let discovery_stage = match (discovery_receiver, discovery_pool) {
+        (Some(receiver), Some(pool)) => Some(tokio::spawn(pipeline::run_discovery_stage(
+            receiver,
+            pool,
+            Duration::from_secs(config.pipeline.discovery_flush_secs.max(1)),
+            config.pipeline.discovery_max_pending_wallets,
+            pipeline::scoring_model(&config.scoring),
+            pipeline::class_thresholds(&config.classification),
+        ))),
+        _ => None,
+    };
+    let mint_stage = match mint_receiver {
+        Some(receiver) => {
+            let mut trackers = pipeline::MintTrackers { creators: None, features: None };
+            if config.creators.enabled {
+                let mut creators = CreatorTracker::new(
+                    config.creators.dump_supply_fraction,
+                    chrono::Duration::hours(config.creators.window_hours),
+                );
+                let loaded = creators.load(&pool, chrono::Utc::now()).await?;
+                info!(
+                    "🧑‍🍳 Following mint creators ({} from the last {}h), alerting at {:.1}% of supply",
+                    loaded,
+                    config.creators.window_hours,
+                    config.creators.dump_supply_fraction * 100.0
+                );
+                trackers.creators = Some(creators);
+            }
+            if config.mint_features.enabled {
+                let mut features = MintFeatureAggregator::new(chrono::Duration::hours(config.mint_features.window_hours));
+                let loaded = features.load(&pool, chrono::Utc::now()).await?;
+                info!("🧮 Computing mint features ({} from the last {}h)", loaded, config.mint_features.window_hours);
+                trackers.features = Some(features);
             }
+            Some(tokio::spawn(pipeline::run_mint_stage(
+                receiver,
+                pool.clone(),
+                sinks.events.clone(),
+                trackers,
+                Duration::from_secs(config.pipeline.mint_flush_secs.max(1)),
+            )))
         }
-    });
+        None => None,
+    };
+    let decode_stage = tokio::spawn(run_decode_stage(
+        tx_receiver,
+        sinks.clone(),
+        wallet_registry.clone(),
+        role_policy,
+        program_id,
+        sol_price_cache.clone(),
+    ));
+    // Keep the tracked set in sync with the wallets table
+    tokio::spawn(wallets::run_wallet_reloader(
+        pool.clone(),
+        wallet_registry.clone(),
+        config.database.wallet_reload_poll_secs,
+        shutdown_rx.clone(),
+    ));
+
+    tokio::spawn(pipeline::report_metrics(
+        tx_sender.clone(),
+        sinks.clone(),
+        config.pipeline.metrics_interval_secs,
+        shutdown_rx.clone(),
+    ));
+
+    // Spawn batch flusher
+    let (flush_stop_tx, flush_stop_rx) = oneshot::channel();
+    let journal = SpillJournal::new(&config.pipeline.spill_journal_path);
+    if journal.has_pending() {
+        info!("♻️  Found spilled events in {}, replaying once the database is healthy", journal.path().display());
+    }
+    let flusher = tokio::spawn(pipeline::run_flusher(
+        buffer.clone(),
+        pool.clone(),
+        journal,
+        flush_wake.clone(),
+        flush_drained.clone(),
+        Duration::from_secs(BATCH_INTERVAL_SECS),
+        flush_stop_rx,
+    ));
 
     // Main ingestion loop with reconnection
-    loop {
+    while !*shutdown_rx.borrow() {
         info!("🔌 Connecting to Yellowstone gRPC: {}", config.solana.grpc_url);
         match run_grpc_stream(
             &config.solana.grpc_url,
             &program_id,
-            &tracked_wallets,
-            &wallet_aliases,
-            buffer.clone(),
-            discovery_pool.clone(),
-            sol_price_cache.clone(),
+            &tx_sender,
+            &sinks.metrics,
+            &mut shutdown_rx,
         )
         .await
         {
+            Ok(_) if *shutdown_rx.borrow() => break,
             Ok(_) => {
                 warn!("Stream ended normally, reconnecting...");
             }
             Err(e) => {
                 error!("Stream error: {}, reconnecting in 5s...", e);
-                tokio::time::sleep(Duration::from_secs(5)).await;
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+                    _ = shutdown_rx.changed() => {}
+                }
             }
         }
     }
-}
-
-async fn load_tracked_wallets(pool: &database::Pool) -> Result<(Vec<String>, HashMap<String, String>)> {
-    let rows = sqlx::query("SELECT wallet, alias FROM wallets WHERE is_tracked")
-        .fetch_all(pool)
-        .await?;
 
-    let mut wallets = Vec::new();
-    let mut aliases = HashMap::new();
-    
-    for row in rows {
-        use sqlx::Row;
-        let wallet: String = row.get("wallet");
-        let alias: Option<String> = row.get("alias");
-        
-        wallets.push(wallet.clone());
-        if let Some(alias) = alias {
-            aliases.insert(wallet, alias);
+    // Drain the pipeline in stage order: closing the tx queue ends the decode
+    // stage, which closes the event/discovery/mint queues behind it. The mint
+    // stage also sends events, so it has to stop before the event stage can.
+    info!("🛑 Shutdown requested, draining pipeline...");
+    drop(tx_sender);
+    drop(sinks);
+    let _ = decode_stage.await;
+    if let Some(handle) = mint_stage {
+        let _ = handle.await;
+    }
+    let _ = event_stage.await;
+    if let Some(handle) = discovery_stage {
+        if tokio::time::timeout(Duration::from_secs(SHUTDOWN_DRAIN_TIMEOUT_SECS), handle).await.is_err() {
+            warn!("⚠️  Discovery stage did not drain within {}s, abandoning remaining updates", SHUTDOWN_DRAIN_TIMEOUT_SECS);
         }
     }
 
-    Ok((wallets, aliases))
+    // Final flush of whatever the event stage buffered
+    let _ = flush_stop_tx.send(());
+    let _ = flusher.await;
+
+    info!("👋 Shutdown complete");
+    Ok(())
+}
+
+/// Resolve on Ctrl+C (SIGINT) or SIGTERM
+async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut sig) => {
+                sig.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to install SIGTERM handler: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => info!("🛑 Received Ctrl+C"),
+        _ = terminate => info!("🛑 Received SIGTERM"),
+    }
 }
 
 async fn run_grpc_stream(
     endpoint: &str,
     program_id: &Pubkey,
-    tracked_wallets: &[String],
-    wallet_aliases: &HashMap<String, String>,
-    buffer: Arc<Mutex<Vec<db::raw_events::RawEvent>>>,
-    discovery_pool: Option<database::Pool>,
-    sol_price_cache: SolPriceCache,
+    tx_sender: &mpsc::Sender<TxMessage>,
+    metrics: &PipelineMetrics,
+    shutdown: &mut watch::Receiver<bool>,
 ) -> Result<()> {
     // Connect to gRPC using the same pattern as your working bot
     let mut client = GeyserGrpcClient::build_from_shared(endpoint.to_string())?
@@ -180,9 +329,17 @@ async fn run_grpc_stream(
 
     let mut tx_count = 0;
 
-    // Process messages
+    // Process messages until the stream ends or shutdown is requested
     loop {
-        match stream.next().await {
+        let next = tokio::select! {
+            _ = shutdown.changed() => {
+                info!("🔌 Closing Yellowstone gRPC stream");
+                return Ok(());
+            }
+            next = stream.next() => next,
+        };
+
+        match next {
             Some(Ok(msg)) => {
                 if let Some(update) = msg.update_oneof {
                     match update {
@@ -190,18 +347,16 @@ async fn run_grpc_stream(
                             tx_count += 1;
 
                             if tx_count % 100 == 0 {
-                                info!("📊 Processed {} transactions", tx_count);
+                                info!("📊 Received {} transactions", tx_count);
                             }
 
-                            // Get current SOL price
-                            let sol_price = sol_price_cache.get_price().await;
-
-                            // Process transaction
-                            if let Err(e) =
-                                process_transaction(&tx_update, tracked_wallets, wallet_aliases, program_id, buffer.clone(), discovery_pool.clone(), sol_price).await
-                            {
-                                warn!("Failed to process transaction: {}", e);
-                            }
+                            // Hand off to the decode stage (waits if the queue is full)
+                            let recv_time_ns = chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0);
+                            metrics.received.fetch_add(1, Ordering::Relaxed);
+                            tx_sender
+                                .send(TxMessage { tx: tx_update, recv_time_ns })
+                                .await
+                                .map_err(|_| anyhow::anyhow!("decode stage closed"))?;
                         }
                         _ => {
                             // Ignore other update types
@@ -221,13 +376,47 @@ async fn run_grpc_stream(
     }
 }
 
+/// Decode stage: turn received transactions into events and discovery updates
+async fn run_decode_stage(
+    mut receiver: mpsc::Receiver<TxMessage>,
+    sinks: StageSinks,
+    wallet_registry: WalletRegistry,
+    role_policy: RolePolicy,
+    program_id: Pubkey,
+    sol_price_cache: SolPriceCache,
+) {
+    while let Some(msg) = receiver.recv().await {
+        // Get current SOL price
+        let sol_price = sol_price_cache.get_price().await;
+
+        // Snapshot so a concurrent reload can't change the set mid-transaction
+        let tracked = wallet_registry.snapshot();
+
+        if let Err(e) = process_transaction(
+            &msg.tx,
+            msg.recv_time_ns,
+            &tracked,
+            &role_policy,
+            &program_id,
+            &sinks,
+            sol_price,
+        )
+        .await
+        {
+            warn!("Failed to process transaction: {}", e);
+        }
+        sinks.metrics.decoded.fetch_add(1, Ordering::Relaxed);
+    }
+    info!("Decode stage stopped: receive stage closed");
+}
+
 async fn process_transaction(
     tx: &SubscribeUpdateTransaction,
-    tracked_wallets: &[String],
-    wallet_aliases: &HashMap<String, String>,
+    recv_time_ns: i64,
+    tracked: &TrackedWallets,
+    role_policy: &RolePolicy,
     program_id: &Pubkey,
-    buffer: Arc<Mutex<Vec<db::raw_events::RawEvent>>>,
-    discovery_pool: Option<database::Pool>,
+    sinks: &StageSinks,
     sol_price: f64,
 ) -> Result<()> {
     // Extract transaction data
@@ -239,34 +428,41 @@ async fn process_transaction(
     // Get signature
     let sig = bs58::encode(&transaction.signature).into_string();
 
-    // Get account keys from transaction
+    // Get account keys from transaction (static keys, then address-lookup-table keys)
     let mut account_keys = Vec::new();
-    for key in &message.account_keys {
+    for key in message
+        .account_keys
+        .iter()
+        .chain(&meta.loaded_writable_addresses)
+        .chain(&meta.loaded_readonly_addresses)
+    {
         if let Ok(pubkey) = Pubkey::try_from(key.as_slice()) {
             account_keys.push(pubkey.to_string());
         }
     }
 
+    // The first `num_required_signatures` keys are signers; key 0 pays the fee
+    let num_required_signatures = message
+        .header
+        .as_ref()
+        .map(|h| h.num_required_signatures as usize)
+        .unwrap_or(1);
+
     // Find tracked wallets in this transaction
     let found_wallets: Vec<String> = account_keys
         .iter()
-        .filter(|key| tracked_wallets.contains(key))
+        .filter(|key| tracked.wallets.contains(*key) && role_policy.records(tracked, key))
         .cloned()
         .collect();
 
     let has_tracked_wallets = !found_wallets.is_empty();
 
-    // For discovery mode: find ALL wallets in Pump.fun transactions
-    // We'll update discovery stats for all wallets, but only create detailed events for tracked ones
-    let all_wallet_keys: Vec<String> = if discovery_pool.is_some() && !has_tracked_wallets {
-        // If no tracked wallets but discovery is enabled, extract all wallet keys
-        account_keys.clone()
-    } else {
-        Vec::new()
-    };
-
-    // Skip if no tracked wallets AND discovery is not enabled
-    if !has_tracked_wallets && discovery_pool.is_none() {
+    // Skip if no tracked wallets AND discovery/mint tracking are off AND nothing is watched
+    if !has_tracked_wallets
+        && !sinks.discovery_enabled()
+        && !sinks.mints_enabled()
+        && tracked.watched_mints.is_empty()
+    {
         return Ok(());
     }
 
@@ -286,7 +482,8 @@ async fn process_transaction(
         let wallet_names: Vec<String> = found_wallets
             .iter()
             .map(|w| {
-                wallet_aliases
+                tracked
+                    .aliases
                     .get(w)
                     .cloned()
                     .unwrap_or_else(|| w[..8].to_string())
@@ -295,24 +492,43 @@ async fn process_transaction(
         info!("👤 Wallets: {:?}", wallet_names);
     }
 
-    // Decode Pump.fun instructions from top-level
+    // Decode Pump.fun instructions from top-level, and SOL transfers (bundle funding)
     let mut decoded_actions = Vec::new();
+    let mut transfers = Vec::new();
     for instruction in &message.instructions {
         let program_idx = instruction.program_id_index as usize;
+        if program_idx < account_keys.len() && account_keys[program_idx] == decoder::SYSTEM_PROGRAM_ID {
+            let ix_accounts = attribution::instruction_accounts(&instruction.accounts, &account_keys);
+            transfers.extend(attribution::sol_transfer(&instruction.data, &ix_accounts));
+            continue;
+        }
         if program_idx < account_keys.len() && account_keys[program_idx] == program_id.to_string() {
             // This is a Pump.fun instruction
-            let decoded = decoder::decode_instruction(&instruction.data, &account_keys)?;
+            let ix_accounts = attribution::instruction_accounts(&instruction.accounts, &account_keys);
+            let decoded = decoder::decode_instruction(&instruction.data, &ix_accounts)?;
             decoded_actions.push(decoded);
         }
     }
 
     // Also check inner instructions (this is where BUYs often hide!)
+    let mut trade_events = Vec::new();
     for inner_ix_set in &meta.inner_instructions {
         for inner_ix in &inner_ix_set.instructions {
             let program_idx = inner_ix.program_id_index as usize;
+            if program_idx < account_keys.len() && account_keys[program_idx] == decoder::SYSTEM_PROGRAM_ID {
+                let ix_accounts = attribution::instruction_accounts(&inner_ix.accounts, &account_keys);
+                transfers.extend(attribution::sol_transfer(&inner_ix.data, &ix_accounts));
+                continue;
+            }
             if program_idx < account_keys.len() && account_keys[program_idx] == program_id.to_string() {
+                // The program's self-CPI TradeEvent, with the curve reserves after the trade
+                if let Some(event) = decoder::decode_trade_event(&inner_ix.data) {
+                    trade_events.push(event);
+                    continue;
+                }
                 // Found a Pump.fun instruction in inner instructions!
-                let decoded = decoder::decode_instruction(&inner_ix.data, &account_keys)?;
+                let ix_accounts = attribution::instruction_accounts(&inner_ix.accounts, &account_keys);
+                let decoded = decoder::decode_instruction(&inner_ix.data, &ix_accounts)?;
                 decoded_actions.push(decoded);
                 info!("🔍 Found Pump.fun instruction in INNER instructions (likely a BUY!)");
             }
@@ -333,12 +549,49 @@ async fn process_transaction(
     // Create events for each tracked wallet and decoded action
     // For discovery mode: also update stats for ALL wallets
     let slot = tx.slot as i64;
-    let ts_ns = chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0);
+    let ts_ns = recv_time_ns;
 
     let mut event_count = 0;
-    
-    // Process tracked wallets (create detailed events)
-    for wallet in &found_wallets {
+    let action_events = attribution::pair_trade_events(&decoded_actions, &trade_events);
+
+    // Per-mint activity (creator, fills, token balance changes) for the mint stage
+    if sinks.mints_enabled() {
+        for mut activity in attribution::mint_activities(
+            &decoded_actions,
+            &action_events,
+            &sig,
+            &program_id.to_string(),
+            pipeline::Landing {
+                slot: tx.slot,
+                tx_index: transaction.index,
+                seen_at: chrono::DateTime::from_timestamp_nanos(recv_time_ns),
+            },
+        ) {
+            let curve = bonding_curve(&activity.mint, program_id);
+            activity.balances = token_balance_changes(meta, &activity.mint, curve.as_deref());
+            sinks.send_mint(activity).await;
+        }
+    }
+
+    // Watchlist: every trader on a watched mint gets detailed events too
+    let mut watched_traders: Vec<String> = Vec::new();
+    for decoded in &decoded_actions {
+        let (Some(mint), Some(user)) = (&decoded.mint, &decoded.user) else {
+            continue;
+        };
+        if tracked.watched_mints.contains(mint)
+            && !found_wallets.contains(user)
+            && !watched_traders.contains(user)
+            && role_policy.records(tracked, user)
+        {
+            watched_traders.push(user.clone());
+        }
+    }
+
+    // Process tracked wallets and watched traders (create detailed events)
+    for wallet in found_wallets.iter().chain(&watched_traders) {
+        // Untracked traders only get events for the watched mints
+        let watch_only = !tracked.wallets.contains(wallet);
         // Find wallet's balance index in the transaction
         let wallet_idx = account_keys.iter().position(|k| k == wallet);
         
@@ -366,29 +619,50 @@ async fn process_transaction(
                 (None, None, None, None, None)
             };
 
-        for decoded in &decoded_actions {
+        for (decoded, &trade_event) in decoded_actions.iter().zip(&action_events) {
             // Skip UNKNOWN actions - these are from non-Pump.fun programs (Token Program, System Program, etc.)
             if matches!(decoded.action, decoder::Action::Unknown) {
                 continue;
             }
+            if watch_only && !decoded.mint.as_ref().is_some_and(|m| tracked.watched_mints.contains(m)) {
+                continue;
+            }
+
+            // Only the instruction's user is credited with the trade; other
+            // roles are recorded for context without amounts
+            let role = match wallet_idx {
+                Some(idx) => attribution::classify_wallet(
+                    &account_keys,
+                    idx,
+                    num_required_signatures,
+                    decoded,
+                    pre_balances,
+                    post_balances,
+                ),
+                None => WalletRole::Referenced,
+            };
 
             // For amount_in: store token amount
             // For amount_out: store SOL amount (spent on BUY, received on SELL)
-            let (amount_in, amount_out) = match decoded.action {
-                decoder::Action::Buy => {
-                    (decoded.token_amount.map(|amt| amt as f64), sol_spent)
-                }
-                decoder::Action::Sell => {
-                    (decoded.token_amount.map(|amt| amt as f64), sol_received)
-                }
-                decoder::Action::Create => {
-                    (None, None)
+            let (amount_in, amount_out) = if !role.is_actor() {
+                (None, None)
+            } else {
+                match decoded.action {
+                    decoder::Action::Buy => {
+                        (decoded.token_amount.map(|amt| amt as f64), sol_spent)
+                    }
+                    decoder::Action::Sell => {
+                        (decoded.token_amount.map(|amt| amt as f64), sol_received)
+                    }
+                    decoder::Action::Create => {
+                        (None, None)
+                    }
+                    _ => (None, None),
                 }
-                _ => (None, None),
             };
 
             // Get wallet alias (name) from the loaded aliases map
-            let wallet_alias = wallet_aliases.get(wallet).cloned();
+            let wallet_alias = tracked.aliases.get(wallet).cloned();
 
             // Build structured meta_json with balance information
             let meta_json = if let Some(idx) = wallet_idx {
@@ -401,7 +675,15 @@ async fn process_transaction(
                         "pre_balance_sol": pre_balances[idx] as f64 / LAMPORTS_PER_SOL,
                         "post_balance_sol": post_balances[idx] as f64 / LAMPORTS_PER_SOL,
                         "fee_lamports": meta.fee,
+                        // BUY max_sol_cost / SELL min_sol_output, for slippage analysis
+                        "sol_limit_lamports": decoded.max_sol_cost,
                         "wallet_alias": wallet_alias,
+                        "role": role.as_str(),
+                        "actor": decoded.user,
+                        // Bonding curve after the trade (from the program's TradeEvent)
+                        "virtual_sol_reserves": trade_event.map(|e| e.virtual_sol_reserves),
+                        "virtual_token_reserves": trade_event.map(|e| e.virtual_token_reserves),
+                        "watchlist": watch_only,
                     }))
                 } else {
                     None
@@ -446,8 +728,28 @@ async fn process_transaction(
                 pre_balance_sol,
                 post_balance_sol,
                 balance_change_sol,
+                role: Some(role.as_str().to_string()),
+                wallet_roles: tracked.roles_label(wallet),
             };
 
+            // Watched mints can be busy; only tracked wallets' trades are logged
+            if watch_only {
+                sinks.send_event(event).await?;
+                event_count += 1;
+                continue;
+            }
+
+            if !role.is_actor() {
+                info!("👀 {} present as {} (actor: {})",
+                    wallet_alias.as_deref().unwrap_or(&wallet[..8]),
+                    role.as_str(),
+                    decoded.user.as_deref().map(|u| &u[..8]).unwrap_or("unknown")
+                );
+                sinks.send_event(event).await?;
+                event_count += 1;
+                continue;
+            }
+
             // Log the trade details with SOL amounts
             match decoded.action {
                 decoder::Action::Buy => {
@@ -468,121 +770,33 @@ async fn process_transaction(
                 _ => {}
             }
 
-            let mut buf = buffer.lock().await;
-            buf.push(event);
+            sinks.send_event(event).await?;
             event_count += 1;
-
-            // Auto-flush if buffer is full
-            if buf.len() >= BATCH_SIZE {
-                info!("📦 Buffer full ({} events), triggering flush", buf.len());
-            }
-            drop(buf);
-
-            // Update discovery database for ALL wallets (not just tracked)
-            // This allows us to identify profitable traders automatically
-            if let Some(ref pool) = discovery_pool {
-                // Determine SOL amount for discovery stats
-                let sol_amount = match decoded.action {
-                    decoder::Action::Buy => sol_spent,
-                    decoder::Action::Sell => sol_received,
-                    _ => None,
-                };
-
-                // Update wallet stats in discovery database
-                match db::discovery::update_wallet_stats(
-                    pool,
-                    wallet,
-                    decoded.action.as_str(),
-                    sol_amount,
-                    decoded.mint.as_deref(),
-                ).await {
-                    Ok(true) => {
-                        // New wallet discovered!
-                        info!("🆕 NEW WALLET DISCOVERED: {} | Action: {} | SOL: {:.4}", 
-                            &wallet[..8], 
-                            decoded.action.as_str(),
-                            sol_amount.unwrap_or(0.0)
-                        );
-                    }
-                    Ok(false) => {
-                        // Existing wallet, no log needed
-                    }
-                    Err(e) => {
-                        warn!("Failed to update discovery stats for wallet {}: {}", &wallet[..8], e);
-                    }
-                }
-            }
         }
     }
 
-    // Discovery mode: Process ALL wallets in the transaction (not just tracked ones)
+    // Discovery mode: credit each decoded trade to its actual trader (tracked or not)
     // This enables automatic discovery of profitable traders
-    if let Some(ref pool) = discovery_pool {
-        if !has_tracked_wallets && !all_wallet_keys.is_empty() {
-            // Process all wallets for discovery stats only (no detailed events)
-            for wallet in &all_wallet_keys {
-                // Skip if this wallet is actually tracked (already processed above)
-                if tracked_wallets.contains(wallet) {
-                    continue;
-                }
-
-                // Find wallet's balance index
-                let wallet_idx = account_keys.iter().position(|k| k == wallet);
-                let (sol_spent, sol_received) = if let Some(idx) = wallet_idx {
-                    if idx < pre_balances.len() && idx < post_balances.len() {
-                        let pre_balance = pre_balances[idx] as f64 / LAMPORTS_PER_SOL;
-                        let post_balance = post_balances[idx] as f64 / LAMPORTS_PER_SOL;
-                        let balance_change = post_balance - pre_balance;
-                        
-                        if balance_change < 0.0 {
-                            (Some(-balance_change), None)
-                        } else {
-                            (None, Some(balance_change))
-                        }
-                    } else {
-                        (None, None)
-                    }
-                } else {
-                    (None, None)
-                };
-
-                // Update discovery stats for each decoded action
-                for decoded in &decoded_actions {
-                    if matches!(decoded.action, decoder::Action::Unknown) {
-                        continue;
-                    }
-
-                    let sol_amount = match decoded.action {
-                        decoder::Action::Buy => sol_spent,
-                        decoder::Action::Sell => sol_received,
-                        _ => None,
-                    };
-
-                    // Update wallet stats
-                    match db::discovery::update_wallet_stats(
-                        pool,
-                        wallet,
-                        decoded.action.as_str(),
-                        sol_amount,
-                        decoded.mint.as_deref(),
-                    ).await {
-                        Ok(true) => {
-                            // New wallet discovered!
-                            info!("🆕 NEW WALLET DISCOVERED: {} | Action: {} | SOL: {:.4}", 
-                                &wallet[..8], 
-                                decoded.action.as_str(),
-                                sol_amount.unwrap_or(0.0)
-                            );
-                        }
-                        Ok(false) => {
-                            // Existing wallet, updated stats
-                        }
-                        Err(e) => {
-                            warn!("Failed to update discovery stats: {}", e);
-                        }
-                    }
-                }
+    if sinks.discovery_enabled() {
+        for update in attribution::discovery_updates(
+            &account_keys,
+            num_required_signatures,
+            &decoded_actions,
+            pre_balances,
+            post_balances,
+            &program_id.to_string(),
+            &transfers,
+            pipeline::Landing {
+                slot: tx.slot,
+                tx_index: transaction.index,
+                seen_at: chrono::DateTime::from_timestamp_nanos(recv_time_ns),
+            },
+        ) {
+            // Liquidity/admin-style wallets would skew P&L and rankings
+            if !role_policy.discovers(tracked, &update.wallet) {
+                continue;
             }
+            sinks.send_discovery(update).await;
         }
     }
 
@@ -592,3 +806,42 @@ async fn process_transaction(
 
     Ok(())
 }
+
+/// The mint's bonding curve PDA, which holds the unsold supply
+fn bonding_curve(mint: &str, program_id: &Pubkey) -> Option<String> {
+    let mint = Pubkey::from_str(mint).ok()?;
+    let (curve, _) = Pubkey::find_program_address(&[b"bonding-curve", mint.as_ref()], program_id);
+    Some(curve.to_string())
+}
+
+/// Owners other than `exclude` whose balance of `mint` changed in the
+/// transaction, in raw units
+fn token_balance_changes(meta: &TransactionStatusMeta, mint: &str, exclude: Option<&str>) -> Vec<BalanceChange> {
+    let mut changes: Vec<BalanceChange> = Vec::new();
+    for (balances, is_post) in [(&meta.pre_token_balances, false), (&meta.post_token_balances, true)] {
+        for balance in balances
+            .iter()
+            .filter(|b| b.mint == mint && !b.owner.is_empty() && Some(b.owner.as_str()) != exclude)
+        {
+            let amount = balance
+                .ui_token_amount
+                .as_ref()
+                .and_then(|a| a.amount.parse::<u64>().ok())
+                .unwrap_or(0);
+            let idx = match changes.iter().position(|c| c.owner == balance.owner) {
+                Some(idx) => idx,
+                None => {
+                    changes.push(BalanceChange { owner: balance.owner.clone(), pre: 0, post: 0 });
+                    changes.len() - 1
+                }
+            };
+            if is_post {
+                changes[idx].post += amount;
+            } else {
+                changes[idx].pre += amount;
+            }
+        }
+    }
+    changes.retain(|c| c.pre != c.post);
+    changes
+}
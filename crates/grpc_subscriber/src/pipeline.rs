@@ -0,0 +1,499 @@
+//! Bounded stage pipeline: receive → decode → persist
+//!
+//! The gRPC stream only hands transactions to the decode stage; all database
+//! work happens in the persist stages so a slow database can't stall the stream.
+
+use anyhow::Result;
+use common::config::{ClassificationConfig, PipelineConfig, QueueFullPolicy, ScoringConfig, ScoringModelKind};
+use chrono::{DateTime, Utc};
+use db::classify::ClassThresholds;
+use db::creators::CreatorTracker;
+use db::discovery::DiscoveryAggregator;
+use db::journal::SpillJournal;
+use db::mint_features::MintFeatureAggregator;
+use db::mints::MintActivity;
+use db::raw_events::{bulk_insert_raw_events, RawEvent};
+use db::scoring::{Legacy, RiskAdjusted, ScoringModel};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot, watch, Mutex, Notify};
+use tokio::time::{interval, Duration};
+use tracing::{error, info, warn};
+use yellowstone_grpc_proto::prelude::SubscribeUpdateTransaction;
+
+/// Transaction handed from the receive stage to the decode stage
+pub struct TxMessage {
+    pub tx: SubscribeUpdateTransaction,
+    pub recv_time_ns: i64,
+}
+
+/// Discovery stats update produced by the decode stage
+#[derive(Debug, Clone)]
+pub struct DiscoveryUpdate {
+    pub wallet: String,
+    pub action: String,
+    pub sol_amount: Option<f64>,
+    pub token_amount: Option<f64>,
+    pub mint: Option<String>,
+    pub seen_at: DateTime<Utc>,
+    pub slot: u64,
+    /// Position of the transaction in its block
+    pub tx_index: u64,
+    /// SOL sent to the wallet in the same transaction, as (funder, lamports); BUYs only
+    pub funders: Vec<(String, u64)>,
+}
+
+/// Where and when a transaction landed
+#[derive(Debug, Clone, Copy)]
+pub struct Landing {
+    pub slot: u64,
+    pub tx_index: u64,
+    pub seen_at: DateTime<Utc>,
+}
+
+/// Counters shared by all stages
+#[derive(Default)]
+pub struct PipelineMetrics {
+    pub received: AtomicU64,
+    pub decoded: AtomicU64,
+    pub events: AtomicU64,
+    pub discovery_updates: AtomicU64,
+    pub discovery_dropped: AtomicU64,
+    pub mint_updates: AtomicU64,
+    pub mint_dropped: AtomicU64,
+}
+
+/// Output side of the decode stage
+#[derive(Clone)]
+pub struct StageSinks {
+    pub events: mpsc::Sender<RawEvent>,
+    pub discovery: Option<mpsc::Sender<DiscoveryUpdate>>,
+    pub mints: Option<mpsc::Sender<MintActivity>>,
+    pub policy: QueueFullPolicy,
+    pub metrics: Arc<PipelineMetrics>,
+}
+
+impl StageSinks {
+    pub fn discovery_enabled(&self) -> bool {
+        self.discovery.is_some()
+    }
+
+    /// Queue a tracked-wallet event (always waits for space)
+    pub async fn send_event(&self, event: RawEvent) -> Result<()> {
+        self.events
+            .send(event)
+            .await
+            .map_err(|_| anyhow::anyhow!("persist stage closed"))?;
+        self.metrics.events.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn mints_enabled(&self) -> bool {
+        self.mints.is_some()
+    }
+
+    /// Queue a discovery update, shedding it if the queue is full and the policy allows
+    pub async fn send_discovery(&self, update: DiscoveryUpdate) {
+        let Some(sender) = &self.discovery else {
+            return;
+        };
+        if self.offer(sender, update, &self.metrics.discovery_dropped, "Discovery").await {
+            self.metrics.discovery_updates.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Queue a mint's activity, shedding it like discovery updates
+    pub async fn send_mint(&self, activity: MintActivity) {
+        let Some(sender) = &self.mints else {
+            return;
+        };
+        if self.offer(sender, activity, &self.metrics.mint_dropped, "Mint").await {
+            self.metrics.mint_updates.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Send discovery-only work, or drop it when the queue is full and the policy allows
+    async fn offer<T>(&self, sender: &mpsc::Sender<T>, item: T, dropped: &AtomicU64, queue: &str) -> bool {
+        match self.policy {
+            QueueFullPolicy::Block => sender.send(item).await.is_ok(),
+            QueueFullPolicy::DropDiscovery => match sender.try_send(item) {
+                Ok(()) => true,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    let dropped = dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                    if dropped % 1000 == 1 {
+                        warn!("⚠️  {} queue full, dropped {} updates so far", queue, dropped);
+                    }
+                    false
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            },
+        }
+    }
+}
+
+/// Channels connecting the stages, created from `PipelineConfig`
+pub struct Pipeline {
+    pub tx_sender: mpsc::Sender<TxMessage>,
+    pub tx_receiver: mpsc::Receiver<TxMessage>,
+    pub event_receiver: mpsc::Receiver<RawEvent>,
+    pub discovery_receiver: Option<mpsc::Receiver<DiscoveryUpdate>>,
+    pub mint_receiver: Option<mpsc::Receiver<MintActivity>>,
+    pub sinks: StageSinks,
+}
+
+impl Pipeline {
+    pub fn new(config: &PipelineConfig, discovery_enabled: bool, mints_enabled: bool) -> Self {
+        let (tx_sender, tx_receiver) = mpsc::channel(config.tx_queue_capacity.max(1));
+        let (event_sender, event_receiver) = mpsc::channel(config.event_queue_capacity.max(1));
+        let (discovery_sender, discovery_receiver) = if discovery_enabled {
+            let (s, r) = mpsc::channel(config.discovery_queue_capacity.max(1));
+            (Some(s), Some(r))
+        } else {
+            (None, None)
+        };
+        let (mint_sender, mint_receiver) = if mints_enabled {
+            let (s, r) = mpsc::channel(config.mint_queue_capacity.max(1));
+            (Some(s), Some(r))
+        } else {
+            (None, None)
+        };
+
+        Self {
+            tx_sender,
+            tx_receiver,
+            event_receiver,
+            discovery_receiver,
+            mint_receiver,
+            sinks: StageSinks {
+                events: event_sender,
+                discovery: discovery_sender,
+                mints: mint_sender,
+                policy: config.on_full,
+                metrics: Arc::new(PipelineMetrics::default()),
+            },
+        }
+    }
+}
+
+/// Persist stage: move decoded events into the batch buffer drained by the flusher.
+/// Once `batch_size` events are buffered it stops receiving until the flusher
+/// has drained them, so a slow database backs up into the event queue.
+pub async fn run_event_stage(
+    mut receiver: mpsc::Receiver<RawEvent>,
+    buffer: Arc<Mutex<Vec<RawEvent>>>,
+    flush_wake: Arc<Notify>,
+    drained: Arc<Notify>,
+    batch_size: usize,
+) {
+    while let Some(event) = receiver.recv().await {
+        let len = {
+            let mut buf = buffer.lock().await;
+            buf.push(event);
+            buf.len()
+        };
+        if len < batch_size {
+            continue;
+        }
+
+        info!("📦 Buffer full ({} events), triggering flush", len);
+        // Re-checked because a permit left by an earlier timed flush wakes us at once
+        while buffer.lock().await.len() >= batch_size {
+            flush_wake.notify_one();
+            drained.notified().await;
+        }
+    }
+    info!("Event stage stopped: decode stage closed");
+}
+
+/// Drain the buffer and write it to the database, spilling to the journal on failure.
+/// Returns false if the database write failed.
+async fn flush_buffer(buffer: &Mutex<Vec<RawEvent>>, pool: &db::Pool, journal: &SpillJournal) -> bool {
+    let events: Vec<_> = buffer.lock().await.drain(..).collect();
+    if events.is_empty() {
+        return true;
+    }
+    match bulk_insert_raw_events(pool, &events).await {
+        Ok(inserted) => {
+            info!("💾 Flushed {} events to database ({} new)", events.len(), inserted);
+            true
+        }
+        Err(e) => {
+            error!("Failed to flush {} events: {}, spilling to {}", events.len(), e, journal.path().display());
+            if let Err(e) = journal.append(&events) {
+                error!("❌ Failed to spill {} events to journal: {}", events.len(), e);
+            }
+            false
+        }
+    }
+}
+
+/// Re-insert spilled events once the database is reachable again
+async fn replay_journal(pool: &db::Pool, journal: &SpillJournal) {
+    if !journal.has_pending() || db::health_check(pool).await.is_err() {
+        return;
+    }
+    match journal.replay(pool).await {
+        Ok(0) => {}
+        Ok(count) => info!("♻️  Replayed {} spilled events from {}", count, journal.path().display()),
+        Err(e) => warn!("Spill journal replay failed, will retry: {}", e),
+    }
+}
+
+/// Flush the buffer every `period`, whenever the event stage reports it full,
+/// and one final time when `stop` fires. `drained` is notified after each flush.
+pub async fn run_flusher(
+    buffer: Arc<Mutex<Vec<RawEvent>>>,
+    pool: db::Pool,
+    journal: SpillJournal,
+    flush_wake: Arc<Notify>,
+    drained: Arc<Notify>,
+    period: Duration,
+    mut stop: oneshot::Receiver<()>,
+) {
+    let mut ticker = interval(period);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = flush_wake.notified() => {
+                ticker.reset();
+            }
+            _ = &mut stop => {
+                if flush_buffer(&buffer, &pool, &journal).await {
+                    replay_journal(&pool, &journal).await;
+                }
+                drained.notify_one();
+                info!("💾 Final flush complete");
+                return;
+            }
+        }
+        let flushed = flush_buffer(&buffer, &pool, &journal).await;
+        drained.notify_one();
+        if flushed {
+            replay_journal(&pool, &journal).await;
+        }
+    }
+}
+
+/// Persist stage: aggregate discovery updates in memory and flush them to the
+/// discovery database every `flush_every`, or sooner once `max_pending` wallets
+/// have deltas waiting
+pub async fn run_discovery_stage(
+    mut receiver: mpsc::Receiver<DiscoveryUpdate>,
+    pool: db::Pool,
+    flush_every: Duration,
+    max_pending: usize,
+    scoring: Arc<dyn ScoringModel>,
+    classes: ClassThresholds,
+) {
+    let mut aggregator = DiscoveryAggregator::with_scoring(scoring).with_class_thresholds(classes);
+    let mut ticker = interval(flush_every);
+
+    loop {
+        tokio::select! {
+            update = receiver.recv() => match update {
+                Some(update) => {
+                    aggregator.record(
+                        &update.wallet,
+                        &update.action,
+                        update.sol_amount,
+                        update.token_amount,
+                        update.mint.as_deref(),
+                        update.seen_at,
+                    );
+                    if let Some(mint) = update.mint.as_deref() {
+                        aggregator.record_slot(&update.wallet, &update.action, mint, update.slot, update.tx_index);
+                    }
+                    for (funder, lamports) in &update.funders {
+                        aggregator.record_funding(&update.wallet, funder, *lamports, update.seen_at);
+                    }
+                    if aggregator.len() < max_pending {
+                        continue;
+                    }
+                }
+                None => {
+                    aggregator.settle_slots();
+                    flush_discovery(&mut aggregator, &pool).await;
+                    break;
+                }
+            },
+            _ = ticker.tick() => {}
+        }
+        flush_discovery(&mut aggregator, &pool).await;
+    }
+    info!("Discovery stage stopped: decode stage closed");
+}
+
+/// The per-mint trackers fed by the mint stage; each can be turned off
+pub struct MintTrackers {
+    pub creators: Option<CreatorTracker>,
+    pub features: Option<MintFeatureAggregator>,
+}
+
+/// Persist stage: follow mint creators (recording a DEV_SELL event through the
+/// event stage and alerting when one dumps) and mint features, and write the
+/// trackers out every `flush_every`
+pub async fn run_mint_stage(
+    mut receiver: mpsc::Receiver<MintActivity>,
+    pool: db::Pool,
+    events: mpsc::Sender<RawEvent>,
+    mut trackers: MintTrackers,
+    flush_every: Duration,
+) {
+    let mut ticker = interval(flush_every);
+
+    loop {
+        tokio::select! {
+            activity = receiver.recv() => match activity {
+                Some(activity) => {
+                    if let Some(features) = trackers.features.as_mut() {
+                        features.record(&activity);
+                    }
+                    let dump = trackers.creators.as_mut().and_then(|c| c.record(&activity));
+                    if let Some(dump) = dump {
+                        warn!(
+                            "🚨 DEV DUMP: creator {} of {} has moved out {:.1}% of supply (sold {}, transferred {}) in {}",
+                            dump.creator,
+                            dump.mint,
+                            dump.supply_fraction * 100.0,
+                            dump.sold,
+                            dump.transferred,
+                            dump.sig
+                        );
+                        if events.send(dump.event()).await.is_err() {
+                            warn!("Event stage closed, DEV_SELL for {} not recorded", dump.mint);
+                        }
+                    }
+                    continue;
+                }
+                None => {
+                    flush_mints(&mut trackers, &pool).await;
+                    break;
+                }
+            },
+            _ = ticker.tick() => {}
+        }
+        flush_mints(&mut trackers, &pool).await;
+    }
+    info!("Mint stage stopped: decode stage closed");
+}
+
+// Changes stay marked on failure and go out with the next flush
+async fn flush_mints(trackers: &mut MintTrackers, pool: &db::Pool) {
+    if let Some(creators) = trackers.creators.as_mut() {
+        match creators.flush(pool, Utc::now()).await {
+            Ok(0) => {}
+            Ok(written) => info!("🧑‍🍳 Creator flush: {} mints updated, {} followed", written, creators.len()),
+            Err(e) => warn!("Failed to flush mint creators, will retry: {}", e),
+        }
+    }
+    if let Some(features) = trackers.features.as_mut() {
+        match features.flush(pool, Utc::now()).await {
+            Ok(0) => {}
+            Ok(written) => info!("🧮 Mint features flush: {} mints updated, {} followed", written, features.len()),
+            Err(e) => warn!("Failed to flush mint features, will retry: {}", e),
+        }
+    }
+}
+
+/// The `profit_score` model configured under `[scoring]`
+pub fn scoring_model(config: &ScoringConfig) -> Arc<dyn ScoringModel> {
+    match config.model {
+        ScoringModelKind::RiskAdjusted => Arc::new(RiskAdjusted {
+            sharpe_weight: config.sharpe_weight,
+            median_roi_weight: config.median_roi_weight,
+            consistency_weight: config.consistency_weight,
+            drawdown_weight: config.drawdown_weight,
+            recency_half_life_days: config.recency_half_life_days,
+            confidence_trades: config.confidence_trades,
+        }),
+        ScoringModelKind::Legacy => Arc::new(Legacy),
+    }
+}
+
+/// The wallet classification thresholds configured under `[classification]`
+pub fn class_thresholds(config: &ClassificationConfig) -> ClassThresholds {
+    ClassThresholds {
+        min_sandwiches: config.min_sandwiches,
+        min_sniper_buys: config.min_sniper_buys,
+        sniper_buy_share: config.sniper_buy_share,
+        hf_trades_per_day: config.hf_trades_per_day,
+    }
+}
+
+async fn flush_discovery(aggregator: &mut DiscoveryAggregator, pool: &db::Pool) {
+    if !aggregator.has_pending() {
+        return;
+    }
+    match aggregator.flush(pool).await {
+        Ok(stats) => {
+            if stats.new_wallets > 0 {
+                info!("🆕 {} NEW WALLETS DISCOVERED", stats.new_wallets);
+            }
+            info!(
+                "🔎 Discovery flush: {} wallets updated, {} positions opened, {} closed, {} unmatched sells",
+                stats.wallets, stats.positions_opened, stats.positions_closed, stats.unmatched_sells
+            );
+            if stats.signalled_wallets > 0 {
+                info!("🤖 Sniper/sandwich signals for {} wallets", stats.signalled_wallets);
+            }
+            if stats.clustered_wallets > 0 {
+                info!("🕸️  {} wallets linked into sybil clusters", stats.clustered_wallets);
+            }
+        }
+        // Deltas stay in the aggregator and go out with the next flush
+        Err(e) => warn!("Failed to flush discovery stats for {} wallets, will retry: {}", aggregator.len(), e),
+    }
+}
+
+fn queue_depth<T>(sender: &mpsc::Sender<T>) -> (usize, usize) {
+    let max = sender.max_capacity();
+    (max - sender.capacity(), max)
+}
+
+/// Periodically log queue depths and stage counters
+pub async fn report_metrics(
+    tx_sender: mpsc::Sender<TxMessage>,
+    sinks: StageSinks,
+    interval_secs: u64,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut ticker = interval(Duration::from_secs(interval_secs.max(1)));
+    ticker.tick().await;
+    loop {
+        // Exit on shutdown so the held senders don't keep the queues open
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.changed() => return,
+        }
+        let metrics = &sinks.metrics;
+        let (tx_depth, tx_max) = queue_depth(&tx_sender);
+        let (event_depth, event_max) = queue_depth(&sinks.events);
+        let optional_depth = |sender: Option<(usize, usize)>| {
+            sender
+                .map(|(depth, max)| format!("{}/{}", depth, max))
+                .unwrap_or_else(|| "off".to_string())
+        };
+        let discovery = optional_depth(sinks.discovery.as_ref().map(queue_depth));
+        let mints = optional_depth(sinks.mints.as_ref().map(queue_depth));
+
+        info!(
+            "📈 Queues: tx={}/{} events={}/{} discovery={} mints={} | received={} decoded={} events={} discovery={} mints={} dropped={}",
+            tx_depth,
+            tx_max,
+            event_depth,
+            event_max,
+            discovery,
+            mints,
+            metrics.received.load(Ordering::Relaxed),
+            metrics.decoded.load(Ordering::Relaxed),
+            metrics.events.load(Ordering::Relaxed),
+            metrics.discovery_updates.load(Ordering::Relaxed),
+            metrics.mint_updates.load(Ordering::Relaxed),
+            metrics.discovery_dropped.load(Ordering::Relaxed) + metrics.mint_dropped.load(Ordering::Relaxed),
+        );
+
+        if tx_depth * 10 >= tx_max * 9 {
+            warn!("⚠️  Decode queue is {}% full, gRPC stream is being throttled", tx_depth * 100 / tx_max);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! Tracked wallet set with live reload from the `wallets`, `wallet_roles` and
+//! `watched_mints` tables
+//!
+//! The decode stage reads an immutable snapshot per transaction; the reloader
+//! swaps in a new snapshot on `NOTIFY wallets_changed` or on a periodic re-poll.
+//! SQLite has no LISTEN/NOTIFY, so there it only re-polls.
+
+use anyhow::Result;
+use common::config::Config;
+use sqlx::Row;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use tokio::sync::watch;
+use tokio::time::{sleep, Duration};
+use tracing::{info, warn};
+
+/// Postgres channel the `wallets`, `wallet_roles` and `watched_mints` triggers
+/// notify on (see migrations 0007, 0010 and 0011)
+#[cfg(feature = "postgres")]
+pub const WALLETS_CHANNEL: &str = "wallets_changed";
+
+#[cfg(feature = "postgres")]
+type WalletListener = sqlx::postgres::PgListener;
+#[cfg(feature = "sqlite")]
+type WalletListener = std::convert::Infallible;
+
+/// One consistent view of the tracked wallets, their aliases and roles, and the watched mints
+#[derive(Debug, Default)]
+pub struct TrackedWallets {
+    pub wallets: HashSet<String>,
+    pub aliases: HashMap<String, String>,
+    /// Roles of every wallet in `wallet_roles`, tracked or not, sorted
+    pub roles: HashMap<String, Vec<String>>,
+    /// Mints whose every trade is recorded, whoever the trader
+    pub watched_mints: HashSet<String>,
+}
+
+impl TrackedWallets {
+    pub fn len(&self) -> usize {
+        self.wallets.len()
+    }
+
+    pub fn roles_of(&self, wallet: &str) -> &[String] {
+        self.roles.get(wallet).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// `raw_events.wallet_roles` for the wallet
+    pub fn roles_label(&self, wallet: &str) -> Option<String> {
+        self.roles.get(wallet).map(|roles| roles.join(","))
+    }
+
+    fn label(&self, wallet: &str) -> String {
+        match self.aliases.get(wallet) {
+            Some(alias) => format!("{} ({})", alias, &wallet[..8.min(wallet.len())]),
+            None => wallet.to_string(),
+        }
+    }
+}
+
+/// Roles the `[roles]` config changes the handling of
+#[derive(Debug, Clone, Default)]
+pub struct RolePolicy {
+    unrecorded: HashSet<String>,
+    no_discovery: HashSet<String>,
+}
+
+impl RolePolicy {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            unrecorded: config.roles_where(|r| !r.record_events).into_iter().collect(),
+            no_discovery: config.roles_where(|r| r.exclude_from_discovery).into_iter().collect(),
+        }
+    }
+
+    /// False if any of the wallet's roles turns event recording off
+    pub fn records(&self, tracked: &TrackedWallets, wallet: &str) -> bool {
+        !tracked.roles_of(wallet).iter().any(|r| self.unrecorded.contains(r))
+    }
+
+    /// False if any of the wallet's roles keeps it out of discovery
+    pub fn discovers(&self, tracked: &TrackedWallets, wallet: &str) -> bool {
+        !tracked.roles_of(wallet).iter().any(|r| self.no_discovery.contains(r))
+    }
+}
+
+/// Shared handle to the current snapshot
+#[derive(Clone, Default)]
+pub struct WalletRegistry {
+    current: Arc<RwLock<Arc<TrackedWallets>>>,
+}
+
+impl WalletRegistry {
+    pub fn new(initial: TrackedWallets) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(initial))),
+        }
+    }
+
+    /// Cheap clone of the current snapshot
+    pub fn snapshot(&self) -> Arc<TrackedWallets> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn replace(&self, next: TrackedWallets) {
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(next);
+    }
+}
+
+pub async fn load_tracked_wallets(pool: &db::Pool) -> Result<TrackedWallets> {
+    let rows = sqlx::query("SELECT wallet, alias FROM wallets WHERE is_tracked")
+        .fetch_all(pool)
+        .await?;
+
+    let mut tracked = TrackedWallets::default();
+    for row in rows {
+        let wallet: String = row.get("wallet");
+        let alias: Option<String> = row.get("alias");
+
+        if let Some(alias) = alias {
+            tracked.aliases.insert(wallet.clone(), alias);
+        }
+        tracked.wallets.insert(wallet);
+    }
+
+    let roles: Vec<(String, String)> = sqlx::query_as("SELECT wallet, role FROM wallet_roles ORDER BY role")
+        .fetch_all(pool)
+        .await?;
+    for (wallet, role) in roles {
+        tracked.roles.entry(wallet).or_default().push(role);
+    }
+
+    tracked.watched_mints = sqlx::query_scalar("SELECT mint FROM watched_mints")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect();
+
+    Ok(tracked)
+}
+
+/// Reload from the database, log the diff and swap the snapshot if anything changed
+async fn reload(pool: &db::Pool, registry: &WalletRegistry) {
+    let next = match load_tracked_wallets(pool).await {
+        Ok(next) => next,
+        Err(e) => {
+            warn!("Failed to reload tracked wallets: {}", e);
+            return;
+        }
+    };
+    let current = registry.snapshot();
+
+    let added: Vec<_> = next.wallets.difference(&current.wallets).collect();
+    let removed: Vec<_> = current.wallets.difference(&next.wallets).collect();
+    let renamed = next
+        .aliases
+        .iter()
+        .filter(|(w, a)| current.wallets.contains(*w) && current.aliases.get(*w) != Some(*a))
+        .count();
+    let reroled: Vec<_> = next
+        .roles
+        .keys()
+        .chain(current.roles.keys())
+        .filter(|w| next.roles.get(*w) != current.roles.get(*w))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+
+    let watch_changed = next.watched_mints != current.watched_mints;
+
+    if added.is_empty() && removed.is_empty() && renamed == 0 && reroled.is_empty() && !watch_changed {
+        return;
+    }
+
+    for wallet in &added {
+        info!("➕ Now tracking wallet: {}", next.label(wallet));
+    }
+    for wallet in &removed {
+        info!("➖ Stopped tracking wallet: {}", current.label(wallet));
+    }
+    for wallet in &reroled {
+        info!("🏷️  Roles of {}: [{}]", next.label(wallet), next.roles_of(wallet).join(", "));
+    }
+    for mint in next.watched_mints.difference(&current.watched_mints) {
+        info!("👁️  Now watching mint: {}", mint);
+    }
+    for mint in current.watched_mints.difference(&next.watched_mints) {
+        info!("🙈 Stopped watching mint: {}", mint);
+    }
+    info!(
+        "👥 Tracked wallets reloaded: {} total (+{} / -{} / {} alias changes / {} role changes)",
+        next.len(),
+        added.len(),
+        removed.len(),
+        renamed,
+        reroled.len()
+    );
+
+    registry.replace(next);
+}
+
+/// Keep the registry in sync with the `wallets`, `wallet_roles` and
+/// `watched_mints` tables until shutdown.
+///
+/// Reloads on every `NOTIFY wallets_changed` and every `poll_secs` regardless,
+/// so missed notifications (listener reconnects, trigger not installed) still
+/// get picked up.
+pub async fn run_wallet_reloader(
+    pool: db::Pool,
+    registry: WalletRegistry,
+    poll_secs: u64,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let poll = Duration::from_secs(poll_secs.max(1));
+    let mut listener: Option<WalletListener> = None;
+    #[cfg(feature = "sqlite")]
+    info!("👂 Polling for wallet changes every {}s", poll.as_secs());
+
+    loop {
+        if listener.is_none() {
+            listener = connect_listener(&pool).await;
+        }
+
+        tokio::select! {
+            _ = shutdown.changed() => return,
+            _ = sleep(poll) => {}
+            notification = async {
+                match listener.as_mut() {
+                    Some(l) => wait_for_notification(l).await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                if let Err(e) = notification {
+                    warn!("Wallet listener error, reconnecting: {}", e);
+                    listener = None;
+                }
+            }
+        }
+
+        reload(&pool, &registry).await;
+    }
+}
+
+#[cfg(feature = "postgres")]
+async fn connect_listener(pool: &db::Pool) -> Option<WalletListener> {
+    match WalletListener::connect_with(pool).await {
+        Ok(mut l) => match l.listen(WALLETS_CHANNEL).await {
+            Ok(()) => {
+                info!("👂 Listening for wallet changes on '{}'", WALLETS_CHANNEL);
+                Some(l)
+            }
+            Err(e) => {
+                warn!("Failed to LISTEN on '{}', polling only: {}", WALLETS_CHANNEL, e);
+                None
+            }
+        },
+        Err(e) => {
+            warn!("Failed to open wallet listener, polling only: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+async fn connect_listener(_pool: &db::Pool) -> Option<WalletListener> {
+    None
+}
+
+#[cfg(feature = "postgres")]
+async fn wait_for_notification(listener: &mut WalletListener) -> Result<(), sqlx::Error> {
+    listener.recv().await.map(|_| ())
+}
+
+#[cfg(feature = "sqlite")]
+async fn wait_for_notification(listener: &mut WalletListener) -> Result<(), sqlx::Error> {
+    match *listener {}
+}
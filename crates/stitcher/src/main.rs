@@ -0,0 +1,64 @@
+//! Trade stitcher: turns tracked wallets' raw_events into `trades` rows
+//!
+//!   cargo run -p stitcher              # follow new events every `stitcher.interval_secs`
+//!   cargo run -p stitcher -- --once    # one incremental pass
+//!   cargo run -p stitcher -- --rebuild # recompute every trade from scratch
+
+use anyhow::{bail, Result};
+use common::{config::Config, logging};
+use db::migrations::{MigrationMode, Schema};
+use db::trades::{self, StitchStats};
+use tokio::time::{interval, Duration};
+use tracing::{error, info};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    logging::init();
+
+    let mode = std::env::args().nth(1);
+    let config = Config::load("configs/config.example.toml")?;
+    let migration_mode = if config.database.migrate_check_only {
+        MigrationMode::Check
+    } else {
+        MigrationMode::Apply
+    };
+    let pool = db::connect(Some(&config.database.url), Schema::Main, migration_mode).await?;
+
+    match mode.as_deref() {
+        Some("--rebuild") => {
+            info!("🧵 Rebuilding all trades from raw_events...");
+            log_stats(&trades::rebuild(&pool).await?);
+        }
+        Some("--once") => log_stats(&trades::stitch_new_events(&pool).await?),
+        None => {
+            let period = Duration::from_secs(config.stitcher.interval_secs.max(1));
+            info!("🧵 Stitching new events every {}s", period.as_secs());
+            let mut ticker = interval(period);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {}
+                    _ = tokio::signal::ctrl_c() => {
+                        info!("🛑 Received Ctrl+C, stopping");
+                        break;
+                    }
+                }
+                match trades::stitch_new_events(&pool).await {
+                    Ok(stats) if stats.pairs > 0 => log_stats(&stats),
+                    Ok(_) => {}
+                    // Watermark didn't move, so the same events are retried next tick
+                    Err(e) => error!("Stitch pass failed: {}", e),
+                }
+            }
+        }
+        Some(other) => bail!("Unknown argument '{}' (expected --once or --rebuild)", other),
+    }
+
+    Ok(())
+}
+
+fn log_stats(stats: &StitchStats) {
+    info!(
+        "🧵 Stitched {} wallet/mint pairs: {} trades ({} closed), {} unmatched sells, up to event {}",
+        stats.pairs, stats.trades, stats.closed, stats.unmatched_sells, stats.last_event_id
+    );
+}
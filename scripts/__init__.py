@@ -1 +0,0 @@
-# Generated Yellowstone gRPC proto files